@@ -99,6 +99,10 @@ pub enum DiagnosticCategory {
     Fs,
     /// oro::parse
     Parse,
+    /// oro::resolve
+    Resolve,
+    /// oro::integrity
+    Integrity,
 }
 
 pub trait AsDiagnostic<T, E> {
@@ -72,16 +72,14 @@ impl fmt::Display for PackageSpec {
             Dir { path } => write!(f, "{}", path.display()),
             Git(info) => write!(f, "{}", info),
             Npm {
-                ref scope,
                 ref name,
                 ref requested,
+                ..
             } => {
-                if let Some(scope) = scope {
-                    write!(f, "@{}/", scope)?;
-                }
+                // the name of scoped packages already starts with their scope
                 write!(f, "{}", name)?;
                 if let Some(req) = requested {
-                    write!(f, "{}", req)?;
+                    write!(f, "@{}", req)?;
                 }
                 Ok(())
             }
@@ -134,3 +132,18 @@ where
         }),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_npm() {
+        let display = |spec: &str| spec.parse::<PackageSpec>().unwrap().to_string();
+
+        assert_eq!(display("hello-world"), "hello-world");
+        assert_eq!(display("hello-world@latest"), "hello-world@latest");
+        // the name of scoped packages already includes their scope
+        assert_eq!(display("@hello/world@1.0.0"), "@hello/world@1.0.0");
+    }
+}
@@ -1,5 +1,6 @@
 use crate::commands::{
-    add, clean, clone, discord, info, init, list, login, node, outdated, run, search,
+    add, audit, clean, clone, dedupe, discord, info, init, list, login, node, outdated, pack,
+    publish, run, search, stat, store, tag, update, why,
 }; // remove outdated later
 use async_trait::async_trait;
 use clap::{crate_authors, crate_description, crate_name, crate_version, Parser, Subcommand};
@@ -25,8 +26,17 @@ pub enum VoltSubCmd {
     Run(run::Run),
     Info(info::Info),
     Node(node::Node),
+    Store(store::Store),
     Outdated(outdated::Outdated), // remove later???
     List(list::List),             // remove later???
+    Why(why::Why),
+    Dedupe(dedupe::Dedupe),
+    Update(update::Update),
+    Audit(audit::Audit),
+    Stat(stat::Stat),
+    Pack(pack::Pack),
+    Publish(publish::Publish),
+    Tag(tag::Tag),
 }
 
 #[async_trait]
@@ -43,8 +53,17 @@ impl VoltCommand for VoltSubCmd {
             Self::Run(x) => x.exec(config).await,
             Self::Info(x) => x.exec(config).await,
             Self::Node(x) => x.exec(config).await,
+            Self::Store(x) => x.exec(config).await,
             Self::Outdated(x) => x.exec(config).await, // remove later
             Self::List(x) => x.exec(config).await,     // remove later
+            Self::Why(x) => x.exec(config).await,
+            Self::Dedupe(x) => x.exec(config).await,
+            Self::Update(x) => x.exec(config).await,
+            Self::Audit(x) => x.exec(config).await,
+            Self::Stat(x) => x.exec(config).await,
+            Self::Pack(x) => x.exec(config).await,
+            Self::Publish(x) => x.exec(config).await,
+            Self::Tag(x) => x.exec(config).await,
         }
     }
 }
@@ -14,23 +14,137 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use crate::core::utils::errors::VoltError;
+use crate::core::{
+    classes::meta::Meta, http::RetryPolicy, store::LinkStrategy, utils::errors::VoltError,
+};
 
 use clap::Parser;
 use ssri::Algorithm;
-use std::{env, path::PathBuf};
+use std::{env, path::PathBuf, time::Duration};
 
 #[derive(Debug, Clone, Parser)]
 pub struct VoltConfig {
     /// Path to current working directory
     #[clap(short, long)]
     cwd: Option<PathBuf>,
+
+    /// Registry to fetch package metadata from
+    #[clap(long, default_value = "https://registry.npmjs.org")]
+    registry: String,
+
+    /// Token to authenticate to the registry with (defaults to `NPM_TOKEN`)
+    #[clap(long)]
+    token: Option<String>,
+
+    /// How many times a failed request is retried
+    #[clap(long, default_value = "4")]
+    fetch_retries: u32,
+
+    /// Delay before retrying a failed request for the first time, in milliseconds
+    #[clap(long, default_value = "1000")]
+    fetch_retry_min_delay: u64,
+
+    /// Maximum delay between two attempts of a request, in milliseconds
+    #[clap(long, default_value = "30000")]
+    fetch_retry_max_delay: u64,
+
+    /// Time allowed for a request to complete (including its body), in seconds
+    #[clap(long, default_value = "300")]
+    fetch_timeout: u64,
+
+    /// Time allowed to connect to a server, in seconds
+    #[clap(long, default_value = "30")]
+    connect_timeout: u64,
+
+    /// Maximum number of requests in flight at the same time
+    #[clap(long, default_value = "16")]
+    network_concurrency: usize,
+
+    /// Maximum number of requests in flight to the same host at the same time
+    #[clap(long, default_value = "8")]
+    max_connections_per_host: usize,
+
+    /// Maximum number of packages being extracted or linked at the same time
+    #[clap(long, default_value = "8")]
+    fs_concurrency: usize,
+
+    /// Proxy for `http` requests (defaults to `HTTP_PROXY`)
+    #[clap(long)]
+    proxy: Option<String>,
+
+    /// Proxy for `https` requests (defaults to `HTTPS_PROXY`, then to `--proxy`)
+    #[clap(long)]
+    https_proxy: Option<String>,
+
+    /// Comma separated hosts, domains and IP ranges to reach without a proxy (defaults to `NO_PROXY`)
+    #[clap(long)]
+    noproxy: Option<String>,
+
+    /// PEM file with the certificate authorities to trust in addition to the built-in ones
+    #[clap(long)]
+    cafile: Option<PathBuf>,
+
+    /// Verify the certificates of https servers
+    #[clap(long, default_value = "true", parse(try_from_str))]
+    strict_ssl: bool,
+
+    /// PEM file with the client certificate to present to servers (mutual TLS)
+    #[clap(long, requires = "key")]
+    cert: Option<PathBuf>,
+
+    /// PEM file with the private key of the client certificate
+    #[clap(long, requires = "cert")]
+    key: Option<PathBuf>,
+
+    /// How packages are linked from the global store into `node_modules`
+    #[clap(long, arg_enum, default_value = "hardlink")]
+    link_strategy: LinkStrategy,
+
+    /// Keep an unpacked copy of every package in the global store for faster installs
+    #[clap(long)]
+    store_unpacked: bool,
+
+    /// Never use the network, resolve from the lockfile and cached metadata only
+    #[clap(long, conflicts_with = "prefer-offline")]
+    offline: bool,
+
+    /// Use cached metadata without revalidating it, unless it doesn't satisfy the requested version
+    #[clap(long)]
+    prefer_offline: bool,
+
+    /// Print machine readable JSON instead of human readable output
+    #[clap(long, global = true)]
+    json: bool,
+
+    /// Only print errors
+    #[clap(short, long, global = true, conflicts_with_all = &["verbose", "debug"])]
+    silent: bool,
+
+    /// Print more details about what volt is doing
+    #[clap(short, long, global = true)]
+    verbose: bool,
+
+    /// Print debugging information
+    #[clap(long, global = true)]
+    debug: bool,
+
+    /// Disable colored output (also disabled by the `NO_COLOR` environment variable)
+    #[clap(long, global = true)]
+    no_color: bool,
+}
+
+/// First non-empty environment variable out of `names`.
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.trim().is_empty())
 }
 
 impl VoltConfig {
     pub const _OS: &'static str = env::consts::OS;
     pub const VOLT_HOME: &'static str = ".volt";
-    pub const _VOLT_LOCK: &'static str = "volt.lock";
+    pub const VOLT_LOCK: &'static str = "volt.lock";
 
     pub fn home(&self) -> miette::Result<PathBuf> {
         Ok(dirs::home_dir().ok_or(VoltError::GetHomeDirError)?)
@@ -47,8 +161,8 @@ impl VoltConfig {
     }
 
     /// Path to the volt lockfile (defaults to `./volt.lock`)
-    pub fn _lockfile(&self) -> miette::Result<PathBuf> {
-        Ok(self.cwd()?.join(Self::_VOLT_LOCK))
+    pub fn lockfile(&self) -> miette::Result<PathBuf> {
+        Ok(self.cwd()?.join(Self::VOLT_LOCK))
     }
 
     /// Path to the `node_modules` directory (defaults to `./node_modules`)
@@ -61,6 +175,126 @@ impl VoltConfig {
         Ok(self.home()?.join(Self::VOLT_HOME))
     }
 
+    /// Url of the registry (defaults to `https://registry.npmjs.org`), without a trailing `/`
+    pub fn registry(&self) -> &str {
+        self.registry.trim_end_matches('/')
+    }
+
+    /// Token to authenticate to the registry with, if any
+    pub fn token(&self) -> Option<String> {
+        self.token.clone().or_else(|| env_var(&["NPM_TOKEN"]))
+    }
+
+    /// How failed requests are retried
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            retries: self.fetch_retries,
+            min_delay: Duration::from_millis(self.fetch_retry_min_delay),
+            max_delay: Duration::from_millis(self.fetch_retry_max_delay),
+        }
+    }
+
+    /// Time allowed for a request to complete (defaults to 5 minutes)
+    pub fn fetch_timeout(&self) -> Duration {
+        Duration::from_secs(self.fetch_timeout)
+    }
+
+    /// Time allowed to connect to a server (defaults to 30 seconds)
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout)
+    }
+
+    /// Maximum number of requests in flight at the same time (defaults to 16, at least 1)
+    pub fn network_concurrency(&self) -> usize {
+        self.network_concurrency.max(1)
+    }
+
+    /// Maximum number of requests in flight to a single host (defaults to 8, at least 1)
+    pub fn max_connections_per_host(&self) -> usize {
+        self.max_connections_per_host.max(1)
+    }
+
+    /// Maximum number of packages extracted or linked at the same time (defaults to 8, at least 1)
+    pub fn fs_concurrency(&self) -> usize {
+        self.fs_concurrency.max(1)
+    }
+
+    /// Proxy for `http` requests
+    pub fn proxy(&self) -> Option<String> {
+        self.proxy
+            .clone()
+            .or_else(|| env_var(&["HTTP_PROXY", "http_proxy"]))
+    }
+
+    /// Proxy for `https` requests
+    pub fn https_proxy(&self) -> Option<String> {
+        self.https_proxy
+            .clone()
+            .or_else(|| env_var(&["HTTPS_PROXY", "https_proxy"]))
+            .or_else(|| self.proxy())
+    }
+
+    /// Hosts to reach without a proxy
+    pub fn noproxy(&self) -> Vec<String> {
+        self.noproxy
+            .clone()
+            .or_else(|| env_var(&["NO_PROXY", "no_proxy"]))
+            .map(|list| {
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|host| !host.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Extra certificate authorities to trust
+    pub fn cafile(&self) -> Option<&PathBuf> {
+        self.cafile.as_ref()
+    }
+
+    /// Whether the certificates of https servers are verified (defaults to `true`)
+    pub fn strict_ssl(&self) -> bool {
+        self.strict_ssl
+    }
+
+    /// Client certificate and its private key, if any
+    pub fn client_cert(&self) -> Option<(&PathBuf, &PathBuf)> {
+        self.cert.as_ref().zip(self.key.as_ref())
+    }
+
+    /// How output is printed
+    pub fn meta(&self) -> Meta {
+        Meta {
+            verbose: self.verbose || self.debug,
+            debug: self.debug,
+            silent: self.silent,
+            no_color: self.no_color || self.json || env::var_os("NO_COLOR").is_some(),
+            json: self.json,
+        }
+    }
+
+    /// How packages are linked from the global store (defaults to `hardlink`)
+    pub fn link_strategy(&self) -> LinkStrategy {
+        self.link_strategy
+    }
+
+    /// Whether an unpacked copy of every package is kept in the global store
+    pub fn store_unpacked(&self) -> bool {
+        self.store_unpacked
+    }
+
+    /// Whether the network must not be used at all
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Whether cached metadata is used without revalidating it (implied by `--offline`)
+    pub fn prefer_offline(&self) -> bool {
+        self.prefer_offline || self.offline
+    }
+
     /// Calculate the hash of a tarball
     ///
     /// ## Examples
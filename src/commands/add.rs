@@ -16,11 +16,17 @@ limitations under the License.
 
 //! Add a package to the dependencies for your project.
 
-use std::{collections::HashMap, time::Instant};
+use std::{collections::HashMap, sync::Arc, time::Instant};
 
 use crate::{
     cli::{VoltCommand, VoltConfig},
+    core::http::HttpClient,
+    core::io::{write, write_json},
+    core::model::lock_file::LockFile,
     core::net::fetch_dep_tree,
+    core::progress::{resolve_spinner, InstallProgress},
+    core::store::{register_project, unpacked_names, Linker},
+    core::utils::errors::VoltError,
     core::utils::voltapi::VoltPackage,
     core::utils::{install_package, State},
 };
@@ -28,11 +34,12 @@ use crate::{
 use async_trait::async_trait;
 use clap::Parser;
 use colored::Colorize;
-use futures::{stream::FuturesUnordered, StreamExt, TryStreamExt};
-use indicatif::{ProgressBar, ProgressStyle};
+use futures::{stream::FuturesUnordered, TryStreamExt};
+use indicatif::HumanBytes;
 use miette::IntoDiagnostic;
 use package_spec::PackageSpec;
-use reqwest::Client;
+use serde::Serialize;
+use tokio::sync::Semaphore;
 
 /// Add a package to your project's dependencies
 #[derive(Debug, Parser)]
@@ -41,261 +48,328 @@ pub struct Add {
     packages: Vec<PackageSpec>,
 }
 
+/// Output of `volt add --json`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AddOutput {
+    added: Vec<AddedPackage>,
+    resolved: usize,
+    installed: usize,
+    from_store: usize,
+    downloaded: usize,
+    downloaded_bytes: u64,
+    skipped: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct AddedPackage {
+    name: String,
+    version: String,
+}
+
 #[async_trait]
 impl VoltCommand for Add {
     async fn exec(self, config: VoltConfig) -> miette::Result<()> {
-        // let global_lock_file = LockFile::load(config.home()?.join(".global.lock"), true).unwrap();
-
-        // let local_lock_file =
-
-        let bar = ProgressBar::new_spinner()
-            .with_style(ProgressStyle::default_spinner().template("{spinner:.cyan} {msg}"));
-
-        bar.enable_steady_tick(10);
-
-        let resolve_start = Instant::now();
-
-        let mut requested_packages = vec![];
-
-        // Fetch pre-flattened dependency trees from the registry
-        let responses = fetch_dep_tree(&self.packages, &bar).await?;
-
-        let mut tree: HashMap<String, VoltPackage> = HashMap::new();
-
-        for response in responses {
-            let _index = 0;
-
-            for package in &self.packages {
-                if let PackageSpec::Npm {
-                    name,
-                    scope,
-                    requested,
-                } = package
-                {
-                    // recieve the version of a package that has been requested from the response
-                    if *name == response.name {
-                        requested_packages.push(PackageSpec::Npm {
-                            scope: scope.to_owned(),
-                            name: name.to_owned(),
-                            requested: Some(package_spec::VersionSpec::Tag(
-                                response.version.clone(),
-                            )),
-                        });
-                    } else {
-                        requested_packages.push(PackageSpec::Npm {
-                            name: name.to_string(),
-                            scope: scope.to_owned(),
-                            requested: requested.to_owned(),
-                        });
-                    }
+        let mut lock_file = LockFile::load(config.lockfile()?)?;
+
+        let output = install(&config, &mut lock_file, &self.packages).await?;
+
+        lock_file.save()?;
+
+        write_json(&output, &config.meta())?;
+
+        // let (mut package_file, path) = PackageJson::get()?;
+
+        // for package in requested_packages.iter() {
+        //     package_file.add_dependency(package.to_owned());
+        // }
+
+        // // Save package.json
+        // package_file.save()?;
+
+        Ok(())
+    }
+}
+
+/// Resolve `packages` and install them along with their dependencies, locking them in
+/// `lock_file`. Saving the lockfile is left to the caller.
+pub(crate) async fn install(
+    config: &VoltConfig,
+    lock_file: &mut LockFile,
+    packages: &[PackageSpec],
+) -> miette::Result<AddOutput> {
+    let meta = config.meta();
+
+    let client = HttpClient::new(config)?;
+
+    let bar = resolve_spinner(&meta);
+
+    let resolve_start = Instant::now();
+
+    let mut requested_packages = vec![];
+
+    // Fetch pre-flattened dependency trees from the registry
+    let responses = fetch_dep_tree(packages, &bar, config, lock_file, &client).await?;
+
+    let mut tree: HashMap<String, VoltPackage> = HashMap::new();
+    let mut added = vec![];
+
+    for response in responses {
+        let _index = 0;
+
+        lock_file.add(&response);
+
+        added.push(AddedPackage {
+            name: response.name.clone(),
+            version: response.version.clone(),
+        });
+
+        for package in packages {
+            if let PackageSpec::Npm {
+                name,
+                scope,
+                requested,
+            } = package
+            {
+                // recieve the version of a package that has been requested from the response
+                if *name == response.name {
+                    requested_packages.push(PackageSpec::Npm {
+                        scope: scope.to_owned(),
+                        name: name.to_owned(),
+                        requested: Some(package_spec::VersionSpec::Tag(response.version.clone())),
+                    });
+                } else {
+                    requested_packages.push(PackageSpec::Npm {
+                        name: name.to_string(),
+                        scope: scope.to_owned(),
+                        requested: requested.to_owned(),
+                    });
                 }
             }
-
-            tree.extend(response.tree);
         }
 
-        bar.finish_and_clear();
+        tree.extend(response.tree);
+    }
+
+    bar.finish_and_clear();
+
+    let resolved = tree.len();
 
-        println!(
+    write(
+        &format!(
             "{} Resolved {} dependencies",
             format!("[{:.2}{}]", resolve_start.elapsed().as_secs_f32(), "s")
                 .truecolor(156, 156, 156)
                 .bold(),
-            tree.len().to_string().truecolor(196, 206, 255).bold()
-        );
+            resolved.to_string().truecolor(196, 206, 255).bold()
+        ),
+        &meta,
+    );
 
-        let install_start = Instant::now();
+    let install_start = Instant::now();
 
-        let nm_dir = config.node_modules()?;
-        let nm_volt_home = nm_dir.join(VoltConfig::VOLT_HOME);
+    let nm_dir = config.node_modules()?;
+    let nm_volt_home = nm_dir.join(VoltConfig::VOLT_HOME);
 
-        if !nm_dir.exists() {
-            std::fs::create_dir_all(&nm_volt_home).unwrap();
-        }
+    if !nm_dir.exists() {
+        std::fs::create_dir_all(&nm_volt_home).map_err(|e| VoltError::WriteFileError {
+            source: e,
+            name: nm_volt_home.display().to_string(),
+        })?;
+    }
 
-        let client = Client::builder().use_rustls_tls().build().unwrap();
+    let linker = Linker::new(config.link_strategy());
+    let fs_permits = Arc::new(Semaphore::new(config.fs_concurrency()));
 
-        let mut incompatible_packages = vec![];
+    let mut incompatible_packages = vec![];
 
-        // pnpm linking algorithm
-        for value in tree.values() {
-            // None means it's not platform-specific
-            // We get a list of platforms, and if our current OS isn't on this list - it means that we can skip this package
-            // this is only if the package is optional
+    // pnpm linking algorithm
+    for value in tree.values() {
+        // None means it's not platform-specific
+        // We get a list of platforms, and if our current OS isn't on this list - it means that we can skip this package
+        // this is only if the package is optional
 
-            if let Some(os) = &value.os {
-                if !os.contains(&"win32".to_string()) && !os.contains(&format!("!{}", "win32")) {
-                    incompatible_packages.push(format!("{}@{}", value.name, value.version));
-                    continue;
-                }
+        if let Some(os) = &value.os {
+            if !os.contains(&"win32".to_string()) && !os.contains(&format!("!{}", "win32")) {
+                incompatible_packages.push(format!("{}@{}", value.name, value.version));
+                continue;
             }
+        }
 
-            if let Some(architecture) = &value.cpu {
-                if !architecture.contains(&"x64".to_string()) {
-                    incompatible_packages.push(format!("{}@{}", value.name, value.version));
-                    continue;
-                }
+        if let Some(architecture) = &value.cpu {
+            if !architecture.contains(&"x64".to_string()) {
+                incompatible_packages.push(format!("{}@{}", value.name, value.version));
+                continue;
             }
+        }
 
-            let mut name = value.name.clone();
-            let mut scope: Option<String> = None;
-            let mut last: Option<String> = None;
-
-            if value.name.starts_with('@') {
-                // replace @ with +
-                name = name.replace('/', "+");
-
-                scope = Some(
-                    name.split('+')
-                        .map(|v| v.to_string())
-                        .collect::<Vec<String>>()
-                        .first()
-                        .unwrap()
-                        .to_string(),
-                );
-
-                last = Some(
-                    name.split('+')
-                        .map(|v| v.to_string())
-                        .collect::<Vec<String>>()
-                        .last()
-                        .unwrap()
-                        .to_string(),
-                );
-            }
+        let mut name = value.name.clone();
+        let mut scope: Option<String> = None;
+        let mut last: Option<String> = None;
+
+        if value.name.starts_with('@') {
+            // replace @ with +
+            name = name.replace('/', "+");
+
+            scope = Some(
+                name.split('+')
+                    .map(|v| v.to_string())
+                    .collect::<Vec<String>>()
+                    .first()
+                    .unwrap()
+                    .to_string(),
+            );
+
+            last = Some(
+                name.split('+')
+                    .map(|v| v.to_string())
+                    .collect::<Vec<String>>()
+                    .last()
+                    .unwrap()
+                    .to_string(),
+            );
+        }
 
-            std::fs::create_dir(nm_volt_home.join(format!("{}@{}", name, value.version)))
-                .into_diagnostic()?;
+        std::fs::create_dir_all(nm_volt_home.join(format!("{}@{}", name, value.version)))
+            .into_diagnostic()?;
+
+        std::fs::create_dir_all(
+            nm_volt_home
+                .join(format!("{}@{}", name, value.version))
+                .join("node_modules/"),
+        )
+        .into_diagnostic()?;
 
-            std::fs::create_dir(
+        if scope.is_none() {
+            std::fs::create_dir_all(
                 nm_volt_home
                     .join(format!("{}@{}", name, value.version))
-                    .join("node_modules/"),
+                    .join("node_modules/")
+                    .join(&name),
+            )
+            .into_diagnostic()?;
+        } else {
+            std::fs::create_dir_all(
+                nm_volt_home
+                    .join(format!("{}@{}", name, value.version))
+                    .join("node_modules/")
+                    .join(scope.as_ref().unwrap()),
             )
             .into_diagnostic()?;
 
-            if scope.is_none() {
-                std::fs::create_dir(
-                    nm_volt_home
-                        .join(format!("{}@{}", name, value.version))
-                        .join("node_modules/")
-                        .join(&name),
-                )
-                .into_diagnostic()?;
-            } else {
-                std::fs::create_dir(
-                    nm_volt_home
-                        .join(format!("{}@{}", name, value.version))
-                        .join("node_modules/")
-                        .join(scope.as_ref().unwrap()),
-                )
-                .into_diagnostic()?;
-
-                std::fs::create_dir(
-                    nm_volt_home
-                        .join(format!("{}@{}", name, value.version))
-                        .join("node_modules/")
-                        .join(scope.as_ref().unwrap())
-                        .join(&last.unwrap()),
-                )
-                .into_diagnostic()?;
-            }
-        }
-
-        for item in incompatible_packages {
-            tree.remove(&item);
+            std::fs::create_dir_all(
+                nm_volt_home
+                    .join(format!("{}@{}", name, value.version))
+                    .join("node_modules/")
+                    .join(scope.as_ref().unwrap())
+                    .join(&last.unwrap()),
+            )
+            .into_diagnostic()?;
         }
+    }
 
-        let total = tree.len();
-
-        let bar = ProgressBar::new(total as u64);
-
-        bar.set_style(
-            ProgressStyle::default_bar()
-                .template("[{bar:40.cyan/blue}] {pos:>7}/{len:7} {msg}")
-                .progress_chars("=>-"),
-        );
-
-        // todo: display progress bar for downloads that are taking time.
-        tree.values()
-            .map(|data| {
-                install_package(
-                    config.clone(),
-                    data.clone(),
-                    State {
-                        http_client: client.clone(),
-                    },
-                )
-            })
-            .collect::<FuturesUnordered<_>>()
-            .inspect(|_| bar.inc(1))
-            .try_collect::<Vec<_>>()
-            .await
-            .unwrap();
-
-        bar.finish_and_clear();
-
-        // for package in requested_packages.iter() {
-        //     if let PackageSpec::Npm {
-        //         name,
-        //         scope,
-        //         requested,
-        //     } = package
-        //     {
-        //         let mut node_modules_directory = config.node_modules().unwrap();
-
-        //         // path to the package directory
-        //         let mut package_directory = node_modules_directory
-        //             .join(".volt")
-        //             .join(format!("{}@{}", &name, requested.as_ref().unwrap()))
-        //             .join("node_modules/")
-        //             .join(&name);
-
-        //         // path to the symlink
-        //         let mut target_directory = node_modules_directory.join(name);
-
-        //         #[cfg(windows)]
-        //         junction::create(&package_directory, &target_directory).unwrap_or_else(|e| {
-        //             eprintln!(
-        //                 "target: {} destination: {}, {}",
-        //                 package_directory.display(),
-        //                 target_directory.display(),
-        //                 e
-        //             );
-        //             std::process::exit(1);
-        //         });
-
-        //         #[cfg(unix)]
-        //         std::os::unix::fs::symlink(package_directory, target_directory).unwrap_or_else(
-        //             |e| {
-        //                 eprintln!("{}", e);
-        //                 std::process::exit(1);
-        //             },
-        //         );
-        //     }
-        // }
+    for item in &incompatible_packages {
+        tree.remove(item);
+    }
 
-        println!(
-            "{} Installed {} dependencies",
+    let total = tree.len();
+
+    let progress = InstallProgress::new(total, &meta);
+
+    let unpacked_names = Arc::new(if config.store_unpacked() {
+        unpacked_names(&tree)
+    } else {
+        HashMap::new()
+    });
+
+    tree.values()
+        .map(|data| {
+            install_package(
+                config.clone(),
+                data.clone(),
+                State {
+                    http_client: client.clone(),
+                    linker: linker.clone(),
+                    fs_permits: fs_permits.clone(),
+                    progress: progress.clone(),
+                    unpacked_names: unpacked_names.clone(),
+                },
+            )
+        })
+        .collect::<FuturesUnordered<_>>()
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let summary = progress.finish();
+
+    // remember the project so that `volt store prune` keeps its packages
+    register_project(&config.volt_home()?, &config.cwd()?)?;
+
+    // for package in requested_packages.iter() {
+    //     if let PackageSpec::Npm {
+    //         name,
+    //         scope,
+    //         requested,
+    //     } = package
+    //     {
+    //         let mut node_modules_directory = config.node_modules().unwrap();
+
+    //         // path to the package directory
+    //         let mut package_directory = node_modules_directory
+    //             .join(".volt")
+    //             .join(format!("{}@{}", &name, requested.as_ref().unwrap()))
+    //             .join("node_modules/")
+    //             .join(&name);
+
+    //         // path to the symlink
+    //         let mut target_directory = node_modules_directory.join(name);
+
+    //         #[cfg(windows)]
+    //         junction::create(&package_directory, &target_directory).unwrap_or_else(|e| {
+    //             eprintln!(
+    //                 "target: {} destination: {}, {}",
+    //                 package_directory.display(),
+    //                 target_directory.display(),
+    //                 e
+    //             );
+    //             std::process::exit(1);
+    //         });
+
+    //         #[cfg(unix)]
+    //         std::os::unix::fs::symlink(package_directory, target_directory).unwrap_or_else(
+    //             |e| {
+    //                 eprintln!("{}", e);
+    //                 std::process::exit(1);
+    //             },
+    //         );
+    //     }
+    // }
+
+    write(
+        &format!(
+            "{} Installed {} dependencies {}",
             format!("[{:.2}{}]", install_start.elapsed().as_secs_f32(), "s")
                 .truecolor(156, 156, 156)
                 .bold(),
-            total.to_string().truecolor(196, 206, 255).bold()
-        );
-
-        // let (mut package_file, path) = PackageJson::get()?;
-
-        // for package in requested_packages.iter() {
-        //     package_file.add_dependency(package.to_owned());
-        // }
-
-        // // Save package.json
-        // package_file.save()?;
-
-        // // Save lockfiles
-        // // global_lock_file.save()?;
-        // // lock_file.save()?;
-
-        Ok(())
-    }
+            total.to_string().truecolor(196, 206, 255).bold(),
+            format!(
+                "({} from the store, {} downloaded, {})",
+                summary.cache_hits,
+                summary.downloads,
+                HumanBytes(summary.downloaded_bytes)
+            )
+            .truecolor(156, 156, 156)
+        ),
+        &meta,
+    );
+
+    Ok(AddOutput {
+        added,
+        resolved,
+        installed: total,
+        from_store: summary.cache_hits,
+        downloaded: summary.downloads,
+        downloaded_bytes: summary.downloaded_bytes,
+        skipped: incompatible_packages,
+    })
 }
@@ -14,168 +14,654 @@
     limitations under the License.
 */
 
-//! Handle an unknown command (can be listed in scripts).
+//! Check the installed packages for known vulnerabilities.
 
 use async_trait::async_trait;
-use miette::Result;
+use clap::{ArgEnum, Parser, Subcommand};
+use colored::{ColoredString, Colorize};
+use miette::{IntoDiagnostic, Result};
+use oro_node_semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::fix,
+    core::http::HttpClient,
+    core::io::{write, write_json},
+    core::model::lock_file::LockFile,
+    core::net::auth_headers,
+    core::utils::errors::VoltError,
+    core::utils::package::{DependencyKind, PackageJson},
+};
+
+/// Check the installed packages for known vulnerabilities
+#[derive(Debug, Parser)]
+pub struct Audit {
+    #[clap(subcommand)]
+    command: Option<AuditCommand>,
+
+    /// Only fail if there are vulnerabilities of this severity or higher
+    #[clap(long, arg_enum)]
+    audit_level: Option<Severity>,
+}
 
-use crate::cli::{VoltCommand, VoltConfig};
+#[derive(Debug, Subcommand)]
+enum AuditCommand {
+    Fix(fix::Fix),
+}
 
-pub struct Audit {}
+/// How severe a vulnerability is, from the least to the most.
+#[derive(
+    ArgEnum, Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Moderate,
+    High,
+    Critical,
+    /// A severity this version doesn't know of, ranked above the others so that it isn't
+    /// overlooked
+    #[serde(other)]
+    #[clap(skip)]
+    Unknown,
+}
 
-#[derive(Debug)]
-pub struct AuditObject {
-    _name: String,
-    _version: String,
-    _install: Vec<String>,
-    _remove: Vec<String>,
-    _metadata: HashMap<String, String>,
-    _requires: HashMap<String, String>,
-    _dependencies: HashMap<String, AuditDependency>,
+impl Severity {
+    fn colored(self, text: &str) -> ColoredString {
+        match self {
+            Severity::Info => text.normal(),
+            Severity::Low => text.bright_white(),
+            Severity::Moderate => text.bright_yellow(),
+            Severity::High => text.bright_red(),
+            Severity::Critical => text.bright_magenta().bold(),
+            Severity::Unknown => text.bold(),
+        }
+    }
 }
 
-#[derive(Debug)]
-pub struct AuditDependency {
-    _version: String,
-    _integrity: String,
-    _requires: HashMap<String, String>,
-    _dependencies: HashMap<String, AuditDependency>,
-    _dev: bool,
+// https://github.com/npm/cli/blob/latest/docs/content/commands/npm-audit.md#audit-endpoints
+/// An advisory of the bulk advisory endpoint.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Advisory {
+    pub id: u64,
+    pub title: String,
+    #[serde(default)]
+    pub url: String,
+    pub severity: Severity,
+    #[serde(rename(serialize = "vulnerableVersions", deserialize = "vulnerable_versions"))]
+    pub vulnerable_versions: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AuditResponse {
-    actions: Vec<String>,
-    advisories: HashMap<String, String>,
-    muted: Vec<String>,
-    metadata: AuditMetadata,
+/// An installed version of a package affected by advisories.
+#[derive(Debug, Serialize)]
+pub struct Vulnerability {
+    pub name: String,
+    pub version: String,
+    pub severity: Severity, // of its most severe advisory
+    pub advisories: Vec<Advisory>,
+    pub paths: Vec<Vec<String>>, // `name@version` of the packages leading to it
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize)]
 pub struct Vulnerabilities {
-    info: u128,
-    low: u128,
-    moderate: u128,
-    high: u128,
-    critical: u128,
+    info: usize,
+    low: usize,
+    moderate: usize,
+    high: usize,
+    critical: usize,
+    unknown: usize,
+}
+
+impl Vulnerabilities {
+    fn count(&mut self, severity: Severity) {
+        match severity {
+            Severity::Info => self.info += 1,
+            Severity::Low => self.low += 1,
+            Severity::Moderate => self.moderate += 1,
+            Severity::High => self.high += 1,
+            Severity::Critical => self.critical += 1,
+            Severity::Unknown => self.unknown += 1,
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AuditMetadata {
     vulnerabilities: Vulnerabilities,
-    dependencies: u128,
-    dev_dependencies: u128,
-    optional_dependencies: u128,
-    total_dependencies: u128,
-}
-
-// pub fn flatten_dependency_tree(
-//     package: &VoltPackage,
-//     volt_packages: &HashMap<String, VoltPackage>,
-// ) -> Vec<AuditDependency> {
-//     let mut packages: Vec<AuditDependency> = vec![];
-
-//     if package.dependencies.is_some() {
-//         for dep in package.dependencies.as_ref().unwrap().iter() {
-//             let dependency = volt_packages.get(dep).unwrap();
-
-//             let mut requires: HashMap<String, String> = HashMap::new();
-
-//             if dependency.dependencies.is_some() {
-//                 for de in dependency.dependencies.as_ref().unwrap() {
-//                     let pkg = volt_packages.get(de).unwrap();
-//                     requires.insert(de.to_owned(), pkg.version.clone());
-//                 }
-//             }
-
-//             let fdt = &mut flatten_dependency_tree(dependency, volt_packages);
-
-//             packages.push(AuditDependency {
-//                 version: dependency.version,
-//                 integrity: dependency.integrity,
-//                 requires: requires,
-//                 dependencies: HashMap::new()
-//                     .insert(
-//                         dep.to_owned(),
-//                         ,
-//                     )
-//                     .unwrap(),
-//                 dev: false,
-//             });
-//         }
-//     }
-
-//     packages
-// }
+    dependencies: usize,
+    dev_dependencies: usize,
+    optional_dependencies: usize,
+    total_dependencies: usize,
+}
+
+/// Output of `volt audit --json`.
+#[derive(Debug, Serialize)]
+struct AuditOutput {
+    vulnerabilities: Vec<Vulnerability>,
+    metadata: AuditMetadata,
+}
+
+/// The dependencies of the project as `(kind, name, range)`: the ones of package.json, or the
+/// locked ones without it.
+pub fn roots(
+    config: &VoltConfig,
+    lock_file: &LockFile,
+) -> Result<Vec<(DependencyKind, String, String)>> {
+    Ok(match PackageJson::get_from_dir(&config.cwd()?) {
+        Ok((package_json, _)) => package_json
+            .dependencies_by_kind()
+            .into_iter()
+            .map(|(kind, name, range)| (kind, name.to_string(), range.to_string()))
+            .collect(),
+        Err(_) => lock_file
+            .dependencies
+            .iter()
+            .map(|(name, version)| (DependencyKind::Dependencies, name.clone(), version.clone()))
+            .collect(),
+    })
+}
+
+/// Send the installed versions of every package to the registry, and get back the advisories
+/// affecting any of them.
+async fn fetch_advisories(
+    client: &HttpClient,
+    config: &VoltConfig,
+    installed: &BTreeMap<&str, BTreeSet<&str>>,
+) -> Result<HashMap<String, Vec<Advisory>>> {
+    let url = format!("{}/-/npm/v1/security/advisories/bulk", config.registry());
+
+    let response = client
+        .send(&url, |client| {
+            client
+                .post(&url)
+                .headers(auth_headers(config, &url))
+                .json(installed)
+        })
+        .await?;
+
+    if !response.status.is_success() {
+        return Err(VoltError::AuditRequestError {
+            url,
+            code: response.status.as_str().to_string(),
+        }
+        .into());
+    }
+
+    serde_json::from_slice(&response.body).into_diagnostic()
+}
+
+/// Match `advisories` against the `installed` versions of each package.
+fn affected<'a>(
+    installed: &BTreeMap<&'a str, BTreeSet<&'a str>>,
+    advisories: &HashMap<String, Vec<Advisory>>,
+) -> Vec<(&'a str, &'a str, Vec<Advisory>)> {
+    let mut affected = vec![];
+
+    for (name, versions) in installed {
+        let advisories = match advisories.get(*name) {
+            Some(advisories) => advisories,
+            None => continue,
+        };
+
+        for version in versions {
+            let parsed = match Version::parse(version) {
+                Ok(parsed) => parsed,
+                Err(_) => continue,
+            };
+
+            let matching = advisories
+                .iter()
+                .filter(|advisory| {
+                    VersionReq::parse(&advisory.vulnerable_versions)
+                        .map_or(false, |range| range.satisfies(&parsed))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if !matching.is_empty() {
+                affected.push((*name, *version, matching));
+            }
+        }
+    }
+
+    affected
+}
+
+/// Audit the packages locked in `lock_file` against the advisories of the registry.
+pub async fn vulnerabilities(
+    config: &VoltConfig,
+    lock_file: &LockFile,
+    roots: &[(DependencyKind, String, String)],
+) -> Result<Vec<Vulnerability>> {
+    let mut installed: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+
+    for package in lock_file.packages.values() {
+        installed
+            .entry(&package.name)
+            .or_default()
+            .insert(&package.version);
+    }
+
+    if installed.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let client = HttpClient::new(config)?;
+    let advisories = fetch_advisories(&client, config, &installed).await?;
+
+    let mut vulnerabilities = affected(&installed, &advisories)
+        .into_iter()
+        .map(|(name, version, mut advisories)| {
+            advisories.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.id.cmp(&b.id)));
+
+            let mut paths = vec![];
+
+            for (_, root, range) in roots {
+                let found = lock_file.paths(root, range, |package| {
+                    package.name == name && package.version == version
+                });
+
+                paths.extend(found.into_iter().map(|path| {
+                    path.iter()
+                        .map(|(_, package)| format!("{}@{}", package.name, package.version))
+                        .collect::<Vec<_>>()
+                }));
+            }
+
+            Vulnerability {
+                name: name.to_string(),
+                version: version.to_string(),
+                severity: advisories[0].severity,
+                advisories,
+                paths,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    vulnerabilities.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| (&a.name, &a.version).cmp(&(&b.name, &b.version)))
+    });
+
+    Ok(vulnerabilities)
+}
+
+/// Count the packages each kind of dependency of the project brings in.
+fn metadata(
+    lock_file: &LockFile,
+    roots: &[(DependencyKind, String, String)],
+    vulnerabilities: &[Vulnerability],
+) -> AuditMetadata {
+    let mut reachable: HashMap<DependencyKind, HashSet<String>> = HashMap::new();
+
+    for (kind, name, range) in roots {
+        if let Some(package) = lock_file.find(name, range) {
+            reachable
+                .entry(*kind)
+                .or_default()
+                .extend(lock_file.tree(&package.name, &package.version).into_keys());
+        }
+    }
+
+    let production = reachable
+        .remove(&DependencyKind::Dependencies)
+        .unwrap_or_default();
+
+    let only = |kind: DependencyKind| {
+        reachable
+            .get(&kind)
+            .map_or(0, |packages| packages.difference(&production).count())
+    };
+
+    let mut counts = Vulnerabilities::default();
+
+    for vulnerability in vulnerabilities {
+        counts.count(vulnerability.severity);
+    }
+
+    AuditMetadata {
+        vulnerabilities: counts,
+        dependencies: production.len(),
+        dev_dependencies: only(DependencyKind::DevDependencies),
+        optional_dependencies: only(DependencyKind::OptionalDependencies),
+        total_dependencies: lock_file.packages.len(),
+    }
+}
 
 #[async_trait]
 impl VoltCommand for Audit {
     /// Execute the `volt audit` command
     ///
-    /// Execute a audit command
+    /// Check the installed packages against the advisories of the registry.
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global configuration of volt
     /// ## Examples
     /// ```
-    /// //
+    /// // fail on high and critical vulnerabilities only
     /// // .exec() is an async call so you need to await it
-    /// Audit.exec(app).await;
+    /// Audit { command: None, audit_level: Some(Severity::High) }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
-        // let package_json = PackageJson::from("package.json");
-
-        // let mut requires = package_json.dependencies;
-        // requires.extend(package_json.dev_dependencies);
-
-        // let responses =
-        //     utils::get_volt_response_multi(requires.keys().cloned().collect::<Vec<String>>()).await;
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        if let Some(AuditCommand::Fix(fix)) = self.command {
+            return fix.exec(config).await;
+        }
+
+        let meta = config.meta();
+
+        let lock_file = LockFile::load(config.lockfile()?)?;
+        let roots = roots(&config, &lock_file)?;
+
+        let vulnerabilities = vulnerabilities(&config, &lock_file, &roots).await?;
+        let metadata = metadata(&lock_file, &roots, &vulnerabilities);
+
+        let failing = vulnerabilities
+            .iter()
+            .filter(|vulnerability| {
+                self.audit_level
+                    .map_or(true, |level| vulnerability.severity >= level)
+            })
+            .count();
+
+        let mut lines = vec![];
+        let mut severity = None;
+
+        for vulnerability in &vulnerabilities {
+            if severity != Some(vulnerability.severity) {
+                severity = Some(vulnerability.severity);
+
+                let title = format!("{:?}", vulnerability.severity).to_uppercase();
+
+                lines.push(vulnerability.severity.colored(&title).to_string());
+            }
+
+            lines.push(format!(
+                "  {}@{}",
+                vulnerability.name.bold(),
+                vulnerability.version
+            ));
+
+            for advisory in &vulnerability.advisories {
+                lines.push(format!(
+                    "    {} {} {}",
+                    advisory
+                        .severity
+                        .colored(&format!("{:?}", advisory.severity).to_lowercase()),
+                    advisory.title,
+                    advisory.url.truecolor(125, 125, 125)
+                ));
+                lines.push(format!(
+                    "      vulnerable versions: {}",
+                    advisory.vulnerable_versions
+                ));
+            }
+
+            for path in vulnerability.paths.iter().take(3) {
+                lines.push(format!("    {}", path.join(" › ").truecolor(125, 125, 125)));
+            }
+
+            if vulnerability.paths.len() > 3 {
+                lines.push(
+                    format!("    and {} more paths", vulnerability.paths.len() - 3)
+                        .truecolor(125, 125, 125)
+                        .to_string(),
+                );
+            }
+
+            lines.push(String::new());
+        }
+
+        let counts = &metadata.vulnerabilities;
+
+        let summary = [
+            (Severity::Unknown, counts.unknown),
+            (Severity::Critical, counts.critical),
+            (Severity::High, counts.high),
+            (Severity::Moderate, counts.moderate),
+            (Severity::Low, counts.low),
+            (Severity::Info, counts.info),
+        ]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(severity, count)| {
+            severity
+                .colored(&format!("{} {:?}", count, severity).to_lowercase())
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+
+        if vulnerabilities.is_empty() {
+            lines.push(format!(
+                "Found {} vulnerabilities in {} packages",
+                "0".bright_green(),
+                metadata.total_dependencies
+            ));
+        } else {
+            lines.push(format!(
+                "Found {} vulnerabilities ({}) in {} packages",
+                vulnerabilities.len().to_string().bright_red(),
+                summary.join(", "),
+                metadata.total_dependencies
+            ));
+        }
+
+        write(&lines.join("\n"), &meta);
+
+        write_json(
+            &AuditOutput {
+                vulnerabilities,
+                metadata,
+            },
+            &meta,
+        )?;
+
+        if failing > 0 {
+            return Err(VoltError::VulnerablePackages { count: failing }.into());
+        }
 
-        // let mut dependencies: HashMap<String, AuditDependency> = HashMap::new();
-
-        // let start = Instant::now();
-
-        // for res in responses {
-        //     let version = res.version;
-
-        //     let packages = &res.versions.get(&version).unwrap().packages;
-
-        //     for package in packages {
-        //         let flattened_dependency_tree: Vec<AuditDependency> =
-        //             flatten_dependency_tree(package.1, packages);
-
-        //         for dp in flattened_dependency_tree {
-        //             dependencies.insert(package.0.to_owned(), dp);
-        //         }
-        //     }
-        // }
-
-        // let audit = AuditObject {
-        //     name: package_json.name,
-        //     version: package_json.version,
-        //     install: vec![],
-        //     remove: vec![],
-        //     metadata: HashMap::new(),
-        //     requires: requires,
-        //     dependencies: dependencies,
-        // };
-
-        // let mut response = chttp::post_async(
-        //     "http://registry.npmjs.org/-/npm/v1/security/audits",
-        //     format!("{:?}", audit),
-        // )
-        // .await
-        // .unwrap();
+        Ok(())
+    }
+}
 
-        // let text = response.text_async().await.unwrap();
+#[cfg(test)]
+mod tests {
+    use super::{affected, roots, vulnerabilities, Advisory, Audit, Severity};
+    use crate::cli::{VoltCommand, VoltConfig};
+    use crate::core::model::lock_file::LockFile;
+    use crate::core::test_server::{json, serve};
+    use crate::core::utils::errors::{category, exit_code, VoltError};
+    use crate::core::utils::voltapi::VoltPackage;
+    use clap::Parser;
+    use serde_json::json;
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+    #[test]
+    fn matches_advisories_to_installed_versions() {
+        let installed = BTreeMap::from([
+            ("lodash", BTreeSet::from(["4.17.15", "4.17.21"])),
+            ("react", BTreeSet::from(["17.0.2"])),
+        ]);
+
+        let advisories = HashMap::from([(
+            "lodash".to_string(),
+            vec![Advisory {
+                id: 1,
+                title: "Prototype Pollution".to_string(),
+                url: String::new(),
+                severity: Severity::High,
+                vulnerable_versions: "<4.17.19".to_string(),
+            }],
+        )]);
+
+        let affected = affected(&installed, &advisories);
+
+        assert_eq!(affected.len(), 1);
+        assert_eq!((affected[0].0, affected[0].1), ("lodash", "4.17.15"));
+    }
 
-        // let response: AuditResponse = serde_json::from_str(text.as_str()).unwrap();
+    fn package(name: &str, version: &str, dependencies: &[(&str, &str)]) -> VoltPackage {
+        VoltPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            optional: false,
+            integrity: String::new(),
+            tarball: String::new(),
+            bin: None,
+            scripts: None,
+            dependencies: Some(
+                dependencies
+                    .iter()
+                    .map(|(name, version)| (name.to_string(), version.to_string()))
+                    .collect(),
+            ),
+            peer_dependencies: None,
+            peer_dependencies_meta: None,
+            optional_dependencies: None,
+            overrides: None,
+            engines: None,
+            os: None,
+            cpu: None,
+        }
+    }
 
-        Ok(())
+    #[tokio::test]
+    async fn audits_the_locked_packages_against_the_registry() {
+        let advisories = json(
+            "200 OK",
+            &json!({
+                "a": [
+                    {
+                        "id": 3,
+                        "title": "Something new",
+                        "severity": "unheard-of",
+                        "vulnerable_versions": "<2.0.0"
+                    }
+                ],
+                "lodash": [
+                    {
+                        "id": 1,
+                        "title": "Prototype Pollution",
+                        "url": "https://example.com/1",
+                        "severity": "high",
+                        "vulnerable_versions": "<4.17.19"
+                    },
+                    {
+                        "id": 2,
+                        "title": "Slow regex",
+                        "severity": "low",
+                        "vulnerable_versions": "<5.0.0"
+                    }
+                ]
+            }),
+        );
+
+        let (registry, requests) = serve(vec![advisories.clone(), advisories]).await;
+
+        let dir = tempfile::tempdir().unwrap();
+
+        std::fs::write(
+            dir.path().join("package.json"),
+            r#"{"name":"app","dependencies":{"a":"^1.0.0"},"devDependencies":{"lodash":"^4.17.0"}}"#,
+        )
+        .unwrap();
+
+        let mut lock_file = LockFile::new(dir.path().join("volt.lock"));
+
+        lock_file.dependencies = BTreeMap::from([
+            ("a".to_string(), "1.0.0".to_string()),
+            ("lodash".to_string(), "4.17.21".to_string()),
+        ]);
+        lock_file.packages = [
+            package("a", "1.0.0", &[("lodash", "4.17.15")]),
+            package("lodash", "4.17.15", &[]),
+            package("lodash", "4.17.21", &[]),
+        ]
+        .into_iter()
+        .map(|package| (format!("{}@{}", package.name, package.version), package))
+        .collect();
+        lock_file.save().unwrap();
+
+        let cwd = dir.path().display().to_string();
+        let config = VoltConfig::parse_from([
+            "volt",
+            "--cwd",
+            &cwd,
+            "--registry",
+            &registry,
+            "--fetch-retries",
+            "0",
+        ]);
+
+        let roots = roots(&config, &lock_file).unwrap();
+        let found = vulnerabilities(&config, &lock_file, &roots).await.unwrap();
+
+        let found = found
+            .iter()
+            .map(|vulnerability| {
+                (
+                    format!("{}@{}", vulnerability.name, vulnerability.version),
+                    vulnerability.severity,
+                    vulnerability
+                        .advisories
+                        .iter()
+                        .map(|advisory| advisory.id)
+                        .collect::<Vec<_>>(),
+                    vulnerability.paths.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "a@1.0.0".to_string(),
+                    Severity::Unknown,
+                    vec![3],
+                    vec![vec!["a@1.0.0".to_string()]],
+                ),
+                (
+                    "lodash@4.17.15".to_string(),
+                    Severity::High,
+                    vec![1, 2],
+                    vec![vec!["a@1.0.0".to_string(), "lodash@4.17.15".to_string()]],
+                ),
+                (
+                    "lodash@4.17.21".to_string(),
+                    Severity::Low,
+                    vec![2],
+                    vec![vec!["lodash@4.17.21".to_string()]],
+                ),
+            ]
+        );
+
+        // the low vulnerability is under the audit level, the unknown one isn't
+        let report = Audit::parse_from(["audit", "--audit-level", "high"])
+            .exec(config)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            report.downcast_ref::<VoltError>(),
+            Some(VoltError::VulnerablePackages { count: 2 })
+        ));
+        assert_eq!(exit_code(&category(&report)), 1);
+
+        let requests = requests.await.unwrap();
+
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/-/npm/v1/security/advisories/bulk");
+        assert_eq!(requests[0].header("content-type"), Some("application/json"));
+        assert_eq!(
+            requests[0].json(),
+            json!({"a": ["1.0.0"], "lodash": ["4.17.15", "4.17.21"]})
+        );
     }
 }
@@ -17,6 +17,7 @@
 //! Clean `./node_modules` and reduce its size.
 
 use crate::cli::{VoltCommand, VoltConfig};
+use crate::core::io::write;
 
 use async_trait::async_trait;
 use clap::Parser;
@@ -56,7 +57,9 @@ impl VoltCommand for Clean {
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
         let regexes = get_regexes(self.remove_licenses);
 
         let mut matches: Vec<PathBuf> = vec![];
@@ -249,12 +252,15 @@ impl VoltCommand for Clean {
 
         let removed_size = initial_file_size - final_file_size;
 
-        println!(
-            "{} {} {} ( {} Saved )",
-            HumanBytes(initial_file_size),
-            "->".bright_magenta().bold(),
-            HumanBytes(final_file_size),
-            HumanBytes(removed_size).to_string().bright_green(),
+        write(
+            &format!(
+                "{} {} {} ( {} Saved )",
+                HumanBytes(initial_file_size),
+                "->".bright_magenta().bold(),
+                HumanBytes(final_file_size),
+                HumanBytes(removed_size).to_string().bright_green(),
+            ),
+            &meta,
         );
 
         Ok(())
@@ -0,0 +1,204 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Lock packages required at several versions at a single one.
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use indicatif::HumanBytes;
+use miette::Result;
+use serde::Serialize;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::io::{write, write_json, write_verbose},
+    core::model::lock_file::{Duplicate, LockFile},
+    core::store::PackageIndex,
+    core::utils::errors::VoltError,
+    core::utils::package::PackageJson,
+};
+
+/// Remove the versions of packages that aren't needed to satisfy the dependency tree
+#[derive(Debug, Parser)]
+pub struct Dedupe {
+    /// Only report the packages that could be deduplicated, failing if there are any
+    #[clap(long)]
+    check: bool,
+}
+
+/// Output of `volt dedupe --json`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DedupeOutput {
+    duplicates: Vec<Duplicate>,
+    /// Packages removed from the lockfile, orphaned dependencies included
+    removed: Vec<String>,
+    /// Size of the store files used by removed packages and by none of the kept ones
+    saved_bytes: u64,
+    /// Removed packages that aren't in the store, so their size is unknown
+    unmeasured: usize,
+}
+
+#[async_trait]
+impl VoltCommand for Dedupe {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+        let store = config.volt_home()?;
+
+        let mut lock_file = LockFile::load(config.lockfile()?)?;
+
+        // the ranges in package.json, or the locked versions without it
+        let roots = match PackageJson::get_from_dir(&config.cwd()?) {
+            Ok((package_json, _)) => package_json
+                .dependencies_by_kind()
+                .into_iter()
+                .map(|(_, name, range)| (name.to_string(), range.to_string()))
+                .collect::<Vec<_>>(),
+            Err(_) => lock_file
+                .dependencies
+                .iter()
+                .map(|(name, version)| (name.clone(), version.clone()))
+                .collect(),
+        };
+
+        let duplicates = lock_file.duplicates(&roots);
+
+        if duplicates.is_empty() {
+            write("No packages can be deduplicated", &meta);
+
+            return write_json(
+                &DedupeOutput {
+                    duplicates,
+                    removed: vec![],
+                    saved_bytes: 0,
+                    unmeasured: 0,
+                },
+                &meta,
+            );
+        }
+
+        write(
+            &format!(
+                "{} packages are locked at several versions:",
+                duplicates.len().to_string().bright_yellow()
+            ),
+            &meta,
+        );
+
+        for duplicate in &duplicates {
+            write(
+                &format!(
+                    "  {} {} {} {}",
+                    duplicate.name.bright_cyan(),
+                    duplicate.removed.join(", ").bright_red(),
+                    "→".truecolor(125, 125, 125),
+                    duplicate.kept.bright_green(),
+                ),
+                &meta,
+            );
+
+            write_verbose(
+                &format!(
+                    "    required with {}",
+                    duplicate.ranges.join(", ").truecolor(125, 125, 125)
+                ),
+                &meta,
+            );
+        }
+
+        let removed = lock_file.dedupe(&duplicates);
+
+        // the removed versions often share files with the kept ones (or each other), those
+        // stay in the store
+        let kept_files = lock_file
+            .packages
+            .values()
+            .filter_map(|package| PackageIndex::read(&store, &package.cacache_key()).ok())
+            .flat_map(|index| index.files)
+            .map(|file| file.integrity)
+            .collect::<HashSet<_>>();
+
+        let mut removed_files = HashMap::new();
+        let mut unmeasured = 0;
+
+        for package in &removed {
+            match PackageIndex::read(&store, &package.cacache_key()) {
+                Ok(index) => removed_files.extend(
+                    index
+                        .files
+                        .into_iter()
+                        .filter(|file| !kept_files.contains(&file.integrity))
+                        .map(|file| (file.integrity, file.size)),
+                ),
+                Err(_) => unmeasured += 1,
+            }
+        }
+
+        let saved_bytes = removed_files.values().sum::<u64>();
+
+        write(
+            &format!(
+                "{} {} packages ({} of files only they use{}), {} fewer to fetch, extract and link",
+                if self.check {
+                    "Would remove"
+                } else {
+                    "Removed"
+                },
+                removed.len().to_string().bright_green(),
+                HumanBytes(saved_bytes).to_string().bright_green(),
+                if unmeasured > 0 {
+                    format!(", {} aren't in the store", unmeasured)
+                } else {
+                    String::new()
+                },
+                removed.len(),
+            ),
+            &meta,
+        );
+
+        let output = DedupeOutput {
+            removed: removed
+                .iter()
+                .map(|package| format!("{}@{}", package.name, package.version))
+                .collect(),
+            duplicates,
+            saved_bytes,
+            unmeasured,
+        };
+
+        if self.check {
+            write_json(&output, &meta)?;
+
+            return Err(VoltError::DuplicatePackages {
+                names: output
+                    .duplicates
+                    .into_iter()
+                    .map(|duplicate| duplicate.name)
+                    .collect(),
+            }
+            .into());
+        }
+
+        lock_file.save()?;
+
+        write(&format!("Updated {}", lock_file.path.display()), &meta);
+
+        write_json(&output, &meta)
+    }
+}
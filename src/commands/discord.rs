@@ -15,6 +15,7 @@
 */
 
 use crate::cli::{VoltCommand, VoltConfig};
+use crate::core::io::write;
 
 use async_trait::async_trait;
 use clap::Parser;
@@ -40,13 +41,15 @@ impl VoltCommand for Discord {
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
         match webbrowser::open("https://discord.gg/fY7BMcrcYr") {
             Ok(_) => {
-                println!("Successfully opened an invite to the official {} server on your default browser.", "discord".truecolor(88, 101, 242).bold());
+                write(&format!("Successfully opened an invite to the official {} server on your default browser.", "discord".truecolor(88, 101, 242).bold()), &meta);
             }
             Err(_) => {
-                println!("Failed to open an invite to the official {} server on your default browser.\nFeel free to join using this link instead: {}", "discord".truecolor(88, 101, 242).bold(), "https://discord.gg/fY7BMcrcYr".bright_purple().underline());
+                write(&format!("Failed to open an invite to the official {} server on your default browser.\nFeel free to join using this link instead: {}", "discord".truecolor(88, 101, 242).bold(), "https://discord.gg/fY7BMcrcYr".bright_purple().underline()), &meta);
             }
         };
 
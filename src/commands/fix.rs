@@ -14,32 +14,605 @@
     limitations under the License.
 */
 
-//! Fix common errors in the package.json file
+//! Upgrade vulnerable packages to the closest versions that aren't affected.
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::add::{install, AddOutput},
+    commands::audit::{roots, vulnerabilities, Advisory, Vulnerability},
+    commands::outdated::{fetch_packuments, Packument},
+    commands::update::bump_range,
+    core::http::HttpClient,
+    core::io::{write, write_json},
+    core::model::lock_file::LockFile,
+    core::utils::errors::VoltError,
+    core::utils::link_dependencies,
+    core::utils::package::{edit_package_json, DependencyKind, PackageJson},
+    core::utils::voltapi::VoltPackage,
+};
 
 use async_trait::async_trait;
+use clap::Parser;
 use colored::Colorize;
 use miette::Result;
+use node_semver::{Range, Version};
+use package_spec::PackageSpec;
+use serde::Serialize;
+use std::collections::HashMap;
 
-/// Struct implementation for the `Deploy` command.
-pub struct Fix;
+/// Upgrade vulnerable packages to the closest versions that aren't affected
+#[derive(Debug, Parser)]
+pub struct Fix {
+    /// Only show what would be upgraded
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Allow upgrades to new major versions, which can contain breaking changes
+    #[clap(long)]
+    force: bool,
+}
+
+/// How a vulnerable package gets upgraded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum Method {
+    /// Raise the range of the dependency in package.json
+    Range,
+    /// Lock the upgrade, it satisfies the ranges every dependent requires it with
+    Lockfile,
+    /// Override the version in the lockfile, some dependents require it with ranges excluding it
+    Override,
+}
+
+/// An entry of `volt audit fix --json`.
+#[derive(Debug, Serialize)]
+struct PlannedFix {
+    name: String,
+    from: String,
+    to: Option<String>, // `None` if every later version is vulnerable too
+    method: Option<Method>,
+    breaking: bool, // `to` is a new major version
+    applied: bool,
+    advisories: Vec<u64>,
+}
+
+/// Output of `volt audit fix --json`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FixOutput {
+    fixes: Vec<PlannedFix>,
+    dry_run: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    install: Option<AddOutput>,
+}
+
+/// The lowest of `versions` above `current` that none of `advisories` affect, along with whether
+/// it's a breaking upgrade. Prereleases are only picked to replace prereleases.
+fn safe_version<'a>(
+    versions: impl Iterator<Item = &'a str>,
+    current: &str,
+    advisories: &[Advisory],
+) -> Option<(String, bool)> {
+    let parsed = current.parse::<Version>().ok()?;
+    let prerelease = current.contains('-');
+
+    let vulnerable = advisories
+        .iter()
+        .filter_map(|advisory| Range::parse(&advisory.vulnerable_versions).ok())
+        .collect::<Vec<_>>();
+
+    let safe = versions
+        .filter(|version| prerelease || !version.contains('-'))
+        .filter_map(|version| Some((version.parse::<Version>().ok()?, version)))
+        .filter(|(version, _)| *version > parsed)
+        .filter(|(version, _)| !vulnerable.iter().any(|range| version.satisfies(range)))
+        .min_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, version)| version.to_string())?;
+
+    let compatible = Range::parse(format!("^{}", current))
+        .ok()
+        .zip(safe.parse::<Version>().ok())
+        .map_or(false, |(range, safe)| safe.satisfies(&range));
+
+    Some((safe, !compatible))
+}
+
+/// Whether `version` satisfies `range`, both as written in the lockfile.
+fn satisfies(version: &str, range: &str) -> bool {
+    match (version.parse::<Version>(), Range::parse(range)) {
+        (Ok(version), Ok(range)) => version.satisfies(&range),
+        _ => false,
+    }
+}
+
+/// The fix of every vulnerability: the versions the project depends on directly are fixed in
+/// package.json, the others in the lockfile, with an override if one of their dependents doesn't
+/// allow the upgrade.
+fn plan(
+    lock_file: &LockFile,
+    roots: &[(DependencyKind, String, String)],
+    vulnerabilities: &[Vulnerability],
+    packuments: &HashMap<String, Packument>,
+) -> Vec<PlannedFix> {
+    let mut fixes = vec![];
+
+    for vulnerability in vulnerabilities {
+        let safe = packuments.get(&vulnerability.name).and_then(|packument| {
+            safe_version(
+                packument.versions.keys().map(String::as_str),
+                &vulnerability.version,
+                &vulnerability.advisories,
+            )
+        });
+
+        let direct = roots.iter().any(|(_, name, range)| {
+            *name == vulnerability.name
+                && lock_file
+                    .find(name, range)
+                    .map_or(false, |package| package.version == vulnerability.version)
+        });
+
+        let method = safe.as_ref().map(|(safe, _)| {
+            let excluded = lock_file.packages.values().any(|package| {
+                requires(package, &vulnerability.name, &vulnerability.version)
+                    .map_or(false, |range| !satisfies(safe, range))
+            });
+
+            if direct {
+                Method::Range
+            } else if excluded {
+                Method::Override
+            } else {
+                Method::Lockfile
+            }
+        });
+
+        let breaking = safe.as_ref().map_or(false, |(_, breaking)| *breaking);
+
+        fixes.push(PlannedFix {
+            name: vulnerability.name.clone(),
+            from: vulnerability.version.clone(),
+            to: safe.map(|(safe, _)| safe),
+            method,
+            breaking,
+            applied: false,
+            advisories: vulnerability
+                .advisories
+                .iter()
+                .map(|advisory| advisory.id)
+                .collect(),
+        });
+    }
+
+    fixes
+}
+
+/// The range `package` requires `name` with, if `version` satisfies it.
+fn requires<'a>(package: &'a VoltPackage, name: &str, version: &str) -> Option<&'a str> {
+    [&package.dependencies, &package.optional_dependencies]
+        .into_iter()
+        .flatten()
+        .filter_map(|dependencies| dependencies.get(name))
+        .find(|range| *range == version || satisfies(version, range))
+        .map(String::as_str)
+}
+
+/// Lock the upgrades of `applied` in place of the vulnerable versions, wherever they're required.
+/// Returns the packages requiring a vulnerable version, resolved to link them to the upgrades.
+fn lock_fixes(lock_file: &mut LockFile, applied: &[&PlannedFix]) -> Vec<VoltPackage> {
+    for fix in applied {
+        let to = fix.to.clone().unwrap_or_default();
+
+        if fix.method == Some(Method::Range) {
+            lock_file.dependencies.insert(fix.name.clone(), to.clone());
+        }
+
+        lock_file
+            .overrides
+            .insert(format!("{}@{}", fix.name, fix.from), to);
+    }
+
+    lock_file
+        .packages
+        .values()
+        .filter(|package| {
+            applied
+                .iter()
+                .any(|fix| requires(package, &fix.name, &fix.from).is_some())
+        })
+        .map(|package| lock_file.resolved(package))
+        .collect()
+}
 
 #[async_trait]
 impl VoltCommand for Fix {
-    /// Execute the `volt fix` command
+    /// Execute the `volt audit fix` command
     ///
-    /// Removes a package from your direct dependencies.
+    /// Upgrade the vulnerable packages found by `volt audit`.
+    /// ## Arguments
+    /// * `config` - The global configuration of volt
     /// ## Examples
     /// ```
+    /// // show the upgrades, new major versions included
     /// // .exec() is an async call so you need to await it
-    /// Create.exec(app, vec![], vec!["--verbose"]).await;
+    /// Fix { dry_run: true, force: true }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
-        println!("{}", "Scanning for errors".bright_cyan());
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
+        let mut lock_file = LockFile::load(config.lockfile()?)?;
+        let roots = roots(&config, &lock_file)?;
+
+        let vulnerabilities = vulnerabilities(&config, &lock_file, &roots).await?;
+
+        if vulnerabilities.is_empty() {
+            write("Found no vulnerabilities to fix", &meta);
+
+            return write_json(
+                &FixOutput {
+                    fixes: vec![],
+                    dry_run: self.dry_run,
+                    install: None,
+                },
+                &meta,
+            );
+        }
+
+        let client = HttpClient::new(&config)?;
+
+        let packuments = fetch_packuments(
+            &client,
+            &config,
+            vulnerabilities
+                .iter()
+                .map(|vulnerability| vulnerability.name.as_str()),
+        )
+        .await?;
+
+        let mut fixes = plan(&lock_file, &roots, &vulnerabilities, &packuments);
+
+        for fix in &mut fixes {
+            fix.applied = fix.to.is_some() && (self.force || !fix.breaking) && !self.dry_run;
+        }
+
+        let width = fixes.iter().map(|fix| fix.name.len()).max().unwrap_or(0);
+
+        for fix in &fixes {
+            let name = format!("{:width$}", fix.name, width = width);
+
+            let line = match (&fix.to, fix.method) {
+                (Some(to), Some(method)) => format!(
+                    "  {} {} {} {} {}{}",
+                    name.bright_cyan(),
+                    fix.from.bright_red(),
+                    "→".truecolor(125, 125, 125),
+                    to.bright_green(),
+                    match method {
+                        Method::Range => "(package.json)",
+                        Method::Lockfile => "(lockfile)",
+                        Method::Override => "(override)",
+                    }
+                    .truecolor(125, 125, 125),
+                    if fix.breaking && !self.force {
+                        " breaking, needs --force".bright_yellow().to_string()
+                    } else if fix.breaking {
+                        " breaking".bright_yellow().to_string()
+                    } else {
+                        String::new()
+                    }
+                ),
+                _ => format!(
+                    "  {} {} {}",
+                    name.bright_cyan(),
+                    fix.from.bright_red(),
+                    "no fixed version available".truecolor(125, 125, 125)
+                ),
+            };
+
+            write(&line, &meta);
+        }
+
+        let applicable = fixes
+            .iter()
+            .filter(|fix| fix.to.is_some() && (self.force || !fix.breaking))
+            .count();
+
+        if self.dry_run {
+            write(
+                &format!(
+                    "Would fix {} of {} vulnerable packages",
+                    applicable.to_string().bright_green(),
+                    fixes.len()
+                ),
+                &meta,
+            );
+
+            return write_json(
+                &FixOutput {
+                    fixes,
+                    dry_run: true,
+                    install: None,
+                },
+                &meta,
+            );
+        }
+
+        let applied = fixes.iter().filter(|fix| fix.applied).collect::<Vec<_>>();
+
+        let output = if applied.is_empty() {
+            None
+        } else {
+            let specs = applied
+                .iter()
+                .map(|fix| {
+                    let spec = format!("{}@{}", fix.name, fix.to.as_deref().unwrap_or_default());
+
+                    spec.parse::<PackageSpec>()
+                        .map_err(|_| VoltError::PackageSpecificationError { spec }.into())
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            // installing locks the upgrades as dependencies of the project, only the ones in
+            // package.json are
+            let dependencies = lock_file.dependencies.clone();
+
+            let output = install(&config, &mut lock_file, &specs).await?;
+
+            lock_file.dependencies = dependencies;
+
+            let dependents = lock_fixes(&mut lock_file, &applied);
+
+            lock_file.prune();
+            lock_file.save()?;
+
+            // the dependents of the vulnerable versions are linked to the upgrades instead
+            for dependent in dependents {
+                if lock_file
+                    .packages
+                    .contains_key(&format!("{}@{}", dependent.name, dependent.version))
+                {
+                    link_dependencies(&dependent, &config)?;
+                }
+            }
+
+            Some(output)
+        };
+
+        let changes_package_json = applied.iter().any(|fix| fix.method == Some(Method::Range));
+
+        if changes_package_json {
+            let (_, path) = PackageJson::get_from_dir(&config.cwd()?)?;
+
+            edit_package_json(&path, |package_json| {
+                for fix in applied
+                    .iter()
+                    .filter(|fix| fix.method == Some(Method::Range))
+                {
+                    let to = fix.to.as_deref().unwrap_or_default();
+
+                    for (kind, name, range) in &roots {
+                        if *name != fix.name || *kind == DependencyKind::PeerDependencies {
+                            continue;
+                        }
+
+                        let range = match to.parse() {
+                            Ok(to) => bump_range(range, &to),
+                            Err(_) => to.to_string(),
+                        };
+
+                        if let Some(declared) = package_json
+                            .get_mut(kind.field())
+                            .and_then(|dependencies| dependencies.get_mut(name))
+                        {
+                            *declared = serde_json::Value::String(range);
+                        }
+                    }
+                }
+            })?;
+        }
+
+        let remaining = fixes.len() - applied.len();
+
+        write(
+            &format!(
+                "Fixed {} of {} vulnerable packages",
+                applied.len().to_string().bright_green(),
+                fixes.len()
+            ),
+            &meta,
+        );
+
+        write_json(
+            &FixOutput {
+                fixes,
+                dry_run: false,
+                install: output,
+            },
+            &meta,
+        )?;
+
+        if remaining > 0 {
+            return Err(VoltError::VulnerablePackages { count: remaining }.into());
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{lock_fixes, plan, safe_version, Method};
+    use crate::commands::audit::{Advisory, Severity, Vulnerability};
+    use crate::commands::outdated::Packument;
+    use crate::core::model::lock_file::LockFile;
+    use crate::core::utils::package::DependencyKind;
+
+    use std::collections::HashMap;
+
+    fn advisory(vulnerable_versions: &str) -> Advisory {
+        Advisory {
+            id: 1,
+            title: String::new(),
+            url: String::new(),
+            severity: Severity::High,
+            vulnerable_versions: vulnerable_versions.to_string(),
+        }
+    }
+
+    #[test]
+    fn picks_the_lowest_safe_version() {
+        let versions = ["1.2.0", "1.2.1", "1.3.0", "1.4.0-beta.1", "2.0.0"];
+
+        assert_eq!(
+            safe_version(versions.into_iter(), "1.2.0", &[advisory("<1.3.0")]),
+            Some(("1.3.0".to_string(), false))
+        );
+        assert_eq!(
+            safe_version(versions.into_iter(), "1.2.0", &[advisory("<2.0.0")]),
+            Some(("2.0.0".to_string(), true))
+        );
+        assert_eq!(
+            safe_version(versions.into_iter(), "1.2.0", &[advisory("*")]),
+            None
+        );
+    }
+
+    fn packument(versions: &[&str]) -> Packument {
+        Packument {
+            dist_tags: HashMap::new(),
+            versions: versions
+                .iter()
+                .map(|version| (version.to_string(), serde_json::json!({})))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn locks_the_upgrades_in_place_of_the_vulnerable_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("volt.lock");
+
+        std::fs::copy(
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/audit-fix/volt.lock"
+            ),
+            &path,
+        )
+        .unwrap();
+
+        let mut lock_file = LockFile::load(&path).unwrap();
+
+        let roots = [("a", "^1.0.0"), ("b", "^1.0.0"), ("minimist", "^1.2.0")]
+            .iter()
+            .map(|(name, range)| {
+                (
+                    DependencyKind::Dependencies,
+                    name.to_string(),
+                    range.to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let vulnerabilities = [
+            ("lodash", "4.17.15", "<4.17.19"),
+            ("minimist", "1.2.0", "<1.2.6"),
+        ]
+        .iter()
+        .map(|(name, version, vulnerable_versions)| Vulnerability {
+            name: name.to_string(),
+            version: version.to_string(),
+            severity: Severity::High,
+            advisories: vec![advisory(vulnerable_versions)],
+            paths: vec![],
+        })
+        .collect::<Vec<_>>();
+
+        let packuments = HashMap::from([
+            (
+                "lodash".to_string(),
+                packument(&["4.17.15", "4.17.19", "4.17.21", "5.0.0"]),
+            ),
+            (
+                "minimist".to_string(),
+                packument(&["1.2.0", "1.2.6", "1.2.8"]),
+            ),
+        ]);
+
+        let fixes = plan(&lock_file, &roots, &vulnerabilities, &packuments);
+
+        let planned = fixes
+            .iter()
+            .map(|fix| {
+                (
+                    fix.name.as_str(),
+                    fix.to.as_deref(),
+                    fix.method,
+                    fix.breaking,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // `a` requires lodash@4.17.15 exactly
+        assert_eq!(
+            planned,
+            [
+                ("lodash", Some("4.17.19"), Some(Method::Override), false),
+                ("minimist", Some("1.2.6"), Some(Method::Range), false)
+            ]
+        );
+
+        // as installing the upgrades does
+        for (name, version) in [("lodash", "4.17.19"), ("minimist", "1.2.6")] {
+            let mut package = lock_file.packages["a@1.0.0"].clone();
+
+            package.name = name.to_string();
+            package.version = version.to_string();
+            package.dependencies = None;
+
+            lock_file
+                .packages
+                .insert(format!("{}@{}", name, version), package);
+        }
+
+        let applied = fixes.iter().collect::<Vec<_>>();
+        let dependents = lock_fixes(&mut lock_file, &applied);
+
+        let relinked = dependents
+            .iter()
+            .map(|package| {
+                (
+                    package.name.as_str(),
+                    package.dependencies.as_ref().unwrap()["lodash"].as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(relinked, [("a", "4.17.19"), ("b", "4.17.19")]);
+
+        lock_file.prune();
+        lock_file.save().unwrap();
+
+        let mut lock_file = LockFile::load(&path).unwrap();
+
+        assert_eq!(
+            lock_file.packages.keys().collect::<Vec<_>>(),
+            ["a@1.0.0", "b@1.0.0", "lodash@4.17.19", "minimist@1.2.6"]
+        );
+        assert_eq!(lock_file.dependencies["minimist"], "1.2.6");
+        assert_eq!(lock_file.overrides["lodash@4.17.15"], "4.17.19");
+
+        // the dependents keep the ranges they declare
+        assert_eq!(
+            lock_file.packages["a@1.0.0"].dependencies.as_ref().unwrap()["lodash"],
+            "4.17.15"
+        );
+        assert_eq!(
+            lock_file.locked("lodash", "4.17.15").unwrap().version,
+            "4.17.19"
+        );
+        assert!(lock_file.prune().is_empty());
+    }
+}
@@ -16,15 +16,156 @@
 
 //! Display info about a package.
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::http::HttpClient,
+    core::io::{write, write_json},
+    core::net::fetch_packument,
+    core::utils::errors::VoltError,
+};
 
 use async_trait::async_trait;
 use clap::Parser;
-use miette::Result;
+use colored::Colorize;
+use indicatif::HumanBytes;
+use miette::{IntoDiagnostic, Result};
+use node_semver::{Range, Version};
+use package_spec::{PackageSpec, VersionSpec};
+use serde_json::{Map, Value};
 
 /// Display information about a package
 #[derive(Debug, Parser)]
-pub struct Info {}
+pub struct Info {
+    /// Package to display, optionally with a version, range or dist-tag (defaults to `latest`)
+    package: PackageSpec,
+
+    /// Only display this field, e.g. `versions` or `dist.tarball`
+    field: Option<String>,
+}
+
+/// Fields of the packument shown for the selected version when its manifest doesn't have them.
+const PACKUMENT_FIELDS: [&str; 9] = [
+    "description",
+    "homepage",
+    "license",
+    "repository",
+    "author",
+    "keywords",
+    "maintainers",
+    "bugs",
+    "readme",
+];
+
+/// The version of `packument` that `requested` selects.
+pub(crate) fn select_version(packument: &Value, requested: Option<&VersionSpec>) -> Option<String> {
+    let tag = |tag: &str| {
+        packument["dist-tags"][tag]
+            .as_str()
+            .map(|version| version.to_string())
+    };
+
+    let range = match requested {
+        None => return tag("latest"),
+        Some(VersionSpec::Tag(name)) => return tag(name),
+        Some(VersionSpec::Version(version)) => {
+            let version = version.to_string();
+
+            return packument["versions"].get(&version).map(|_| version);
+        }
+        Some(VersionSpec::Range(range)) => Range::parse(range.to_string()).ok()?,
+    };
+
+    // the latest version is preferred when it satisfies the range, like installs do
+    if let Some(latest) = tag("latest") {
+        if latest
+            .parse::<Version>()
+            .map_or(false, |latest| latest.satisfies(&range))
+        {
+            return Some(latest);
+        }
+    }
+
+    packument["versions"]
+        .as_object()?
+        .keys()
+        .filter_map(|version| version.parse::<Version>().ok())
+        .filter(|version| version.satisfies(&range))
+        .max()
+        .map(|version| version.to_string())
+}
+
+/// The manifest of `version`, along with the fields of the packument describing the package as
+/// a whole: its dist-tags, the list of its versions and their publish times.
+fn view(packument: &Value, version: &str) -> Value {
+    let mut view = packument["versions"][version]
+        .as_object()
+        .cloned()
+        .unwrap_or_default();
+
+    for field in PACKUMENT_FIELDS {
+        if let Some(value) = packument.get(field) {
+            view.entry(field).or_insert_with(|| value.clone());
+        }
+    }
+
+    let mut versions = packument["versions"]
+        .as_object()
+        .map(|versions| versions.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    versions.sort_by_cached_key(|version| version.parse::<Version>().ok());
+
+    view.insert(
+        "dist-tags".to_string(),
+        packument
+            .get("dist-tags")
+            .cloned()
+            .unwrap_or_else(|| Value::Object(Map::new())),
+    );
+    view.insert(
+        "versions".to_string(),
+        Value::Array(versions.into_iter().map(Value::String).collect()),
+    );
+
+    if let Some(time) = packument.get("time") {
+        view.insert("time".to_string(), time.clone());
+    }
+
+    Value::Object(view)
+}
+
+/// The value of a `.` separated `field` of `view`, indexing into arrays with numbers.
+fn select_field<'a>(view: &'a Value, field: &str) -> Option<&'a Value> {
+    field.split('.').try_fold(view, |value, key| match value {
+        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        value => value.get(key),
+    })
+}
+
+/// Someone as listed in package.json: a `Name <email> (url)` string or an object.
+fn person(value: &Value) -> Option<String> {
+    match value {
+        Value::String(person) => Some(person.clone()),
+        Value::Object(person) => {
+            let name = person.get("name")?.as_str()?;
+
+            Some(match person.get("email").and_then(Value::as_str) {
+                Some(email) => format!("{} <{}>", name, email),
+                None => name.to_string(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The url of a `repository` field: a url, a `github:user/repo` shortcut or an object.
+fn repository(value: &Value) -> Option<String> {
+    match value {
+        Value::String(url) => Some(url.clone()),
+        Value::Object(repository) => Some(repository.get("url")?.as_str()?.to_string()),
+        _ => None,
+    }
+}
 
 #[async_trait]
 impl VoltCommand for Info {
@@ -32,249 +173,216 @@ impl VoltCommand for Info {
     ///
     /// Display info about a package
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global configuration of volt
     /// ## Examples
     /// ```
-    /// // Display info about a package
+    /// // Display the versions of react
     /// // .exec() is an async call so you need to await it
-    /// Info.exec(app).await;
+    /// Info { package: "react".parse()?, field: Some("versions".into()) }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
-        // #[allow(unused_assignments)]
-        // let mut name = String::new();
-
-        // if !std::env::current_dir()
-        //     .unwrap()
-        //     .join("package.json")
-        //     .exists()
-        //     && app.args.len() == 1
-        // {
-        //     println!(
-        //         "{}: {}\n",
-        //         "warning".yellow().bold(),
-        //         "Could not find a package.json file in the current directory"
-        //     );
-        //     name = utils::get_basename(app.current_dir.to_str().unwrap()).to_string();
-        // }
-
-        // let mut field: String = String::new();
-
-        // if app.args.len() > 2 {
-        //     field = String::from(&app.args[2]);
-        //     name = String::from(&app.args[1]);
-        // } else if app.args.len() == 2 {
-        //     name = String::from(&app.args[1]);
-        // }
-
-        // let package: Package = get_package(&name).await.unwrap().unwrap();
-
-        // if field == String::new() {
-        //     let latest_version = package.dist_tags.get("latest").unwrap();
-        //     println!("{}\n", format!("v{}", latest_version).bright_blue());
-
-        //     if package.description != None {
-        //         println!("{}\n", package.description.unwrap());
-        //     }
-        //     if package.keywords != None {
-        //         print!("{}: ", "keywords".bright_blue().bold());
-        //         for keyword in package.keywords.unwrap().iter() {
-        //             print!("{} ", keyword.green())
-        //         }
-        //         print!("\n\n")
-        //     }
-
-        //     let latestpackage: &Version = &package.versions[latest_version];
-        //     println!("distribution:");
-        //     println!(
-        //         "  tarball: {}",
-        //         latestpackage.dist.tarball.bright_blue().underline()
-        //     );
-        //     println!("  shasum: {}", latestpackage.dist.shasum.bright_green());
-        //     if latestpackage.dist.integrity != "" {
-        //         println!(
-        //             "  integrity: {}",
-        //             latestpackage.dist.integrity.bright_blue()
-        //         );
-        //     }
-        //     if latestpackage.dist.unpacked_size != 0 {
-        //         println!(
-        //             "  unpackedSize: {}{}",
-        //             (latestpackage.dist.unpacked_size / 1024)
-        //                 .to_string()
-        //                 .bright_blue()
-        //                 .bold(),
-        //             "kb".bright_blue().bold()
-        //         );
-        //     }
-
-        //     let dependencies = latestpackage
-        //         .dependencies
-        //         .keys()
-        //         .cloned()
-        //         .collect::<Vec<String>>();
-
-        //     if dependencies.len() != 0 {
-        //         println!("\ndependencies:");
-        //         for dep in dependencies.iter() {
-        //             println!("{}{}", "  - ".bright_magenta(), dep);
-        //         }
-        //     }
-
-        //     println!("{}", "\nmaintainers:");
-        //     for maintainer in latestpackage.maintainers.iter() {
-        //         println!(
-        //             "  {} {}<{}>",
-        //             "-".bright_magenta(),
-        //             maintainer.email,
-        //             maintainer.name.yellow().bold()
-        //         )
-        //     }
-        //     print!("\n");
-        // } else {
-        //     match field.as_str() {
-        //         "readme" => {
-        //             let text: String;
-
-        //             if package.readme.is_some() && package.readme.as_ref().unwrap().trim() != "" {
-        //                 text = package.readme.unwrap();
-        //             } else {
-        //                 let latest_version = package.dist_tags.get("latest").unwrap();
-        //                 let current_version = package.versions.get(latest_version).unwrap();
-
-        //                 if current_version.readme.is_some() {
-        //                     text = current_version.readme.as_ref().unwrap().to_string();
-        //                 } else {
-        //                     text = String::new();
-        //                 }
-        //             }
-        //             if text != String::new() {
-        //                 PrettyPrinter::new()
-        //                     .input_from_bytes(text.as_bytes())
-        //                     .theme("Dracula")
-        //                     .language("markdown")
-        //                     .print()
-        //                     .unwrap();
-
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find a readme for {}", name);
-        //             }
-        //         }
-        //         "version" => {
-        //             let mut table = Table::new();
-
-        //             let versions: HashMap<String, String> = package.dist_tags;
-
-        //             let mut labels: Vec<Cell> = vec![Cell::new("")];
-
-        //             let mut values: Vec<Cell> =
-        //                 vec![Cell::new(&name.bright_blue().to_string().as_str())];
-
-        //             for (k, v) in versions.iter() {
-        //                 labels.push(Cell::new(k.as_str()));
-        //                 values.push(Cell::new(v.bright_magenta().to_string().as_str()));
-        //             }
-
-        //             table.add_row(Row::new(labels));
-
-        //             table.add_row(Row::new(values));
-
-        //             table.printstd();
-        //             print!("\n");
-        //         }
-        //         "versions" => {
-        //             let versions = package.versions.keys().cloned().collect::<Vec<String>>();
-
-        //             println!("{}{}", "versions".bright_cyan(), ":".bright_magenta());
-        //             for v in versions {
-        //                 println!("  {} {}", "-".bright_magenta(), v.bright_green());
-        //             }
-        //             print!("\n");
-        //         }
-        //         "description" => {
-        //             let description = package.description;
-
-        //             if description.is_some() {
-        //                 println!("{}", description.unwrap());
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find a description for {}", name);
-        //             }
-        //         }
-        //         "name" => {
-        //             println!("{}", name);
-        //             print!("\n");
-        //         }
-        //         "maintainers" => {
-        //             for maintainer in package.maintainers.iter() {
-        //                 println!(
-        //                     "{} <{}>",
-        //                     maintainer.name.bright_green().bold(),
-        //                     maintainer.email.bright_magenta(),
-        //                 )
-        //             }
-        //             print!("\n");
-        //         }
-        //         "time" => {
-        //             let times = package.time;
-
-        //             for (version, time) in times {
-        //                 println!(
-        //                     "{} {} {}",
-        //                     version.bright_cyan(),
-        //                     ":".bright_magenta(),
-        //                     time.bright_black()
-        //                 );
-        //             }
-
-        //             print!("\n");
-        //         }
-        //         "repository" => {
-        //             let repo = package.repository;
-
-        //             if repo.is_some() {
-        //                 let data = repo.unwrap();
-
-        //                 println!("{}: {}", "provider".bright_yellow(), data.type_field);
-        //                 println!("{}: {}", "url".bright_purple(), data.url.underline());
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find a repository for {}", name);
-        //             }
-        //         }
-        //         "homepage" => {
-        //             let page = package.homepage;
-
-        //             if page.is_some() {
-        //                 let data = page.unwrap();
-
-        //                 println!("{}", data);
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find a homepage for {}", name);
-        //             }
-        //         }
-        //         "keywords" => {
-        //             let keywords = package.keywords;
-        //             println!("{}{}", "keywords".bright_cyan(), ":".bright_cyan());
-        //             if keywords.is_some() {
-        //                 let keywords = keywords.unwrap();
-
-        //                 for kw in keywords {
-        //                     println!("{} {}", "-".bright_magenta(), kw);
-        //                 }
-        //                 print!("\n");
-        //             } else {
-        //                 error!("could not find keywords for {}", name);
-        //             }
-        //         }
-        //         "users" => {}
-        //         &_ => {}
-        //     }
-        // }
-
-        Ok(())
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
+        let (name, requested) = match &self.package {
+            PackageSpec::Npm {
+                name, requested, ..
+            } => (name, requested.as_ref()),
+            spec => {
+                return Err(VoltError::PackageSpecificationError {
+                    spec: spec.to_string(),
+                }
+                .into())
+            }
+        };
+
+        let client = HttpClient::new(&config)?;
+
+        let packument = fetch_packument::<Value>(&client, &config, name, requested, true)
+            .await?
+            .ok_or_else(|| VoltError::PackageNotFound {
+                url: format!("{}/{}", config.registry(), name.replace('/', "%2f")),
+                package_name: name.clone(),
+            })?;
+
+        let version =
+            select_version(&packument, requested).ok_or_else(|| VoltError::VersionNotFound {
+                name: name.clone(),
+                requested: requested
+                    .map(|requested| requested.to_string())
+                    .unwrap_or_else(|| "latest".to_string()),
+            })?;
+
+        let view = view(&packument, &version);
+
+        if let Some(field) = &self.field {
+            let value = match select_field(&view, field) {
+                Some(value) => value,
+                None => return write_json(&Value::Null, &meta),
+            };
+
+            match value {
+                Value::String(text) => write(text, &meta),
+                Value::Array(items) if items.iter().all(Value::is_string) => write(
+                    &items
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    &meta,
+                ),
+                value => write(
+                    &serde_json::to_string_pretty(value).into_diagnostic()?,
+                    &meta,
+                ),
+            }
+
+            return write_json(value, &meta);
+        }
+
+        let str_field = |field: &str| view.get(field).and_then(Value::as_str);
+
+        let dependencies = view["dependencies"]
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        let versions = view["versions"].as_array().map_or(0, Vec::len);
+
+        let mut lines = vec![format!(
+            "{}@{} | {} | deps: {} | versions: {}",
+            name.bright_green().bold(),
+            version.bright_green().bold(),
+            str_field("license").unwrap_or("Proprietary").bright_green(),
+            dependencies.len().to_string().bright_cyan(),
+            versions.to_string().bright_yellow()
+        )];
+
+        if let Some(description) = str_field("description") {
+            lines.push(description.to_string());
+        }
+
+        if let Some(homepage) = str_field("homepage") {
+            lines.push(homepage.bright_cyan().to_string());
+        }
+
+        if let Some(repository) = view.get("repository").and_then(repository) {
+            lines.push(format!("repository: {}", repository.bright_cyan()));
+        }
+
+        let dist = &view["dist"];
+
+        lines.push(String::new());
+        lines.push("dist".bold().to_string());
+
+        for field in ["tarball", "shasum", "integrity"] {
+            if let Some(value) = dist[field].as_str() {
+                lines.push(format!(".{}: {}", field, value.bright_cyan()));
+            }
+        }
+
+        if let Some(size) = dist["unpackedSize"].as_u64() {
+            lines.push(format!(
+                ".unpackedSize: {}",
+                HumanBytes(size).to_string().bright_yellow()
+            ));
+        }
+
+        if let Some(files) = dist["fileCount"].as_u64() {
+            lines.push(format!(".fileCount: {}", files.to_string().bright_yellow()));
+        }
+
+        if !dependencies.is_empty() {
+            lines.push(String::new());
+            lines.push("dependencies:".bold().to_string());
+
+            for (dependency, range) in &dependencies {
+                lines.push(format!(
+                    "{}: {}",
+                    dependency.bright_cyan(),
+                    range.as_str().unwrap_or_default()
+                ));
+            }
+        }
+
+        if let Some(maintainers) = view["maintainers"].as_array() {
+            lines.push(String::new());
+            lines.push("maintainers:".bold().to_string());
+
+            for maintainer in maintainers.iter().filter_map(person) {
+                lines.push(format!("- {}", maintainer));
+            }
+        }
+
+        if let Some(tags) = view["dist-tags"].as_object() {
+            lines.push(String::new());
+            lines.push("dist-tags:".bold().to_string());
+
+            for (tag, tagged) in tags {
+                lines.push(format!(
+                    "{}: {}",
+                    tag.bright_cyan(),
+                    tagged.as_str().unwrap_or_default()
+                ));
+            }
+        }
+
+        if let Some(published) = view["time"][&version].as_str() {
+            let by = view
+                .get("_npmUser")
+                .and_then(person)
+                .map(|user| format!(" by {}", user.bright_cyan()))
+                .unwrap_or_default();
+
+            lines.push(String::new());
+            lines.push(format!("published {}{}", published.bright_yellow(), by));
+        }
+
+        write(&lines.join("\n"), &meta);
+
+        write_json(&view, &meta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{select_field, select_version, view};
+    use serde_json::json;
+
+    #[test]
+    fn selects_versions_and_fields() {
+        let packument = json!({
+            "dist-tags": { "latest": "1.1.0", "next": "2.0.0-rc.1" },
+            "versions": {
+                "1.0.0": { "version": "1.0.0" },
+                "1.1.0": { "version": "1.1.0", "dist": { "tarball": "https://r/a-1.1.0.tgz" } },
+                "2.0.0-rc.1": { "version": "2.0.0-rc.1" }
+            },
+            "license": "MIT"
+        });
+
+        let version = |spec: &str| match spec.parse::<package_spec::PackageSpec>().unwrap() {
+            package_spec::PackageSpec::Npm { requested, .. } => {
+                select_version(&packument, requested.as_ref())
+            }
+            _ => unreachable!(),
+        };
+
+        assert_eq!(version("a").as_deref(), Some("1.1.0"));
+        assert_eq!(version("a@next").as_deref(), Some("2.0.0-rc.1"));
+        assert_eq!(version("a@~1.0.0").as_deref(), Some("1.0.0"));
+        assert_eq!(version("a@3"), None);
+
+        let view = view(&packument, "1.1.0");
+
+        assert_eq!(
+            select_field(&view, "dist.tarball"),
+            Some(&json!("https://r/a-1.1.0.tgz"))
+        );
+        assert_eq!(
+            select_field(&view, "versions.2"),
+            Some(&json!("2.0.0-rc.1"))
+        );
+        assert_eq!(select_field(&view, "license"), Some(&json!("MIT")));
     }
 }
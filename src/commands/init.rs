@@ -16,6 +16,7 @@
 
 use crate::{
     cli::{VoltCommand, VoltConfig},
+    core::io::write,
     core::{
         classes::init_data::{InitData, License},
         prompt::prompts::{Confirm, Input, Select},
@@ -60,6 +61,8 @@ impl VoltCommand for Init {
     /// ## Returns
     /// * `Result<()>`
     async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
         let _start = Instant::now();
 
         // get name of cwd
@@ -86,7 +89,12 @@ impl VoltCommand for Init {
                 name: String::from(PACKAGE_JSON),
             })?;
 
-        println!("{}", "Successfully Initialized package.json".bright_green());
+        write(
+            &"Successfully Initialized package.json"
+                .bright_green()
+                .to_string(),
+            &meta,
+        );
 
         Ok(())
     }
@@ -141,7 +149,10 @@ fn manual_initialization(default_name: String, config: &VoltConfig) -> Result<In
             break;
         }
 
-        println!("{}", "Name cannot contain special characters".red());
+        write(
+            &"Name cannot contain special characters".red().to_string(),
+            &config.meta(),
+        );
     }
 
     // Get "version"
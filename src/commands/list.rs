@@ -14,22 +14,79 @@
     limitations under the License.
 */
 
-//! Compress node_modules into node_modules.pack.
+//! List the dependency tree of the project.
 
 use async_trait::async_trait;
 use clap::Parser;
 use colored::Colorize;
-use miette::Result;
-//use node_semver::Version;
+use miette::{IntoDiagnostic, Result};
+use regex::Regex;
 
 use crate::{
     cli::{VoltCommand, VoltConfig},
-    core::utils::package::PackageJson,
+    core::io::{write, write_json},
+    core::model::lock_file::LockFile,
+    core::utils::package::{DependencyKind, PackageJson},
+    core::utils::voltapi::VoltPackage,
 };
 
+use serde::Serialize;
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+};
+
+/// List the dependency tree of your project
 #[derive(Debug, Parser)]
 pub struct List {
+    /// Only show dependencies up to this depth (0 only shows the direct dependencies)
+    #[clap(long)]
     depth: Option<usize>,
+
+    /// Only show `dependencies` and `optionalDependencies`
+    #[clap(long, conflicts_with = "dev")]
+    prod: bool,
+
+    /// Only show `devDependencies`
+    #[clap(long)]
+    dev: bool,
+
+    /// Only show the branches leading to packages matching this pattern (`*` matches anything)
+    #[clap(long)]
+    pattern: Option<String>,
+
+    /// Draw the tree with ASCII characters only
+    #[clap(long)]
+    ascii: bool,
+
+    /// Print the path of every package instead of a tree
+    #[clap(long)]
+    parseable: bool,
+}
+
+/// Output of `volt list --json`.
+#[derive(Debug, Serialize)]
+struct ListOutput {
+    name: String,
+    version: String,
+    path: String,
+    dependencies: BTreeMap<String, ListedDependency>,
+}
+
+#[derive(Debug, Serialize)]
+struct ListedDependency {
+    required: String,        // version range it's required with
+    version: Option<String>, // installed version
+    #[serde(rename = "type")]
+    kind: DependencyKind,
+    missing: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    deduped: bool, // already listed higher up in the tree, along with its dependencies
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    dependencies: BTreeMap<String, ListedDependency>,
 }
 
 // CREDIT:
@@ -37,213 +94,607 @@ pub struct List {
 // Repo: cargo-tree (tree.rs)
 // ------------------------------------------------
 pub struct Symbols {
-    _down: &'static str,
+    down: &'static str,
     tee: &'static str,
     ell: &'static str,
     right: &'static str,
 }
 
 pub static UTF8_SYMBOLS: Symbols = Symbols {
-    _down: "│",
+    down: "│",
     tee: "├",
     ell: "└",
     right: "─",
 };
 
-pub static _ASCII_SYMBOLS: Symbols = Symbols {
-    _down: "|",
+pub static ASCII_SYMBOLS: Symbols = Symbols {
+    down: "|",
     tee: "|",
     ell: "`",
     right: "-",
 };
 // ------------------------------------------------
 
+/// Builds the tree of a project from its lockfile.
+struct TreeBuilder<'a> {
+    lock_file: &'a LockFile,
+    node_modules: PathBuf,
+    depth: Option<usize>,
+    seen: HashSet<String>, // packages whose dependencies were already listed
+}
+
+impl TreeBuilder<'_> {
+    /// The dependency `name@required` of a package, `depth` levels down from the project.
+    fn dependency(
+        &mut self,
+        name: &str,
+        required: &str,
+        kind: DependencyKind,
+        depth: usize,
+    ) -> ListedDependency {
+        let package = self.lock_file.locked(name, required);
+
+        let mut dependency = ListedDependency {
+            required: required.to_string(),
+            version: package.map(|package| package.version.clone()),
+            kind,
+            missing: package.is_none(),
+            deduped: false,
+            path: package.map(|package| {
+                self.node_modules
+                    .join(".volt")
+                    .join(package.directory_name())
+                    .join("node_modules")
+                    .join(&package.name)
+            }),
+            dependencies: BTreeMap::new(),
+        };
+
+        let package = match package {
+            Some(package) => package,
+            None => return dependency,
+        };
+
+        if self.depth.map_or(false, |max| depth >= max) {
+            return dependency;
+        }
+
+        if !self
+            .seen
+            .insert(format!("{}@{}", package.name, package.version))
+        {
+            dependency.deduped = true;
+            return dependency;
+        }
+
+        for (name, range, kind) in dependencies(package) {
+            let child = self.dependency(name, range, kind, depth + 1);
+
+            dependency.dependencies.insert(name.to_string(), child);
+        }
+
+        dependency
+    }
+}
+
+/// Every dependency of a locked package sorted by name, peer dependencies included.
+fn dependencies(package: &VoltPackage) -> Vec<(&str, &str, DependencyKind)> {
+    let mut dependencies = [
+        (DependencyKind::Dependencies, &package.dependencies),
+        (
+            DependencyKind::OptionalDependencies,
+            &package.optional_dependencies,
+        ),
+        (DependencyKind::PeerDependencies, &package.peer_dependencies),
+    ]
+    .into_iter()
+    .flat_map(|(kind, dependencies)| {
+        dependencies
+            .iter()
+            .flatten()
+            .map(move |(name, range)| (name.as_str(), range.as_str(), kind))
+    })
+    .collect::<Vec<_>>();
+
+    // the first of the packages showing up several times lists its dependencies
+    dependencies.sort_unstable();
+
+    dependencies
+}
+
+/// Turn a `--pattern` into a regex matching whole package names.
+fn pattern_regex(pattern: &str) -> Result<Regex> {
+    Regex::new(&format!(
+        "^{}$",
+        regex::escape(pattern).replace("\\*", ".*")
+    ))
+    .into_diagnostic()
+}
+
+/// The locked packages matching `pattern`, or depending on one that does (`name@version`).
+fn leading_to(lock_file: &LockFile, pattern: &Regex) -> HashSet<String> {
+    let key = |package: &VoltPackage| format!("{}@{}", package.name, package.version);
+
+    let mut dependents: HashMap<String, Vec<&VoltPackage>> = HashMap::new();
+
+    for package in lock_file.packages.values() {
+        for (name, range, _) in dependencies(package) {
+            if let Some(dependency) = lock_file.locked(name, range) {
+                dependents.entry(key(dependency)).or_default().push(package);
+            }
+        }
+    }
+
+    let mut leads = HashSet::new();
+    let mut queue = lock_file
+        .packages
+        .values()
+        .filter(|package| pattern.is_match(&package.name))
+        .collect::<Vec<_>>();
+
+    while let Some(package) = queue.pop() {
+        if leads.insert(key(package)) {
+            queue.extend(dependents.get(&key(package)).into_iter().flatten());
+        }
+    }
+
+    leads
+}
+
+/// Only keep the dependencies matching `pattern`, or leading to one that does. Deduped
+/// dependencies don't list their own, so `leads` tells whether they lead to a match.
+fn retain_matching(
+    dependencies: &mut BTreeMap<String, ListedDependency>,
+    pattern: &Regex,
+    leads: &HashSet<String>,
+) {
+    dependencies.retain(|name, dependency| {
+        retain_matching(&mut dependency.dependencies, pattern, leads);
+
+        let deduped_lead = dependency.deduped
+            && dependency.version.as_ref().map_or(false, |version| {
+                leads.contains(&format!("{}@{}", name, version))
+            });
+
+        pattern.is_match(name) || !dependency.dependencies.is_empty() || deduped_lead
+    });
+}
+
+/// Print `dependencies` as a tree, each line starting with `prefix`.
+fn write_tree(
+    dependencies: &BTreeMap<String, ListedDependency>,
+    prefix: &str,
+    symbols: &Symbols,
+    lines: &mut Vec<String>,
+) {
+    let count = dependencies.len();
+
+    for (index, (name, dependency)) in dependencies.iter().enumerate() {
+        let last = index + 1 == count;
+
+        let mut line = format!(
+            "{}{}{}{} ",
+            prefix,
+            if last { symbols.ell } else { symbols.tee },
+            symbols.right,
+            symbols.right
+        );
+
+        if dependency.missing {
+            let missing = match dependency.kind {
+                DependencyKind::PeerDependencies => "UNMET PEER",
+                DependencyKind::OptionalDependencies => "UNMET OPTIONAL",
+                _ => "MISSING",
+            };
+
+            line.push_str(&format!(
+                "{} {}@{}",
+                missing.truecolor(255, 000, 000),
+                name.truecolor(000, 255, 000),
+                dependency.required.truecolor(000, 155, 000)
+            ));
+        } else {
+            line.push_str(&format!(
+                "{}@{}",
+                name.truecolor(000, 255, 000),
+                dependency
+                    .version
+                    .as_deref()
+                    .unwrap_or_default()
+                    .truecolor(000, 155, 000)
+            ));
+        }
+
+        let annotation = match dependency.kind {
+            DependencyKind::Dependencies => None,
+            DependencyKind::DevDependencies => Some("dev"),
+            DependencyKind::OptionalDependencies => Some("optional"),
+            DependencyKind::PeerDependencies => Some("peer"),
+        };
+
+        if let Some(annotation) = annotation {
+            line.push_str(
+                &format!(" ({})", annotation)
+                    .truecolor(125, 125, 125)
+                    .to_string(),
+            );
+        }
+
+        if dependency.deduped {
+            line.push_str(&" deduped".truecolor(125, 125, 125).to_string());
+        }
+
+        lines.push(line);
+
+        let prefix = format!("{}{}   ", prefix, if last { " " } else { symbols.down });
+
+        write_tree(&dependency.dependencies, &prefix, symbols, lines);
+    }
+}
+
+/// The path of every installed package of the tree, each package once.
+fn write_paths(
+    dependencies: &BTreeMap<String, ListedDependency>,
+    seen: &mut HashSet<PathBuf>,
+    lines: &mut Vec<String>,
+) {
+    for dependency in dependencies.values() {
+        if let Some(path) = &dependency.path {
+            if seen.insert(path.clone()) {
+                lines.push(path.display().to_string());
+            }
+        }
+
+        write_paths(&dependency.dependencies, seen, lines);
+    }
+}
+
+impl List {
+    /// The dependencies of the project selected by the flags, along with theirs.
+    fn tree(
+        &self,
+        pkg_json: &PackageJson,
+        lock_file: &LockFile,
+        node_modules: PathBuf,
+    ) -> Result<BTreeMap<String, ListedDependency>> {
+        let mut builder = TreeBuilder {
+            lock_file,
+            node_modules,
+            depth: self.depth,
+            seen: HashSet::new(),
+        };
+
+        let mut dependencies = BTreeMap::new();
+
+        for (kind, name, required) in pkg_json.dependencies_by_kind() {
+            let wanted = match kind {
+                DependencyKind::DevDependencies => !self.prod,
+                _ => !self.dev,
+            };
+
+            if !wanted {
+                continue;
+            }
+
+            let mut dependency = builder.dependency(name, required, kind, 0);
+
+            // direct dependencies are linked at the top of node_modules
+            let path = builder.node_modules.join(name);
+
+            if !path.exists() {
+                dependency.missing = true;
+            }
+
+            dependency.path = Some(path);
+
+            dependencies.insert(name.to_string(), dependency);
+        }
+
+        if let Some(pattern) = &self.pattern {
+            let pattern = pattern_regex(pattern)?;
+
+            retain_matching(
+                &mut dependencies,
+                &pattern,
+                &leading_to(lock_file, &pattern),
+            );
+        }
+
+        Ok(dependencies)
+    }
+}
+
 #[async_trait]
 impl VoltCommand for List {
     /// Execute the `volt list` command
     ///
-    /// List node_modules into node_modules.pack.
+    /// List the dependency tree of the project, from its lockfile.
     /// ## Arguments
-    /// * `app` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global configuration of volt
     /// ## Examples
     /// ```
-    /// // List node_modules into node_modules.pack
+    /// // List the dependencies of the project, two levels deep
     /// // .exec() is an async call so you need to await it
-    /// Add.exec(app).await;
+    /// List { depth: Some(1), .. }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
-        let symbols = &UTF8_SYMBOLS;
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let symbols = if self.ascii {
+            &ASCII_SYMBOLS
+        } else {
+            &UTF8_SYMBOLS
+        };
+
+        let meta = config.meta();
+        let cwd = config.cwd()?;
 
         // grab the project's package.json file to get primary dependencies
-        let (pkg_json, pkg_json_path) = match PackageJson::get() {
-            Ok(p) => (Some(p.0), Some(p.1)),
-            Err(_) => (None, None),
+        let pkg_json = match PackageJson::get_from_dir(&cwd) {
+            Ok((pkg_json, _)) => pkg_json,
+            Err(_) => {
+                if meta.json {
+                    miette::bail!("Missing 'package.json' file!");
+                }
+
+                write("Missing 'package.json' file!", &meta);
+
+                return Ok(());
+            }
         };
 
-        if let (Some(pkg_json), Some(pkg_json_path)) = (pkg_json, pkg_json_path) {
-            // package.json exists
-            let mut node_modules = pkg_json_path;
-
-            let project_info = format!(
-                "{}@{} {}",
-                &pkg_json.name,
-                &pkg_json.version,
-                &node_modules
-                    .parent()
-                    .unwrap()
-                    .canonicalize()
-                    .unwrap()
-                    .to_string_lossy()
-            );
-            println!("{}", project_info);
-
-            let last = pkg_json.dependencies.clone();
-
-            if let Some(packages) = pkg_json.dependencies {
-                node_modules.pop();
-                node_modules.push("node_modules");
-
-                // unsure if this is necessary?
-                if !packages.is_empty() {
-                    let last = last.unwrap().into_keys().last().unwrap();
-
-                    for package in packages {
-                        if node_modules.join(&package.0).exists() {
-                            let base_pkg =
-                                PackageJson::get_from_dir(&node_modules.join(&package.0))
-                                    .unwrap()
-                                    .0;
-                            // let current: Version = base_pkg.version.parse().unwrap();
-                            let current = base_pkg.version;
-
-                            let output = format!(
-                                "{}@{}",
-                                &base_pkg.name.truecolor(000, 255, 000),
-                                &current.to_string().truecolor(000, 155, 000),
-                            );
-
-                            if last.eq(&package.0) {
-                                println!("{}{} {}\n", symbols.ell, symbols.right, output);
-                            } else {
-                                println!("{}{} {}", symbols.tee, symbols.right, output);
-                            }
-                        } else {
-                            let output = format!(
-                                "{} {}@{}",
-                                "MISSING".to_string().truecolor(255, 000, 000),
-                                &package.0.truecolor(000, 255, 000),
-                                &package.1.truecolor(000, 155, 000)
-                            );
-
-                            if last.eq(&package.0) {
-                                println!("{}{} {}\n", symbols.ell, symbols.right, output);
-                            } else {
-                                println!("{}{} {}", symbols.tee, symbols.right, output);
-                            }
-                        }
-                    }
-                }
+        let path = cwd
+            .canonicalize()
+            .unwrap_or_else(|_| cwd.clone())
+            .to_string_lossy()
+            .to_string();
+
+        let lock_file = LockFile::load(config.lockfile()?)?;
+
+        let dependencies = self.tree(&pkg_json, &lock_file, config.node_modules()?)?;
+
+        let mut lines = vec![];
+
+        if self.parseable {
+            lines.push(path.clone());
+
+            write_paths(&dependencies, &mut HashSet::new(), &mut lines);
+        } else {
+            lines.push(format!("{}@{} {}", &pkg_json.name, &pkg_json.version, path));
+
+            if dependencies.is_empty() {
+                lines.push(format!(
+                    "{}{} (No dependencies)",
+                    symbols.ell, symbols.right
+                ));
             } else {
-                // package.json exists without any packages
-                let output = "(No dependencies)".to_string();
-                println!("{}{} {}\n", symbols.ell, symbols.right, output);
+                write_tree(&dependencies, "", symbols, &mut lines);
             }
-        } else {
-            // package.json just doesn't exist
-            let output = "Missing 'package.json' file!".to_string();
-            println!("{}", output);
+
+            lines.push(String::new());
         }
 
-        // let flags = &app.flags;
-
-        // let mut depth: u64 = 2;
-
-        // // if flags.contains(&"--depth".to_string()) {
-        // //     depth = app.args.iter().find_map(|s| s.parse().ok()).unwrap_or(2);
-        // // }
-
-        // let dirs = WalkDir::new("node_modules");
-
-        // let dependency_paths: Vec<_> = dirs
-        //     .into_iter()
-        //     .filter_map(Result::ok)
-        //     .filter(|entry| entry.file_type().is_dir() || entry.file_type().is_symlink())
-        //     .collect();
-
-        // if dependency_paths.len() == 1 {
-        //     println!("{}", "No Dependencies Found!".bright_cyan());
-        //     return Ok(());
-        // } else if dependency_paths.is_empty() {
-        //     println!(
-        //         "{} {} {}",
-        //         "Failed to find".bright_cyan(),
-        //         "node_modules".bright_yellow().bold(),
-        //         "folder".bright_cyan(),
-        //     );
-        //     return Ok(());
-        // }
-
-        // for dep in dependency_paths {
-        //     let dep_path = dep.path().to_str().unwrap();
-        //     let dep_path_split: Vec<&str> = dep_path.split('\\').collect();
-        //     let dep_name: &str = dep_path_split[dep_path_split.len() - 1];
-        //     if dep_name != "node_modules"
-        //         && dep_name != "scripts"
-        //         && !dep_name.starts_with("node_modules")
-        //     {
-        //         println!("{} {}", "-".bright_cyan(), dep_name.bright_blue().bold());
-        //         let dirs = WalkDir::new(format!("node_modules/{}/node_modules", dep_name))
-        //             .follow_links(true)
-        //             .max_depth((depth - 1) as usize);
-        //         let dependency_paths: Vec<_> = dirs
-        //             .into_iter()
-        //             .filter_map(Result::ok)
-        //             .filter(|entry| entry.file_type().is_dir() || entry.file_type().is_symlink())
-        //             .collect();
-
-        //         for dep in dependency_paths {
-        //             let dep_path = dep.path().to_str().unwrap();
-        //             let dep_path_split: Vec<&str> = dep_path.split('\\').collect();
-        //             let dep_name: &str = dep_path_split[dep_path_split.len() - 1];
-        //             if dep_name != "node_modules"
-        //                 && dep_name != "scripts"
-        //                 && !dep_path.contains("lib")
-        //                 && !dep_path.contains("src")
-        //                 && !dep_path.contains("dist")
-        //                 && !dep_path.contains("test")
-        //                 && !dep_name.starts_with("node_modules")
-        //             {
-        //                 for _ in 0..dep_path_split.len() {
-        //                     print!("  ");
-        //                 }
-        //                 let mut version = "".to_owned();
-        //                 for file in read_dir(std::env::temp_dir().join("volt")).unwrap() {
-        //                     let file_path: PathBuf = file.unwrap().path();
-        //                     let file_name: &str = file_path.to_str().unwrap();
-        //                     let file_split: Vec<&str> = file_name.split('\\').collect();
-        //                     let name: &str = file_split[file_split.len() - 1];
-        //                     if name.starts_with(dep_name) {
-        //                         let file_split: Vec<&str> = name.split('@').collect();
-        //                         let file_end = file_split[1];
-        //                         let file_split: Vec<&str> = file_end.split(".tgz").collect();
-        //                         version = file_split[0].to_owned();
-        //                     }
-        //                 }
-        //                 let padding = 50 - (dep_path_split.len() * 2);
-        //                 print!(
-        //                     "{} {:<width$}",
-        //                     "-".bright_purple(),
-        //                     dep_name,
-        //                     width = padding
-        //                 );
-        //                 println!("{}", version.clone().truecolor(190, 190, 190));
-        //             }
-        //         }
-        //     }
-        // }
+        write(&lines.join("\n"), &meta);
+
+        write_json(
+            &ListOutput {
+                name: pkg_json.name,
+                version: pkg_json.version,
+                path,
+                dependencies,
+            },
+            &meta,
+        )?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{pattern_regex, write_paths, List, ListedDependency};
+    use crate::core::model::lock_file::LockFile;
+    use crate::core::utils::package::PackageJson;
+    use crate::core::utils::voltapi::VoltPackage;
+    use clap::Parser;
+    use std::collections::{BTreeMap, HashSet};
+    use std::fs::create_dir_all;
+    use std::path::Path;
+
+    fn package(name: &str, version: &str, dependencies: &[(&str, &str)]) -> VoltPackage {
+        VoltPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            optional: false,
+            integrity: String::new(),
+            tarball: String::new(),
+            bin: None,
+            scripts: None,
+            dependencies: Some(
+                dependencies
+                    .iter()
+                    .map(|(name, range)| (name.to_string(), range.to_string()))
+                    .collect(),
+            ),
+            peer_dependencies: None,
+            peer_dependencies_meta: None,
+            optional_dependencies: None,
+            overrides: None,
+            engines: None,
+            os: None,
+            cpu: None,
+        }
+    }
+
+    // a -> b -> c, d -> b (deduped), dev dependency e, f is missing
+    fn project(node_modules: &Path) -> (PackageJson, LockFile) {
+        let dependencies = |list: &[(&str, &str)]| {
+            Some(
+                list.iter()
+                    .map(|(name, range)| (name.to_string(), range.to_string()))
+                    .collect::<BTreeMap<_, _>>(),
+            )
+        };
+
+        let pkg_json = PackageJson {
+            name: "project".to_string(),
+            version: "1.0.0".to_string(),
+            dependencies: dependencies(&[("a", "^1.0.0"), ("d", "^1.0.0"), ("f", "^1.0.0")]),
+            dev_dependencies: dependencies(&[("e", "^1.0.0")]),
+            ..Default::default()
+        };
+
+        let lock_file = LockFile {
+            packages: [
+                package("a", "1.0.0", &[("b", "1.0.0")]),
+                package("b", "1.0.0", &[("c", "1.0.0")]),
+                package("c", "1.0.0", &[]),
+                package("d", "1.0.0", &[("b", "1.0.0")]),
+                package("e", "1.0.0", &[]),
+            ]
+            .into_iter()
+            .map(|package| (format!("{}@{}", package.name, package.version), package))
+            .collect(),
+            ..Default::default()
+        };
+
+        for name in ["a", "d", "e"] {
+            create_dir_all(node_modules.join(name)).unwrap();
+        }
+
+        (pkg_json, lock_file)
+    }
+
+    fn tree(args: &[&str]) -> BTreeMap<String, ListedDependency> {
+        let dir = tempfile::tempdir().unwrap();
+        let (pkg_json, lock_file) = project(dir.path());
+
+        List::parse_from(["list"].iter().chain(args))
+            .tree(&pkg_json, &lock_file, dir.path().to_path_buf())
+            .unwrap()
+    }
+
+    // every dependency of the tree as `name@version`, indented by depth, with its markers
+    fn lines(dependencies: &BTreeMap<String, ListedDependency>, depth: usize) -> Vec<String> {
+        dependencies
+            .iter()
+            .flat_map(|(name, dependency)| {
+                let mut line = format!(
+                    "{}{}@{}",
+                    "  ".repeat(depth),
+                    name,
+                    dependency
+                        .version
+                        .as_deref()
+                        .unwrap_or(&dependency.required)
+                );
+
+                if dependency.missing {
+                    line.push_str(" missing");
+                }
+
+                if dependency.deduped {
+                    line.push_str(" deduped");
+                }
+
+                std::iter::once(line).chain(lines(&dependency.dependencies, depth + 1))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn builds_the_tree_from_the_lock_file() {
+        assert_eq!(
+            lines(&tree(&[]), 0),
+            vec![
+                "a@1.0.0",
+                "  b@1.0.0",
+                "    c@1.0.0",
+                "d@1.0.0",
+                "  b@1.0.0 deduped",
+                "e@1.0.0",
+                "f@^1.0.0 missing",
+            ]
+        );
+    }
+
+    #[test]
+    fn limits_the_depth_and_kind_of_dependencies() {
+        assert_eq!(
+            lines(&tree(&["--depth", "0"]), 0),
+            vec!["a@1.0.0", "d@1.0.0", "e@1.0.0", "f@^1.0.0 missing"]
+        );
+        assert_eq!(
+            lines(&tree(&["--depth", "1", "--prod"]), 0),
+            vec![
+                "a@1.0.0",
+                "  b@1.0.0",
+                "d@1.0.0",
+                "  b@1.0.0",
+                "f@^1.0.0 missing",
+            ]
+        );
+        assert_eq!(lines(&tree(&["--dev"]), 0), vec!["e@1.0.0"]);
+    }
+
+    #[test]
+    fn keeps_matches_behind_deduped_packages() {
+        assert_eq!(
+            lines(&tree(&["--pattern", "c"]), 0),
+            vec![
+                "a@1.0.0",
+                "  b@1.0.0",
+                "    c@1.0.0",
+                "d@1.0.0",
+                "  b@1.0.0 deduped",
+            ]
+        );
+        assert!(tree(&["--pattern", "z*"]).is_empty());
+    }
+
+    #[test]
+    fn lists_every_path_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let (pkg_json, lock_file) = project(dir.path());
+
+        let dependencies = List::parse_from(["list", "--prod"])
+            .tree(&pkg_json, &lock_file, dir.path().to_path_buf())
+            .unwrap();
+
+        let mut lines = vec![];
+        write_paths(&dependencies, &mut HashSet::new(), &mut lines);
+
+        let store = |package: &str| {
+            let (name, _) = package.split_once('@').unwrap();
+
+            dir.path()
+                .join(".volt")
+                .join(package)
+                .join("node_modules")
+                .join(name)
+                .display()
+                .to_string()
+        };
+
+        assert_eq!(
+            lines,
+            vec![
+                dir.path().join("a").display().to_string(),
+                store("b@1.0.0"),
+                store("c@1.0.0"),
+                dir.path().join("d").display().to_string(),
+                dir.path().join("f").display().to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_patterns() {
+        let pattern = pattern_regex("@babel/*").unwrap();
+
+        assert!(pattern.is_match("@babel/core"));
+        assert!(!pattern.is_match("babel"));
+        assert!(pattern_regex("react").unwrap().is_match("react"));
+        assert!(!pattern_regex("react").unwrap().is_match("react-dom"));
+    }
+}
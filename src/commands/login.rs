@@ -18,7 +18,9 @@
 
 use crate::{
     cli::{VoltCommand, VoltConfig},
+    core::io::write,
     core::prompt::prompts::{Input, Secret},
+    core::utils::errors::VoltError,
 };
 
 use async_trait::async_trait;
@@ -46,7 +48,9 @@ impl VoltCommand for Login {
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
         loop {
             let username_input = Input {
                 message: "Username".into(),
@@ -62,24 +66,18 @@ impl VoltCommand for Login {
             };
 
             // Get Username and Password
-            let username = username_input.run().unwrap_or_else(|err| {
-                eprintln!("{}", err);
-                std::process::exit(1);
-            });
+            let username = username_input.run().map_err(VoltError::PromptError)?;
 
-            let password = password_input.run().unwrap_or_else(|err| {
-                eprintln!("{}", err);
-                std::process::exit(1);
-            });
+            let password = password_input.run().map_err(VoltError::PromptError)?;
 
             // Log Error
             if let Err(e) = validate_username(&username) {
-                println!("{}", e);
+                write(&e, &meta);
                 continue;
             }
 
             if let Err(e) = validate_password(&username, &password) {
-                println!("{}", e);
+                write(&e, &meta);
                 continue;
             }
 
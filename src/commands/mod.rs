@@ -19,6 +19,7 @@ pub mod check;
 pub mod clean;
 pub mod clone;
 pub mod create;
+pub mod dedupe;
 pub mod deploy;
 pub mod discord;
 pub mod fix;
@@ -32,13 +33,16 @@ pub mod migrate;
 pub mod node;
 pub mod outdated;
 pub mod owner;
+pub mod pack;
 pub mod publish;
 pub mod remove;
 pub mod run;
 pub mod search;
 pub mod set;
 pub mod stat;
+pub mod store;
 pub mod tag;
 pub mod team;
 pub mod update;
 pub mod watch;
+pub mod why;
@@ -31,8 +31,18 @@ use clap::{Parser, Subcommand};
 use miette::Result;
 use node_semver::Version;
 use serde::{Deserialize, Deserializer};
+use std::path::PathBuf;
 
 use crate::cli::{VoltCommand, VoltConfig};
+use crate::core::utils::errors::VoltError;
+
+/// Directory the NodeJS versions are installed to.
+fn node_dir() -> Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .ok_or(VoltError::GetHomeDirError)?
+        .join("volt")
+        .join("node"))
+}
 
 #[derive(Deserialize)]
 #[serde(untagged)]
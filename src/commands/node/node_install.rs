@@ -5,7 +5,7 @@ use clap::CommandFactory;
 use clap::{ErrorKind, Parser};
 use colored::Colorize;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use miette::Result;
+use miette::{IntoDiagnostic, Result};
 use node_semver::{Range, Version};
 use std::fs::File;
 use std::io::Write;
@@ -13,7 +13,10 @@ use tempfile::tempdir;
 
 use crate::{
     cli::{VoltCommand, VoltConfig},
-    commands::node::NodeVersion,
+    commands::node::{node_dir, NodeVersion},
+    core::http::HttpClient,
+    core::io::write,
+    core::utils::errors::VoltError,
 };
 
 #[derive(Debug, PartialEq)]
@@ -86,7 +89,7 @@ impl VoltCommand for NodeInstall {
     // TODO: Handle errors with file already existing and handle file creation/deletion errors
     // TODO: Only make a tempdir if we have versions to download, i.e. verify all versions before
     //       creating the directory
-    async fn exec(self, _: VoltConfig) -> Result<()> {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
         if self.versions.is_empty() {
             let mut cmd = NodeInstall::command();
             cmd.error(
@@ -97,27 +100,31 @@ impl VoltCommand for NodeInstall {
         }
 
         tracing::debug!("On platform '{}' and arch '{}'", PLATFORM, ARCH);
-        let dir = tempdir().unwrap();
+        let dir = tempdir().into_diagnostic()?;
         tracing::debug!("Temp dir is {:?}", dir);
 
         let mirror = "https://nodejs.org/dist";
 
+        let client = HttpClient::new(&config)?;
+
         // Deserialize all available NodeJS versions
-        let node_versions: Vec<NodeVersion> = reqwest::get(format!("{}/index.json", mirror))
-            .await
-            .unwrap()
-            .json()
-            .await
-            .unwrap();
+        let node_versions: Vec<NodeVersion> =
+            serde_json::from_slice(&client.get(&format!("{}/index.json", mirror)).await?.body)
+                .into_diagnostic()?;
 
         let node_path = {
-            let datadir = dirs::data_dir().unwrap().join("volt").join("node");
+            let datadir = node_dir()?;
             if !datadir.exists() {
-                std::fs::create_dir_all(&datadir).unwrap();
+                std::fs::create_dir_all(&datadir).map_err(|e| VoltError::WriteFileError {
+                    source: e,
+                    name: datadir.display().to_string(),
+                })?;
             }
             datadir
         };
 
+        let meta = config.meta();
+
         let mut validversions = vec![];
         let download_url = format!("{}/", mirror);
 
@@ -127,7 +134,10 @@ impl VoltCommand for NodeInstall {
             let current_version: Option<Version> = if let Ok(ver) = v.parse() {
                 if cfg!(all(unix, target_arch = "X86")) && ver >= Version::parse("10.0.0").unwrap()
                 {
-                    println!("32 bit versions are not available for MacOS and Linux after version 10.0.0!");
+                    write(
+                        "32 bit versions are not available for MacOS and Linux after version 10.0.0!",
+                        &meta,
+                    );
                     continue;
                 }
 
@@ -156,22 +166,21 @@ impl VoltCommand for NodeInstall {
                 if cfg!(all(unix, target_arch = "X86"))
                     && Range::parse(">=10").unwrap().allows_any(&ver)
                 {
-                    println!("32 bit versions are not available for macos and linux after version 10.0.0!");
+                    write(
+                        "32 bit versions are not available for macos and linux after version 10.0.0!",
+                        &meta,
+                    );
                     continue;
                 }
 
                 max_ver
             } else {
-                // TODO: Not a valid version
-                println!("Invalid version: {}!", v.truecolor(255, 0, 0));
-                std::process::exit(1);
+                None
             };
 
-            if let Some(version) = current_version {
-                validversions.push(version)
-            } else {
-                println!("Invalid version: {}!", v.truecolor(255, 0, 0));
-                std::process::exit(1);
+            match current_version {
+                Some(version) => validversions.push(version),
+                None => return Err(VoltError::NodeVersionNotFound { version: v.clone() }.into()),
             }
         }
 
@@ -182,7 +191,8 @@ impl VoltCommand for NodeInstall {
             .clone()
             .into_iter()
             .map(|i| {
-                let download_url = format!("{download_url}v{i}/node-v{i}-{PLATFORM}-{ARCH}.tar.xz");
+                let name = format!("node-v{i}-{PLATFORM}-{ARCH}");
+                let download_url = format!("{download_url}v{i}/{name}.tar.xz");
 
                 let pb = mb.add(ProgressBar::new_spinner().with_style(
                     ProgressStyle::default_spinner().template("{spinner:.cyan} {msg}"),
@@ -193,7 +203,8 @@ impl VoltCommand for NodeInstall {
                 let node_path = node_path.clone();
 
                 let dir = dir.path().to_owned();
-                handle.spawn_blocking(move || {
+                let client = client.clone();
+                handle.clone().spawn_blocking(move || -> Result<()> {
                     if node_path.join(&i.to_string()).exists() {
                         pb.set_message(format!(
                             "{:8} {}",
@@ -201,7 +212,7 @@ impl VoltCommand for NodeInstall {
                             "Already Installed ✓"
                         ));
                         pb.finish();
-                        return;
+                        return Ok(());
                     }
 
                     pb.set_message(format!(
@@ -212,49 +223,66 @@ impl VoltCommand for NodeInstall {
 
                     pb.enable_steady_tick(10);
 
-                    let response = reqwest::blocking::get(&download_url).unwrap();
-                    let content = response.bytes().unwrap();
+                    let response = handle.block_on(client.get(&download_url))?;
+
+                    if !response.status.is_success() {
+                        return Err(VoltError::NetworkUnknownError {
+                            url: download_url,
+                            package_name: format!("node {}", i),
+                            code: response.status.as_str().to_string(),
+                        }
+                        .into());
+                    }
 
                     #[cfg(target_family = "unix")]
                     {
+                        let write_error =
+                            |source, path: &std::path::Path| VoltError::WriteFileError {
+                                source,
+                                name: path.display().to_string(),
+                            };
+
                         // Path to write the decompressed tarball to
-                        let fname = download_url.split('/').last().unwrap().to_string();
-                        let tarpath = dir.join(&fname.strip_suffix(".xz").unwrap());
+                        let tarpath = dir.join(format!("{name}.tar"));
+
+                        let decompressed = lzma::decompress(&response.body).map_err(|e| {
+                            VoltError::NodeArchiveError {
+                                url: download_url.clone(),
+                                reason: e.to_string(),
+                            }
+                        })?;
 
                         // Decompress the tarball
-                        let mut tarball = File::create(&tarpath).unwrap();
+                        let mut tarball =
+                            File::create(&tarpath).map_err(|e| write_error(e, &tarpath))?;
                         tarball
-                            .write_all(&lzma::decompress(&content).unwrap())
-                            .unwrap();
+                            .write_all(&decompressed)
+                            .map_err(|e| write_error(e, &tarpath))?;
 
                         // Make sure the first file handle is closed
                         drop(tarball);
 
                         // Have to reopen it for reading, File::create() opens for write only
-                        let tarball = File::open(&tarpath).unwrap();
+                        let tarball =
+                            File::open(&tarpath).map_err(|e| VoltError::ReadFileError {
+                                source: e,
+                                name: tarpath.display().to_string(),
+                            })?;
 
                         // Unpack the tarball
                         let mut w = tar::Archive::new(tarball);
-                        w.unpack(&node_path).unwrap();
-
-                        // TODO: Find a less disgusting way to do this?
-                        // Grab the name of the folder the tarball will extract to
-                        let p = tarpath
-                            .file_name()
-                            .unwrap()
-                            .to_str()
-                            .to_owned()
-                            .unwrap()
-                            .strip_suffix(".tar")
-                            .unwrap();
-
-                        let from = node_path.join(&p);
+                        w.unpack(&node_path)
+                            .map_err(|e| VoltError::NodeArchiveError {
+                                url: download_url.clone(),
+                                reason: e.to_string(),
+                            })?;
+
+                        // The tarball extracts to a folder named after it, rename it to just the
+                        // version number
+                        let from = node_path.join(&name);
                         let to = node_path.join(&i.to_string());
 
-                        // TODO: Find a way to handle this error correctly?
-                        // Rename the folder from the default set by the tarball
-                        // to just the version number
-                        let _ = std::fs::rename(from, to);
+                        std::fs::rename(&from, &to).map_err(|e| write_error(e, &to))?;
                     }
 
                     pb.set_message(format!(
@@ -263,11 +291,15 @@ impl VoltCommand for NodeInstall {
                         "Installed ✓"
                     ));
                     pb.finish();
+
+                    Ok(())
                 })
             })
             .collect();
 
-        futures::future::join_all(handles).await;
+        for result in futures::future::join_all(handles).await {
+            result.into_diagnostic()??;
+        }
 
         Ok(())
     }
@@ -2,7 +2,10 @@ use async_trait::async_trait;
 use clap::Parser;
 use miette::Result;
 
+use super::node_dir;
 use crate::cli::{VoltCommand, VoltConfig};
+use crate::core::io::write;
+use crate::core::utils::errors::VoltError;
 
 /// List available NodeJS versions
 #[derive(Debug, Parser)]
@@ -11,32 +14,37 @@ pub struct NodeList {}
 #[async_trait]
 impl VoltCommand for NodeList {
     // On windows, versions install to C:\Users\[name]\AppData\Roaming\volt\node\[version]
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
         let node_path = {
-            let datadir = dirs::data_dir().unwrap().join("volt").join("node");
+            let datadir = node_dir()?;
             if !datadir.exists() {
-                eprintln!("No NodeJS versions installed!");
-                std::process::exit(1);
+                return Err(VoltError::NoNodeVersions.into());
             };
             datadir
         };
 
         let mut versions = std::fs::read_dir(&node_path)
-            .unwrap()
-            .map(|d| d.unwrap().file_name().to_str().unwrap().to_owned())
+            .map_err(|e| VoltError::ReadFileError {
+                source: e,
+                name: node_path.display().to_string(),
+            })?
+            .filter_map(|d| d.ok()?.file_name().into_string().ok())
             .filter(|f| f != "current")
             .collect::<Vec<String>>();
 
         if versions.is_empty() {
-            eprintln!("No NodeJS versions installed!");
-            std::process::exit(0);
+            write("No NodeJS versions installed!", &meta);
+
+            return Ok(());
         }
 
         // Sort in descending order
         versions.sort_by(|a, b| b.cmp(a));
 
         for version in versions {
-            println!("{version}");
+            write(&version, &meta);
         }
 
         Ok(())
@@ -1,9 +1,10 @@
-use std::path::PathBuf;
-
+use super::node_dir;
 use crate::cli::{VoltCommand, VoltConfig};
+use crate::core::io::write;
+use crate::core::utils::errors::VoltError;
 use async_trait::async_trait;
 use clap::{CommandFactory, ErrorKind, Parser};
-use miette::{IntoDiagnostic, Result};
+use miette::Result;
 
 /// Uninstall a specified version of node
 #[derive(Debug, Parser)]
@@ -12,14 +13,10 @@ pub struct NodeRemove {
     versions: Vec<String>,
 }
 
-fn get_node_dir() -> PathBuf {
-    dirs::data_dir().unwrap().join("volt").join("node")
-}
-
 // #[cfg(unix)]
 #[async_trait]
 impl VoltCommand for NodeRemove {
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
         if self.versions.is_empty() {
             NodeRemove::command()
                 .error(
@@ -29,62 +26,60 @@ impl VoltCommand for NodeRemove {
                 .exit();
         }
 
-        let node_dir = get_node_dir();
+        let node_dir = node_dir()?;
+        let current = node_dir.join("current");
+
+        let read_error = |source, path: &std::path::Path| VoltError::ReadFileError {
+            source,
+            name: path.display().to_string(),
+        };
+        let write_error = |source, path: &std::path::Path| VoltError::WriteFileError {
+            source,
+            name: path.display().to_string(),
+        };
 
-        let current_dir = if node_dir.join("current").exists() {
-            let curr = std::fs::canonicalize(node_dir.join("current"))
-                .unwrap()
-                .parent()
-                .unwrap()
-                .to_owned();
-            Some(curr)
+        // `current` links to the `bin` directory of the version in use
+        let current_dir = if current.exists() {
+            let bin = std::fs::canonicalize(&current).map_err(|e| read_error(e, &current))?;
+
+            bin.parent().map(|dir| dir.to_path_buf())
         } else {
             None
         };
 
         let current_version = current_dir
             .as_ref()
-            .map(|dir| dir.file_name().unwrap().to_str().unwrap());
+            .and_then(|dir| dir.file_name())
+            .and_then(|name| name.to_str());
 
         for v in self.versions {
             let version_dir = node_dir.join(&v);
 
             if !version_dir.exists() {
-                eprintln!("Version {v} not installed");
+                write(&format!("Version {v} not installed"), &config.meta());
                 continue;
             }
 
-            if matches!(current_version, Some(ver) if ver == v) {
-                let current_dir = current_dir.as_ref().unwrap();
-                let current_bin = std::fs::read_dir(current_dir.join("bin")).unwrap();
+            if let Some(current_dir) = current_dir
+                .as_ref()
+                .filter(|_| current_version == Some(v.as_str()))
+            {
+                let bin = current_dir.join("bin");
+                let executable_dir = dirs::executable_dir().ok_or(VoltError::GetHomeDirError)?;
 
                 // Remove all the installed symlinks
-                for binary in current_bin {
-                    let b = binary.unwrap();
-                    let result =
-                        std::fs::remove_file(dirs::executable_dir().unwrap().join(b.file_name()));
+                for binary in std::fs::read_dir(&bin).map_err(|e| read_error(e, &bin))? {
+                    let installed =
+                        executable_dir.join(binary.map_err(|e| read_error(e, &bin))?.file_name());
 
-                    match result {
-                        Ok(_) => {}
-                        Err(e) => return Err(e).into_diagnostic(),
-                    }
+                    std::fs::remove_file(&installed).map_err(|e| write_error(e, &installed))?;
                 }
 
-                let result = std::fs::remove_file(node_dir.join("current"));
-
-                match result {
-                    Ok(_) => {}
-                    Err(e) => return Err(e).into_diagnostic(),
-                }
+                std::fs::remove_file(&current).map_err(|e| write_error(e, &current))?;
             }
 
             // Always remove the version directory, regardless of current version status
-            let result = std::fs::remove_dir_all(node_dir.join(v));
-
-            match result {
-                Ok(_) => {}
-                Err(e) => return Err(e).into_diagnostic(),
-            }
+            std::fs::remove_dir_all(&version_dir).map_err(|e| write_error(e, &version_dir))?;
         }
         Ok(())
     }
@@ -1,11 +1,12 @@
-use std::path::PathBuf;
-
 use async_trait::async_trait;
 
 use clap::Parser;
 use miette::Result;
 
+use super::node_dir;
 use crate::cli::{VoltCommand, VoltConfig};
+use crate::core::io::{write, write_verbose};
+use crate::core::utils::errors::VoltError;
 
 /// Switch current node version
 #[derive(Debug, Parser)]
@@ -14,67 +15,80 @@ pub struct NodeUse {
     version: String,
 }
 
-fn get_node_dir() -> PathBuf {
-    dirs::data_dir().unwrap().join("volt").join("node")
-}
-
 #[async_trait]
 impl VoltCommand for NodeUse {
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
         #[cfg(target_family = "windows")]
         {
-            use_windows(self.version).await;
+            use_windows(self.version, &meta).await?;
         }
 
         #[cfg(target_family = "unix")]
         {
-            let node_path = get_node_dir().join(&self.version);
-
-            if node_path.exists() {
-                let link_dir = dirs::home_dir().unwrap().join(".local").join("bin");
-
-                let to_install = node_path.join("bin");
-                let current = node_path.parent().unwrap().join("current");
-
-                // TODO: Handle file deletion errors
-                if current.exists() {
-                    // Remove all the currently installed links
-                    for f in std::fs::read_dir(&current).unwrap() {
-                        let original = f.unwrap().file_name();
-                        let installed = link_dir.join(&original);
-                        if installed.exists() {
-                            std::fs::remove_file(installed).unwrap();
-                        }
-                    }
+            let node_path = node_dir()?.join(&self.version);
 
-                    // Remove the old link
-                    std::fs::remove_file(&current).unwrap();
+            if !node_path.exists() {
+                return Err(VoltError::NodeVersionNotInstalled {
+                    version: self.version,
+                }
+                .into());
+            }
 
-                    // Make a new one to the currently installed version
-                    std::os::unix::fs::symlink(&to_install, current).unwrap();
-                } else {
-                    println!("Installing first version");
-                    std::os::unix::fs::symlink(&to_install, current).unwrap();
+            let link_dir = dirs::home_dir()
+                .ok_or(VoltError::GetHomeDirError)?
+                .join(".local")
+                .join("bin");
+
+            let to_install = node_path.join("bin");
+            let current = node_path.with_file_name("current");
+
+            let read_error = |source, path: &std::path::Path| VoltError::ReadFileError {
+                source,
+                name: path.display().to_string(),
+            };
+            let write_error = |source, path: &std::path::Path| VoltError::WriteFileError {
+                source,
+                name: path.display().to_string(),
+            };
+
+            if current.exists() {
+                // Remove all the currently installed links
+                for f in std::fs::read_dir(&current).map_err(|e| read_error(e, &current))? {
+                    let original = f.map_err(|e| read_error(e, &current))?.file_name();
+                    let installed = link_dir.join(&original);
+                    if installed.exists() {
+                        std::fs::remove_file(&installed).map_err(|e| write_error(e, &installed))?;
+                    }
                 }
 
-                // Install all the links for the new version
-                for f in std::fs::read_dir(&to_install).unwrap() {
-                    let original = f.unwrap().path();
-                    let fname = original.file_name().unwrap();
-                    let link = link_dir.join(fname);
+                // Remove the old link
+                std::fs::remove_file(&current).map_err(|e| write_error(e, &current))?;
+            } else {
+                write("Installing first version", &meta);
+            }
 
-                    // INFO: DOC: Need to run `rehash` in zsh for the changes to take effect
-                    println!("Linking to {:?} from {:?}", link, original);
+            // Make a new one to the currently installed version
+            std::os::unix::fs::symlink(&to_install, &current)
+                .map_err(|e| write_error(e, &current))?;
 
-                    // TODO: Do something with this error
-                    let _ = std::fs::remove_file(&link);
+            std::fs::create_dir_all(&link_dir).map_err(|e| write_error(e, &link_dir))?;
 
-                    // maybe ship `vnm` as a shell function to run `volt node use ... && rehash` on
-                    // zsh?
-                    let _symlink = std::os::unix::fs::symlink(original, link).unwrap();
-                }
-            } else {
-                println!("That version of node is not installed!\nTry \"volt node install {}\" to install that version.", self.version)
+            // Install all the links for the new version
+            for f in std::fs::read_dir(&to_install).map_err(|e| read_error(e, &to_install))? {
+                let original = f.map_err(|e| read_error(e, &to_install))?.path();
+                let link = link_dir.join(original.file_name().unwrap_or_default());
+
+                // INFO: DOC: Need to run `rehash` in zsh for the changes to take effect
+                write_verbose(&format!("Linking to {:?} from {:?}", link, original), &meta);
+
+                // the link is missing when this version is used for the first time
+                let _ = std::fs::remove_file(&link);
+
+                // maybe ship `vnm` as a shell function to run `volt node use ... && rehash` on
+                // zsh?
+                std::os::unix::fs::symlink(&original, &link).map_err(|e| write_error(e, &link))?;
             }
         }
         Ok(())
@@ -82,61 +96,60 @@ impl VoltCommand for NodeUse {
 }
 
 #[cfg(windows)]
-async fn use_windows(version: String) {
-    use std::{env, path::Path, process::Command};
-    use tokio::fs;
-
-    let node_path = get_node_dir().join(&version).join("node.exe");
-    let path = Path::new(&node_path);
-
-    if path.exists() {
-        println!("Using version {}", version);
-
-        let link_dir = dirs::data_dir()
-            .unwrap()
-            .join("volt")
-            .join("bin")
-            .into_os_string()
-            .into_string()
-            .unwrap();
-
-        let link_file = dirs::data_dir()
-            .unwrap()
-            .join("volt")
-            .join("bin")
-            .join("node.exe");
-        let link_file = Path::new(&link_file);
-
-        if link_file.exists() {
-            fs::remove_file(link_file).await.unwrap();
-        }
+async fn use_windows(version: String, meta: &crate::core::classes::meta::Meta) -> Result<()> {
+    use std::{env, process::Command};
 
-        let newfile = std::fs::copy(node_path, link_file);
+    let node_path = node_dir()?.join(&version).join("node.exe");
 
-        match newfile {
-            Ok(_) => {}
-            Err(_) => {
-                println!("Sorry, something went wrong.");
-                return;
-            }
-        }
+    if !node_path.exists() {
+        return Err(VoltError::NodeVersionNotInstalled { version }.into());
+    }
 
-        let vfpath = dirs::data_dir().unwrap().join("volt").join("current");
-        let vfpath = Path::new(&vfpath);
-        let vfile = std::fs::write(vfpath, version);
+    write(&format!("Using version {}", version), meta);
 
-        let path = env::var("PATH").unwrap();
-        if !path.contains(&link_dir) {
-            let command = format!("[Environment]::SetEnvironmentVariable('Path', [Environment]::GetEnvironmentVariable('Path', 'User') + '{}', 'User')", &link_dir);
+    let volt_dir = dirs::data_dir()
+        .ok_or(VoltError::GetHomeDirError)?
+        .join("volt");
 
-            Command::new("Powershell")
-                .args(&["-Command", &command])
-                .output()
-                .unwrap();
+    let link_dir = volt_dir.join("bin");
+    let link_file = link_dir.join("node.exe");
 
-            println!("PATH environment variable updated.\nYou will need to restart your terminal for changes to apply.");
-        }
-    } else {
-        println!("That version of node is not installed!\nTry \"volt node install {}\" to install that version.", version);
+    let write_error = |source, path: &std::path::Path| VoltError::WriteFileError {
+        source,
+        name: path.display().to_string(),
+    };
+
+    if link_file.exists() {
+        tokio::fs::remove_file(&link_file)
+            .await
+            .map_err(|e| write_error(e, &link_file))?;
     }
+
+    std::fs::copy(&node_path, &link_file).map_err(|e| write_error(e, &link_file))?;
+
+    let vfpath = volt_dir.join("current");
+    std::fs::write(&vfpath, &version).map_err(|e| write_error(e, &vfpath))?;
+
+    let link_dir = link_dir.display().to_string();
+
+    let path = env::var("PATH").map_err(|e| VoltError::EnvironmentError {
+        source: std::io::Error::new(std::io::ErrorKind::NotFound, e),
+        env: String::from("PATH"),
+    })?;
+
+    if !path.contains(&link_dir) {
+        let command = format!("[Environment]::SetEnvironmentVariable('Path', [Environment]::GetEnvironmentVariable('Path', 'User') + '{}', 'User')", &link_dir);
+
+        Command::new("Powershell")
+            .args(&["-Command", &command])
+            .output()
+            .map_err(|e| VoltError::EnvironmentError {
+                source: e,
+                env: String::from("PATH"),
+            })?;
+
+        write("PATH environment variable updated.\nYou will need to restart your terminal for changes to apply.", meta);
+    }
+
+    Ok(())
 }
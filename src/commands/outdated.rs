@@ -15,321 +15,365 @@
 */
 
 //! Check for outdated packages.
-use std::collections::HashMap;
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use async_trait::async_trait;
 use clap::Parser;
-use colored::Colorize;
+use comfy_table::{
+    modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL, Attribute, Cell, Color, ContentArrangement,
+    Table,
+};
+use futures::{stream::FuturesUnordered, StreamExt};
 use miette::Result;
-use node_semver::Version;
-use serde::Deserialize;
+use node_semver::{Range, Version};
+use serde::{Deserialize, Serialize};
 
 use crate::cli::{VoltCommand, VoltConfig};
-use crate::core::utils::package::PackageJson;
+use crate::core::http::HttpClient;
+use crate::core::io::{write, write_json};
+use crate::core::net::fetch_packument;
+use crate::core::utils::errors::VoltError;
+use crate::core::utils::package::{DependencyKind, PackageJson};
 
 // https://github.com/npm/registry/blob/master/docs/REGISTRY-API.md#version
-// abbreviated version uses "Accept: application/vnd.npm.install-v1+json" in header
-// this struct is specifically for the appreviated package responses from the registry.
-// this method is used to ensure faster responses when doing all dependencies.
-// all response fields must be optional in order to allow for different response
-// types, and the error response, which only contains a single "error" field.
-#[derive(Deserialize, Debug)]
-pub struct PackageResponse {
-    // resource for handling dynamic registry responses with Rust
-    // https://hamatti.org/posts/learning-rust-4-parsing-json-with-strong-types/
-
-    // resource for serde aliasing since the registry response uses "dist-tags"
-    // https://stackoverflow.com/questions/71328963/how-to-read-response-json-as-structs-when-they-contain-hyphens-in-key-names
-    name: Option<String>, // string name of the package
-    #[serde(alias = "dist-tags")]
-    dist_tags: Option<HashMap<String, String>>, // will always contain at least one tag which is "latest", for latest version
-    _versions: Option<HashMap<String, PackageVersion>>, // dictionary of versions with key: semver version string, value: PackageVersion struct
-    _modified: Option<String>,                          // string date of package last modified date
-    error: Option<String>,                              // registry request string error
+// the abbreviated packument ("Accept: application/vnd.npm.install-v1+json") has everything needed
+// to tell which versions of a package exist, so its large version manifests are left out.
+#[derive(Deserialize, Debug, Default)]
+pub struct Packument {
+    #[serde(rename = "dist-tags", default)]
+    pub dist_tags: HashMap<String, String>,
+    #[serde(default)]
+    pub versions: HashMap<String, serde_json::Value>,
+}
+
+impl Packument {
+    /// The version `range` resolves to: the highest version satisfying it, or the version a
+    /// dist-tag points to. Like npm, prereleases are only wanted by ranges naming a prerelease of
+    /// the same version.
+    pub fn wanted(&self, range: &str) -> Option<Version> {
+        if let Some(tagged) = self.dist_tags.get(range) {
+            return tagged.parse().ok();
+        }
+
+        let parsed = Range::parse(range).ok()?;
+
+        self.versions
+            .keys()
+            .filter_map(|version| version.parse::<Version>().ok())
+            .filter(|version| {
+                version.pre_release.is_empty()
+                    || range.contains(&format!(
+                        "{}.{}.{}-",
+                        version.major, version.minor, version.patch
+                    ))
+            })
+            .filter(|version| version.satisfies(&parsed))
+            .max()
+    }
+
+    /// The version the `latest` dist-tag points to.
+    pub fn latest(&self) -> Option<Version> {
+        self.dist_tags.get("latest")?.parse().ok()
+    }
+}
+
+/// Fetch the packuments of `names` concurrently, failing if any of the packages doesn't exist.
+pub async fn fetch_packuments<'a>(
+    client: &HttpClient,
+    config: &VoltConfig,
+    names: impl Iterator<Item = &'a str>,
+) -> Result<HashMap<String, Packument>> {
+    let mut names = names.collect::<Vec<_>>();
+
+    names.sort_unstable();
+    names.dedup();
+
+    // the client limits how many of these are in flight
+    names
+        .into_iter()
+        .map(|name| async move {
+            let packument = fetch_packument::<Packument>(client, config, name, None, false)
+                .await?
+                .ok_or_else(|| VoltError::PackageNotFound {
+                    url: format!("{}/{}", config.registry(), name.replace('/', "%2f")),
+                    package_name: name.to_string(),
+                })?;
+
+            Ok::<_, miette::Report>((name.to_string(), packument))
+        })
+        .collect::<FuturesUnordered<_>>()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
 }
 
-#[derive(Deserialize, Debug)]
-struct PackageVersion {
-    _name: String,    // package name
-    _version: String, // semver version string
+/// An entry of `volt outdated --json`.
+#[derive(Debug, Serialize)]
+struct OutdatedPackage {
+    name: String,
+    current: Option<String>, // installed version, `None` if missing
+    wanted: Option<String>,  // highest version satisfying the range in package.json
+    latest: Option<String>,
+    #[serde(rename = "type")]
+    kind: DependencyKind,
+    workspace: String, // name of the package.json the dependency is declared in
+    homepage: Option<String>,
 }
 
+/// Check the dependencies of the project for newer versions
 #[derive(Debug, Parser)]
 pub struct Outdated {
-    package: Option<String>,
+    /// Only check these packages
+    packages: Vec<String>,
+}
+
+/// A dependency declared in the package.json of the project, or of one of its workspaces.
+struct Declared {
+    name: String,
+    range: String,
+    kind: DependencyKind,
+    workspace: String,
+    installed: Option<PackageJson>, // package.json of the installed package
+}
+
+/// The package.json of `name` as installed in `node_modules` of `dir`, or of the root of the
+/// project if it's hoisted there.
+fn installed(name: &str, dir: &Path, root: &Path) -> Option<PackageJson> {
+    [dir, root].iter().find_map(|dir| {
+        let path = dir.join("node_modules").join(name);
+
+        if path.join("package.json").is_file() {
+            PackageJson::get_from_dir(&path)
+                .ok()
+                .map(|(package, _)| package)
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether the `current` version of a dependency is the one its range resolves to (if it resolves
+/// at all) and isn't behind `latest`. Missing dependencies are never up to date.
+fn up_to_date(
+    current: &Option<Version>,
+    wanted: &Option<Version>,
+    latest: &Option<Version>,
+) -> bool {
+    current.is_some() && (wanted.is_none() || current == wanted) && current >= latest
 }
 
 #[async_trait]
 impl VoltCommand for Outdated {
-    // TODO: figure out how to run with & without arguments
-
-    // TECHNICALLY DONE.
-    // TODO: figure out how to combine with primary dependencies, to check each dependency/version
-    //       on a project.
-
-    // TECHNICALLY DONE
-    // TODO: understand how semver works and can be used to our advantage
-
-    // TODO: validate that the user provided a dependency/package that is actually installed before
-    //       sending a request to the registry.
-
-    // TECHNICALLY DONE, SHOULD ONLY ACCEPT ONE VERSION
-    // TODO: Need to handle version ranges and exact versions separately
-
-    async fn exec(self, _: VoltConfig) -> Result<()> {
-        // realistically the 'node_modules' file should be in the same directory
-        // as the primary package.json file for a project.
-        let base_url = "https://registry.npmjs.org/";
-
-        // TODO:
-        // make this result optional entirely for when 'package.json' file doesn't exist!
-        let primary_pkg = PackageJson::get().unwrap();
-        let primary_deps = primary_pkg.0.dependencies;
-        let mut node_modules = primary_pkg.1;
-        node_modules.pop();
-        node_modules.push("node_modules");
-
-        //println!("Node modules are at {:?}", node_modules);
-
-        // add to the modules path for single dependency, so we can have the
-        // path to that dependency's folder within 'node_modules'
-        //modules.push(&self.dependency);
-
-        //println!("Dependency is at {modules:?}");
-
-        // grab the 'package.json' file for this specific dependency in order
-        // to get exact version information.
-        //let pkg_json = PackageJson::get_from_dir(modules).unwrap().0;
-        // convert the semver version string from the PackageJson struct into
-        // a node_semver Version struct which can be used to compare to the
-        // semver version string 'latest' returned in the registry response.
-        //let current: Version = pkg_json.version.parse().unwrap();
-        //println!("Got version {}", pkg_json.version);
-        //let deps = pkgjson.dependencies.unwrap();
-
-        //let pwd = std::env::current_dir().unwrap();
-        //println!("Running from {pwd:?}");
-
-        // in this case there are no packages installed in a project!
-        if let Some(deps) = &primary_deps {
-            //if !primary_deps.is_none() {
-            // test to see if an argument was provided, if not run for all dependencies???
-            if let Some(package_name) = self.package {
-                //&& !self.dependency.contains("!") {
-                if !deps.contains_key(&package_name) {
-                    let output = format!(
-                        "{} is not an installed package!",
-                        &package_name.truecolor(255, 000, 000)
-                    );
-                    println!("{}", output);
-                } else {
-                    //println!("Arguments: {}\n", self.dependency);
-
-                    let mut single = node_modules.clone();
-                    single.push(&package_name);
-
-                    //let pkg_json: PackageJson = PackageJson::get_from_dir(&single).unwrap().0;
-                    //let current: Version = pkg_json.version.parse().unwrap();
-
-                    // println!("Dependency is at {single:?}");
-                    //pkg_json = PackageJson::get_from_dir(&single).unwrap().0;
-                    //current = pkg_json.version.parse().unwrap();
-                    //pkg_json = PackageJson::get_from_dir(&single).unwrap().0;
-                    //current = pkg_json.version.parse().unwrap();
-                    //}
-                    // if !deps.contains_key(&self.dependency) {
-                    //     println!("Dependency not installed!");
-                    //     std::process::exit(1);
-                    // }
-                    // let curr = &deps[&self.dependency];
-
-                    // println!("current version is {curr}");
-                    // let current: Range = deps[&self.dependency].parse().unwrap();
-                    // need client to add headers
-                    let client = reqwest::Client::new();
-
-                    // NOTE: biggest help for handling dynamic JSON responses was hamatti.org!!!!
-                    // https://hamatti.org/posts/learning-rust-4-parsing-json-with-strong-types/
-                    // https://stackoverflow.com/questions/47911513/how-do-i-set-the-request-headers-using-reqwest
-                    // https://stackoverflow.com/questions/63872942/how-can-an-arbitrary-json-structure-be-deserialized-with-reqwest-get-in-rust
-                    // https://stackoverflow.com/questions/68357867/dynamically-receive-json-data-in-rust-with-reqwest
-
-                    /* working version without serde_json structure, this format uses assumed structure...
-                    let package_info = client
-                        .get(format!("{}/{}", base_url, &self.dependency))
-                        .header("Accept", "application/vnd.npm.install-v1+json")
-                        .send()
-                        .await
-                        .unwrap()
-                        .json::<serde_json::Value>()
-                        .await
-                        .unwrap();
-                    println!("Testing: {}", package_info);
-                    */
-
-                    // this format assigns the JSON into the appropriate
-                    // fields within the Package struct.
-                    let package_info: PackageResponse = client
-                        .get(format!("{}/{}", base_url, &package_name))
-                        .header("Accept", "application/vnd.npm.install-v1+json")
-                        .send()
-                        .await
-                        .unwrap()
-                        .json()
-                        .await
-                        .unwrap();
-
-                    // check to see if the optional error value was none
-                    // which indicates the request was successful.
-                    if package_info.error.is_none() {
-                        //println!("Package name: {}\n", package_info.name.unwrap());
-                        //println!("Tags: {:?}", package_info.dist_tags.as_ref().unwrap());
-
-                        let latest: Version = package_info
-                            .dist_tags
-                            .as_ref()
-                            .unwrap()
-                            .get("latest")
-                            .unwrap()
-                            .parse()
-                            .unwrap();
-
-                        println!("Package \t\t| Current \t\t| Latest\n---------------------------------------------------------");
-                        if !single.exists() {
-                            let output = format!(
-                                "{} \t\t| {} \t\t| {}",
-                                package_info.name.unwrap().truecolor(255, 000, 000),
-                                "MISSING",
-                                latest.to_string().truecolor(55, 125, 235),
-                            );
-                            println!("{}", output);
-                        } else {
-                            let pkg_json: PackageJson =
-                                PackageJson::get_from_dir(&single).unwrap().0;
-                            let current: Version = pkg_json.version.parse().unwrap();
-                            if current < latest {
-                                let output = format!(
-                                    "{} \t\t| {} \t\t| {}",
-                                    package_info.name.unwrap().truecolor(255, 000, 000),
-                                    current,
-                                    latest.to_string().truecolor(55, 125, 235),
-                                );
-                                println!("{}", output);
-                            } else {
-                                let output = format!(
-                                    "{} \t\t| {} \t\t| {}",
-                                    package_info.name.unwrap().truecolor(000, 255, 000),
-                                    current,
-                                    latest.to_string().truecolor(55, 125, 235),
-                                );
-                                println!("{}", output);
-                            }
-                        }
-                    } else {
-                        let output = format!(
-                            "Error fetching package info for {} due to: \n\t{}",
-                            package_info.name.unwrap(),
-                            package_info.error.unwrap().truecolor(255, 000, 000)
-                        );
-                        println!("{}", output);
-                    }
-                }
-            } else {
-                //println!("No dependency provided, run for all????");
-
-                let multiple = node_modules.clone();
-                //println!("{:?}", &multiple);
-
-                let mut found_outdated = false;
-
-                let dependencies = primary_deps.unwrap();
-
-                for dependency in dependencies {
-                    //multiple.push(dependency.0);
-                    //println!("Dependency is at {multiple:?}");
-                    let dep_name = dependency.0;
-                    //println!("{:?}", &multiple.join(&dep_name));
-
-                    let client = reqwest::Client::new();
-
-                    let package_info: PackageResponse = client
-                        .get(format!("{}/{}", base_url, &dep_name))
-                        .header("Accept", "application/vnd.npm.install-v1+json")
-                        .send()
-                        .await
-                        .unwrap()
-                        .json()
-                        .await
-                        .unwrap();
-
-                    // check to see if the optional error value was none
-                    // which indicates the request was successful.
-                    if package_info.error.is_none() {
-                        //println!("Package name: {}\n", package_info.name.unwrap());
-                        //println!("Tags: {:?}", package_info.dist_tags.as_ref().unwrap());
-
-                        let latest: Version = package_info
-                            .dist_tags
-                            .as_ref()
-                            .unwrap()
-                            .get("latest")
-                            .unwrap()
-                            .parse()
-                            .unwrap();
-
-                        //println!("Latest version: {}", latest); :? is debug print
-                        //println!("Current version: {:?}", current);
-
-                        if !multiple.join(&dep_name).exists() {
-                            if !found_outdated {
-                                println!("Package \t\t| Current \t\t| Latest\n---------------------------------------------------------");
-                                found_outdated = true;
-                            }
-
-                            let output = format!(
-                                "{} \t\t| {} \t\t| {}",
-                                package_info.name.unwrap().truecolor(255, 000, 000),
-                                "MISSING",
-                                latest.to_string().truecolor(55, 125, 235),
-                            );
-                            println!("{}", output);
-                        } else {
-                            let pkg_json = PackageJson::get_from_dir(&multiple.join(&dep_name))
-                                .unwrap()
-                                .0;
-                            let current: Version = pkg_json.version.parse().unwrap();
-                            if current < latest {
-                                if !found_outdated {
-                                    println!("Package \t\t| Current \t\t| Latest\n---------------------------------------------------------");
-                                    found_outdated = true;
-                                }
-
-                                let output = format!(
-                                    "{} \t\t| {} \t\t| {}",
-                                    package_info.name.unwrap().truecolor(255, 000, 000),
-                                    current,
-                                    latest.to_string().truecolor(55, 125, 235),
-                                );
-                                println!("{}", output);
-                            }
-                        }
-                    } else {
-                        let output = format!(
-                            "Error fetching package info for {} due to: \n\t{}",
-                            package_info.name.unwrap(),
-                            package_info.error.unwrap().truecolor(255, 000, 000)
-                        );
-                        println!("{}", output);
-                    }
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
+        let (root_package, root_path) = PackageJson::get_from_dir(&config.cwd()?)?;
+        let root = root_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        let mut projects: Vec<(PackageJson, PathBuf)> = root_package
+            .workspace_dirs(&root)
+            .into_iter()
+            .filter_map(|dir| {
+                PackageJson::get_from_dir(&dir)
+                    .ok()
+                    .map(|(package, _)| (package, dir))
+            })
+            .collect();
+
+        projects.insert(0, (root_package, root.clone()));
+
+        let mut declared = vec![];
+
+        for (package, dir) in &projects {
+            for (kind, name, range) in package.dependencies_by_kind() {
+                if !self.packages.is_empty() && !self.packages.iter().any(|p| p == name) {
+                    continue;
                 }
 
-                if !found_outdated {
-                    println!("All packages are up to date!")
-                }
+                declared.push(Declared {
+                    name: name.to_string(),
+                    range: range.to_string(),
+                    kind,
+                    workspace: package.name.clone(),
+                    installed: installed(name, dir, &root),
+                });
             }
-        } else {
-            println!("No packages are installed in the current project!")
         }
-        Ok(())
+
+        for name in &self.packages {
+            if !declared.iter().any(|dependency| dependency.name == *name) {
+                return Err(VoltError::PackageNotInstalled { spec: name.clone() }.into());
+            }
+        }
+
+        let client = HttpClient::new(&config)?;
+
+        let packuments = fetch_packuments(
+            &client,
+            &config,
+            declared.iter().map(|dependency| dependency.name.as_str()),
+        )
+        .await?;
+
+        let mut outdated = vec![];
+
+        for dependency in declared {
+            let packument = &packuments[&dependency.name];
+
+            let current = dependency
+                .installed
+                .as_ref()
+                .and_then(|installed| installed.version.parse::<Version>().ok());
+            let wanted = packument.wanted(&dependency.range);
+            let latest = packument.latest();
+
+            if up_to_date(&current, &wanted, &latest) {
+                continue;
+            }
+
+            outdated.push(OutdatedPackage {
+                name: dependency.name,
+                current: current.map(|version| version.to_string()),
+                wanted: wanted.map(|version| version.to_string()),
+                latest: latest.map(|version| version.to_string()),
+                kind: dependency.kind,
+                workspace: dependency.workspace,
+                homepage: dependency
+                    .installed
+                    .and_then(|installed| installed.homepage),
+            });
+        }
+
+        outdated.sort_by(|a, b| (&a.workspace, &a.name).cmp(&(&b.workspace, &b.name)));
+
+        write_json(&outdated, &meta)?;
+
+        if outdated.is_empty() {
+            write("All packages are up to date!", &meta);
+
+            return Ok(());
+        }
+
+        let mut table = Table::new();
+
+        table
+            .load_preset(UTF8_FULL)
+            .apply_modifier(UTF8_ROUND_CORNERS)
+            .set_content_arrangement(ContentArrangement::Dynamic);
+
+        if meta.no_color {
+            table.force_no_tty();
+        }
+
+        let workspaces = projects.len() > 1;
+
+        let mut header = vec!["Package", "Current", "Wanted", "Latest", "Type"];
+
+        if workspaces {
+            header.push("Workspace");
+        }
+
+        header.push("Homepage");
+
+        table.set_header(
+            header
+                .into_iter()
+                .map(|title| Cell::new(title).add_attribute(Attribute::Bold)),
+        );
+
+        for package in &outdated {
+            // red when an update is available within the declared range, yellow when only a
+            // new major is
+            let color = if package.current.is_none() || package.current != package.wanted {
+                Color::Red
+            } else {
+                Color::Yellow
+            };
+
+            let mut row = vec![
+                Cell::new(&package.name).fg(color),
+                Cell::new(package.current.as_deref().unwrap_or("MISSING")),
+                Cell::new(package.wanted.as_deref().unwrap_or("-")).fg(Color::Green),
+                Cell::new(package.latest.as_deref().unwrap_or("-")).fg(Color::Magenta),
+                Cell::new(package.kind.field()),
+            ];
+
+            if workspaces {
+                row.push(Cell::new(&package.workspace));
+            }
+
+            row.push(Cell::new(package.homepage.as_deref().unwrap_or("")));
+
+            table.add_row(row);
+        }
+
+        write(&table.to_string(), &meta);
+
+        Err(VoltError::OutdatedPackages {
+            count: outdated.len(),
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{up_to_date, Packument};
+    use node_semver::Version;
+
+    fn packument(versions: &[&str], tags: &[(&str, &str)]) -> Packument {
+        Packument {
+            dist_tags: tags
+                .iter()
+                .map(|(tag, version)| (tag.to_string(), version.to_string()))
+                .collect(),
+            versions: versions
+                .iter()
+                .map(|version| (version.to_string(), serde_json::Value::Null))
+                .collect(),
+        }
+    }
+
+    fn version(version: &str) -> Option<Version> {
+        Some(version.parse().unwrap())
+    }
+
+    #[test]
+    fn resolves_wanted_versions() {
+        let packument = packument(
+            &["1.0.0", "1.2.0", "1.3.0-beta.1", "2.0.0", "3.0.0-rc.1"],
+            &[("latest", "2.0.0"), ("next", "3.0.0-rc.1")],
+        );
+
+        assert_eq!(packument.wanted("^1.0.0"), version("1.2.0"));
+        assert_eq!(packument.wanted("*"), version("2.0.0"));
+        assert_eq!(packument.wanted("next"), version("3.0.0-rc.1"));
+        assert_eq!(packument.wanted("^1.3.0-beta.0"), version("1.3.0-beta.1"));
+        assert_eq!(packument.wanted("^4.0.0"), None);
+        assert_eq!(packument.wanted("not a range"), None);
+        assert_eq!(packument.latest(), version("2.0.0"));
+    }
+
+    #[test]
+    fn tells_up_to_date_dependencies() {
+        let (one, two) = (version("1.0.0"), version("2.0.0"));
+
+        assert!(up_to_date(&two, &two, &two));
+        assert!(up_to_date(&two, &None, &two));
+        assert!(up_to_date(&two, &two, &None));
+        // newer than latest, e.g. a prerelease
+        assert!(up_to_date(&two, &two, &one));
+
+        assert!(!up_to_date(&one, &two, &two));
+        assert!(!up_to_date(&one, &one, &two));
+        assert!(!up_to_date(&None, &two, &two));
     }
 }
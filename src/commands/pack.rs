@@ -0,0 +1,811 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Create a tarball of the package, the one that gets published.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use indicatif::HumanBytes;
+use miette::{IntoDiagnostic, Result};
+use regex::Regex;
+use serde::Serialize;
+use ssri::Algorithm;
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::io::{write, write_json},
+    core::utils::compress_gzip,
+    core::utils::errors::VoltError,
+    core::utils::package::{NewBin, PackageJson},
+};
+
+/// Create a tarball of the package
+#[derive(Debug, Parser)]
+pub struct Pack {
+    /// Only list the files that would be packed
+    #[clap(long)]
+    dry_run: bool,
+}
+
+/// Files and directories that are never packed, wherever they are.
+const ALWAYS_EXCLUDED: [&str; 17] = [
+    ".npmignore",
+    ".gitignore",
+    ".git",
+    ".svn",
+    ".hg",
+    "CVS",
+    ".DS_Store",
+    "._*",
+    ".*.swp",
+    ".lock-wscript",
+    ".wafpickle-*",
+    ".npmrc",
+    "npm-debug.log",
+    "config.gypi",
+    "*.orig",
+    "node_modules",
+    "package-lock.json",
+];
+
+/// Files at the root of the package that are packed whatever `files` and the ignore files say,
+/// in any case.
+const ALWAYS_INCLUDED: [&str; 4] = ["package.json", "README*", "LICENSE*", "LICENCE*"];
+
+/// Modification time of every entry of the tarball, the one npm uses: 1985-10-26T08:15:00Z.
+const MTIME: u64 = 499162500;
+
+/// A line of a `.gitignore`, a `.npmignore` or of the `files` of package.json.
+#[derive(Debug)]
+struct Pattern {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Pattern {
+    /// Parse a pattern, `None` for blank lines and comments.
+    fn parse(line: &str) -> Option<Pattern> {
+        let line = line.trim_end();
+
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+
+        let line = line.trim_start_matches("./");
+
+        // patterns with a `/` are relative to the directory of their file, others match at any depth
+        let anchor = if line.trim_start_matches('/').contains('/') || line.starts_with('/') {
+            "^"
+        } else {
+            "^(?:.*/)?"
+        };
+
+        let regex = Regex::new(&format!(
+            "{}{}$",
+            anchor,
+            glob_regex(line.trim_start_matches('/'))
+        ))
+        .ok()?;
+
+        Some(Pattern {
+            regex,
+            negated,
+            dir_only,
+        })
+    }
+
+    /// The pattern matching paths in any case, e.g. `readme.md` for `README*`.
+    fn case_insensitive(self) -> Option<Pattern> {
+        Some(Pattern {
+            regex: Regex::new(&format!("(?i){}", self.regex.as_str())).ok()?,
+            ..self
+        })
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        (is_dir || !self.dir_only) && self.regex.is_match(path)
+    }
+}
+
+/// The regex matching the same paths as `glob`.
+fn glob_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let class = chars.clone().take_while(|c| *c != ']').collect::<String>();
+
+                if class.len() == chars.clone().count() {
+                    // no closing bracket
+                    regex.push_str("\\[");
+                    continue;
+                }
+
+                for _ in 0..=class.len() {
+                    chars.next();
+                }
+
+                let class = match class.strip_prefix('!') {
+                    Some(class) => format!("^{}", class),
+                    None => class,
+                };
+
+                regex.push_str(&format!("[{}]", class.replace('\\', "\\\\")));
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex
+}
+
+/// Whether the last of `patterns` that matches `path` ignores it.
+fn ignored<'a>(
+    patterns: impl IntoIterator<Item = (&'a str, &'a Pattern)>,
+    path: &str,
+    is_dir: bool,
+) -> bool {
+    let mut ignored = false;
+
+    for (base, pattern) in patterns {
+        let relative = match base {
+            "" => path,
+            base => match path
+                .strip_prefix(base)
+                .and_then(|path| path.strip_prefix('/'))
+            {
+                Some(relative) => relative,
+                None => continue,
+            },
+        };
+
+        if pattern.matches(relative, is_dir) {
+            ignored = !pattern.negated;
+        }
+    }
+
+    ignored
+}
+
+/// Whether the last of the `files` patterns that matches `path` lists it, a pattern matching a
+/// directory lists everything in it.
+fn listed(files: &[Pattern], path: &str) -> bool {
+    let ancestors = path
+        .match_indices('/')
+        .map(|(index, _)| (&path[..index], true))
+        .chain(std::iter::once((path, false)));
+
+    files.iter().fold(false, |listed, pattern| {
+        if ancestors
+            .clone()
+            .any(|(ancestor, is_dir)| pattern.matches(ancestor, is_dir))
+        {
+            !pattern.negated
+        } else {
+            listed
+        }
+    })
+}
+
+/// Patterns of the ignore files read so far, with the directory they are relative to.
+type Ignores = Vec<(String, Pattern)>;
+
+/// A file of the tarball.
+#[derive(Debug, Serialize)]
+pub(crate) struct PackedFile {
+    pub path: String,
+    pub size: u64,
+    pub mode: u32,
+}
+
+/// Output of `volt pack --json`, also used to publish the tarball.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Packed {
+    pub name: String,
+    pub version: String,
+    pub filename: String,
+    pub files: Vec<PackedFile>,
+    pub entry_count: usize,
+    pub unpacked_size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shasum: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+    #[serde(skip)]
+    pub tarball: Vec<u8>,
+}
+
+/// Name of the tarball of `name` at `version`, `@scope/name` gives `scope-name-1.0.0.tgz`.
+pub(crate) fn tarball_name(name: &str, version: &str) -> String {
+    format!(
+        "{}-{}.tgz",
+        name.trim_start_matches('@').replace('/', "-"),
+        version
+    )
+}
+
+/// Run the `script` of `package_json` in `root` if it has one, with the binaries of the
+/// dependencies in the `PATH`.
+pub(crate) fn run_script(
+    root: &Path,
+    package_json: &PackageJson,
+    script: &str,
+    config: &VoltConfig,
+) -> Result<()> {
+    let command = match package_json
+        .scripts
+        .as_ref()
+        .and_then(|scripts| scripts.get(script))
+    {
+        Some(command) => command,
+        None => return Ok(()),
+    };
+
+    write(
+        &format!(
+            "> {}@{} {}\n> {}",
+            package_json.name, package_json.version, script, command
+        )
+        .truecolor(156, 156, 156)
+        .to_string(),
+        &config.meta(),
+    );
+
+    let mut paths = vec![root.join("node_modules").join(".bin")];
+
+    if let Some(path) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&path));
+    }
+
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    shell
+        .arg(command)
+        .current_dir(root)
+        .env("PATH", std::env::join_paths(paths).into_diagnostic()?)
+        .env("npm_lifecycle_event", script)
+        .env("npm_package_name", &package_json.name)
+        .env("npm_package_version", &package_json.version)
+        .env(
+            "npm_config_user_agent",
+            concat!("volt/", env!("CARGO_PKG_VERSION")),
+        );
+
+    let failed = |e: std::io::Error| VoltError::ScriptFailed {
+        script: script.to_string(),
+        reason: e.to_string(),
+    };
+
+    // with `--json` only the output of volt goes to stdout, the script's is moved to stderr
+    let status = if config.meta().json {
+        let output = shell.stderr(Stdio::inherit()).output().map_err(failed)?;
+
+        std::io::stderr()
+            .write_all(&output.stdout)
+            .map_err(failed)?;
+
+        output.status
+    } else {
+        shell.status().map_err(failed)?
+    };
+
+    if !status.success() {
+        return Err(VoltError::ScriptFailed {
+            script: script.to_string(),
+            reason: status.to_string(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// The files of the package in `root` to pack, as sorted `/` separated relative paths.
+pub(crate) fn package_files(root: &Path, package_json: &PackageJson) -> Result<Vec<String>> {
+    let excluded = ALWAYS_EXCLUDED
+        .iter()
+        .filter_map(|pattern| Pattern::parse(pattern))
+        .collect::<Vec<_>>();
+
+    let included = ALWAYS_INCLUDED
+        .iter()
+        .filter_map(|pattern| Pattern::parse(&format!("/{}", pattern))?.case_insensitive())
+        .collect::<Vec<_>>();
+
+    // the `main` and `bin` files are packed even when `files` leaves them out
+    let mut entry_points = package_json.main.iter().cloned().collect::<Vec<_>>();
+
+    match &package_json.bin {
+        Some(NewBin::Str(bin)) => entry_points.push(bin.clone()),
+        Some(NewBin::BTreeMap(bins)) => entry_points.extend(bins.values().cloned()),
+        None => {}
+    }
+
+    let entry_points = entry_points
+        .iter()
+        .map(|path| path.trim_start_matches("./").replace('\\', "/"))
+        .collect::<Vec<_>>();
+
+    let files = package_json.files.as_ref().map(|files| {
+        files
+            .iter()
+            .filter_map(|pattern| Pattern::parse(pattern))
+            .collect::<Vec<_>>()
+    });
+
+    let mut packed = vec![];
+    let mut ignores = vec![];
+
+    walk(
+        root,
+        "",
+        &mut ignores,
+        &mut |path, is_dir, ignores: &Ignores| {
+            let relative_ignores = || {
+                ignores
+                    .iter()
+                    .map(|(base, pattern)| (base.as_str(), pattern))
+            };
+
+            if excluded.iter().any(|pattern| pattern.matches(path, is_dir)) {
+                return false;
+            }
+
+            if is_dir {
+                return !ignored(relative_ignores(), path, true);
+            }
+
+            if included.iter().any(|pattern| pattern.matches(path, false)) {
+                packed.push(path.to_string());
+                return true;
+            }
+
+            if ignored(relative_ignores(), path, false) {
+                return false;
+            }
+
+            let listed = match &files {
+                Some(files) => {
+                    entry_points.iter().any(|entry_point| entry_point == path)
+                        || listed(files, path)
+                }
+                None => true,
+            };
+
+            if listed {
+                packed.push(path.to_string());
+            }
+
+            listed
+        },
+        package_json.files.is_some(),
+    )?;
+
+    packed.sort();
+
+    Ok(packed)
+}
+
+/// Walk the directory `dir` of `root`, depth first in sorted order, reading the ignore files of
+/// every directory. `visit` is called for every file and directory, a directory is only walked
+/// when it returns `true` for it.
+fn walk(
+    root: &Path,
+    dir: &str,
+    ignores: &mut Ignores,
+    visit: &mut dyn FnMut(&str, bool, &Ignores) -> bool,
+    has_files: bool,
+) -> Result<()> {
+    let path = root.join(dir);
+
+    let read_error = |e| VoltError::ReadFileError {
+        source: e,
+        name: path.display().to_string(),
+    };
+
+    let count = ignores.len();
+
+    // the ignore files at the root don't apply when package.json lists its `files`
+    if !(has_files && dir.is_empty()) {
+        // `.npmignore` replaces `.gitignore` in its directory
+        for name in [".npmignore", ".gitignore"] {
+            if let Ok(data) = fs::read_to_string(path.join(name)) {
+                ignores.extend(
+                    data.lines()
+                        .filter_map(Pattern::parse)
+                        .map(|pattern| (dir.to_string(), pattern)),
+                );
+                break;
+            }
+        }
+    }
+
+    let mut entries = fs::read_dir(&path)
+        .map_err(read_error)?
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(read_error)?;
+
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let file_type = entry.file_type().map_err(read_error)?;
+
+        // links aren't packed, like npm
+        if file_type.is_symlink() {
+            continue;
+        }
+
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative = if dir.is_empty() {
+            name
+        } else {
+            format!("{}/{}", dir, name)
+        };
+
+        if visit(&relative, file_type.is_dir(), ignores) && file_type.is_dir() {
+            walk(root, &relative, ignores, visit, has_files)?;
+        }
+    }
+
+    ignores.truncate(count);
+
+    Ok(())
+}
+
+/// The gzipped tarball of `files` in `root`, under a `package/` directory. Entries have fixed
+/// times and owners and normalized modes, so packing the same files gives the same tarball.
+pub(crate) fn tarball(root: &Path, files: &[String]) -> Result<(Vec<u8>, Vec<PackedFile>)> {
+    let mut builder = tar::Builder::new(vec![]);
+    let mut packed = vec![];
+
+    for file in files {
+        let path = root.join(file);
+
+        let data = fs::read(&path).map_err(|e| VoltError::ReadFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        #[cfg(unix)]
+        let executable = {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::metadata(&path)
+                .map(|metadata| metadata.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        };
+
+        #[cfg(not(unix))]
+        let executable = false;
+
+        let mode = if executable { 0o755 } else { 0o644 };
+
+        let mut header = tar::Header::new_gnu();
+
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(data.len() as u64);
+        header.set_mode(mode);
+        header.set_mtime(MTIME);
+        header.set_uid(0);
+        header.set_gid(0);
+
+        builder
+            .append_data(&mut header, format!("package/{}", file), data.as_slice())
+            .into_diagnostic()?;
+
+        packed.push(PackedFile {
+            path: file.clone(),
+            size: data.len() as u64,
+            mode,
+        });
+    }
+
+    let tar = builder.into_inner().into_diagnostic()?;
+
+    Ok((compress_gzip(&tar)?, packed))
+}
+
+/// Pack the package in `root`. With `dry_run`, the files are only listed, no scripts are run
+/// and no tarball is created.
+pub(crate) fn pack(root: &Path, config: &VoltConfig, dry_run: bool) -> Result<Packed> {
+    let (package_json, _) = PackageJson::get_from_dir(root)?;
+
+    if !dry_run {
+        run_script(root, &package_json, "prepack", config)?;
+    }
+
+    // `prepack` usually builds the files that are packed, and can change package.json
+    let (package_json, _) = PackageJson::get_from_dir(root)?;
+
+    let filename = tarball_name(&package_json.name, &package_json.version);
+
+    let files = package_files(root, &package_json)?
+        .into_iter()
+        .filter(|file| *file != filename)
+        .collect::<Vec<_>>();
+
+    if dry_run {
+        let files = files
+            .into_iter()
+            .map(|file| PackedFile {
+                size: fs::metadata(root.join(&file)).map_or(0, |metadata| metadata.len()),
+                path: file,
+                mode: 0o644,
+            })
+            .collect::<Vec<_>>();
+
+        return Ok(Packed {
+            name: package_json.name,
+            version: package_json.version,
+            filename,
+            entry_count: files.len(),
+            unpacked_size: files.iter().map(|file| file.size).sum(),
+            files,
+            size: None,
+            shasum: None,
+            integrity: None,
+            tarball: vec![],
+        });
+    }
+
+    let (tarball, files) = tarball(root, &files)?;
+
+    let bytes = bytes::Bytes::from(tarball);
+
+    let shasum = VoltConfig::calc_hash(&bytes, Algorithm::Sha1)?
+        .trim_start_matches("sha1-")
+        .to_string();
+    let integrity = VoltConfig::calc_hash(&bytes, Algorithm::Sha512)?;
+
+    Ok(Packed {
+        name: package_json.name,
+        version: package_json.version,
+        filename,
+        entry_count: files.len(),
+        unpacked_size: files.iter().map(|file| file.size).sum(),
+        files,
+        size: Some(bytes.len() as u64),
+        shasum: Some(shasum),
+        integrity: Some(integrity),
+        tarball: bytes.to_vec(),
+    })
+}
+
+/// The contents and details of `packed`, as displayed by `volt pack` and `volt publish`.
+pub(crate) fn describe(packed: &Packed) -> String {
+    let mut lines = vec![
+        format!(
+            "📦 {}@{}",
+            packed.name.bright_green().bold(),
+            packed.version.bright_green().bold()
+        ),
+        "Tarball Contents".bold().to_string(),
+    ];
+
+    for file in &packed.files {
+        lines.push(format!(
+            "  {:>9}  {}",
+            HumanBytes(file.size).to_string(),
+            file.path
+        ));
+    }
+
+    lines.push("Tarball Details".bold().to_string());
+
+    let mut details = vec![
+        ("name", packed.name.clone()),
+        ("version", packed.version.clone()),
+        ("filename", packed.filename.clone()),
+    ];
+
+    if let Some(size) = packed.size {
+        details.push(("package size", HumanBytes(size).to_string()));
+    }
+
+    details.push((
+        "unpacked size",
+        HumanBytes(packed.unpacked_size).to_string(),
+    ));
+
+    if let Some(shasum) = &packed.shasum {
+        details.push(("shasum", shasum.clone()));
+    }
+
+    if let Some(integrity) = &packed.integrity {
+        details.push(("integrity", integrity.clone()));
+    }
+
+    details.push(("total files", packed.entry_count.to_string()));
+
+    for (label, value) in details {
+        lines.push(format!(
+            "  {:14} {}",
+            format!("{}:", label).truecolor(125, 125, 125),
+            value.bright_cyan()
+        ));
+    }
+
+    lines.join("\n")
+}
+
+#[async_trait]
+impl VoltCommand for Pack {
+    /// Execute the `volt pack` command
+    ///
+    /// Create a tarball of the package, running its `prepack` and `postpack` scripts.
+    /// ## Arguments
+    /// * `config` - The global configuration of volt
+    /// ## Examples
+    /// ```
+    /// // List the files that would be packed
+    /// // .exec() is an async call so you need to await it
+    /// Pack { dry_run: true }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+        let root = config.cwd()?;
+
+        let packed = pack(&root, &config, self.dry_run)?;
+
+        write(&describe(&packed), &meta);
+
+        if !self.dry_run {
+            let path: PathBuf = root.join(&packed.filename);
+
+            fs::write(&path, &packed.tarball).map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: path.display().to_string(),
+            })?;
+
+            let (package_json, _) = PackageJson::get_from_dir(&root)?;
+
+            run_script(&root, &package_json, "postpack", &config)?;
+
+            write(&packed.filename, &meta);
+        }
+
+        write_json(&packed, &meta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{package_files, tarball, Pattern};
+    use crate::core::utils::package::PackageJson;
+    use std::fs;
+
+    #[test]
+    fn matches_ignore_patterns() {
+        let matches = |pattern: &str, path: &str, is_dir: bool| {
+            Pattern::parse(pattern).unwrap().matches(path, is_dir)
+        };
+
+        assert!(matches("*.log", "a/b/debug.log", false));
+        assert!(matches("/dist", "dist", true));
+        assert!(!matches("/dist", "src/dist", true));
+        assert!(matches("src/*.test.js", "src/a.test.js", false));
+        assert!(!matches("src/*.test.js", "src/lib/a.test.js", false));
+        assert!(matches("**/fixtures/**", "a/fixtures/b/c.json", false));
+        assert!(!matches("build/", "build", false));
+        assert!(matches("[abc].js", "b.js", false));
+        assert!(Pattern::parse("# comment").is_none());
+    }
+
+    #[test]
+    fn packs_listed_files_reproducibly() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        for (path, data) in [
+            ("package.json", "{}"),
+            ("README.md", "# a"),
+            ("License", ""),
+            ("index.js", ""),
+            ("dist/a.js", ""),
+            ("dist/a.test.js", ""),
+            ("dist/.npmignore", "*.test.js"),
+            ("src/a.ts", ""),
+            ("node_modules/b/index.js", ""),
+            (".gitignore", "dist"),
+        ] {
+            fs::create_dir_all(root.join(path).parent().unwrap()).unwrap();
+            fs::write(root.join(path), data).unwrap();
+        }
+
+        let package_json = PackageJson {
+            main: Some("./index.js".to_string()),
+            files: Some(vec!["dist".to_string()]),
+            ..Default::default()
+        };
+
+        let files = package_files(root, &package_json).unwrap();
+
+        assert_eq!(
+            files,
+            [
+                "License",
+                "README.md",
+                "dist/a.js",
+                "index.js",
+                "package.json"
+            ]
+        );
+
+        let all = package_files(root, &PackageJson::default()).unwrap();
+
+        assert_eq!(
+            all,
+            [
+                "License",
+                "README.md",
+                "index.js",
+                "package.json",
+                "src/a.ts"
+            ]
+        );
+
+        assert_eq!(
+            tarball(root, &files).unwrap().0,
+            tarball(root, &files).unwrap().0
+        );
+    }
+}
@@ -14,31 +14,366 @@
     limitations under the License.
 */
 
-//! Check for outdated packages.
+//! Publish the package to the registry.
 
 use async_trait::async_trait;
-use miette::Result;
+use clap::{ArgEnum, Parser};
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use node_semver::Range;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use serde_json::{json, Value};
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::pack::{describe, pack, run_script, Packed},
+    core::http::HttpClient,
+    core::io::{write, write_json},
+    core::net::{check_write, write_headers},
+    core::utils::errors::VoltError,
+    core::utils::package::PackageJson,
+};
 
-pub struct Publish {}
+/// Publish the package to the registry
+#[derive(Debug, Parser)]
+pub struct Publish {
+    /// Dist-tag to publish the version under
+    #[clap(long, default_value = "latest")]
+    tag: String,
+
+    /// Who can install the package, scoped packages are restricted unless published as public
+    #[clap(long, arg_enum)]
+    access: Option<Access>,
+
+    /// One-time password from your authenticator app
+    #[clap(long)]
+    otp: Option<String>,
+
+    /// Pack the package and show what would be published, without publishing it
+    #[clap(long)]
+    dry_run: bool,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    Public,
+    Restricted,
+}
+
+/// Output of `volt publish --json`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PublishOutput {
+    id: String,
+    tag: String,
+    access: Option<Access>,
+    registry: String,
+    dry_run: bool,
+    #[serde(flatten)]
+    packed: Packed,
+}
+
+/// The document publishing the version described by `manifest`, the contents of package.json,
+/// with the `packed` tarball attached.
+fn publish_document(
+    manifest: Value,
+    packed: &Packed,
+    registry: &str,
+    tag: &str,
+    access: Option<Access>,
+) -> Value {
+    let id = format!("{}@{}", packed.name, packed.version);
+    let attachment = format!("{}-{}.tgz", packed.name, packed.version);
+
+    let mut manifest = match manifest {
+        Value::Object(manifest) => manifest,
+        _ => Default::default(),
+    };
+
+    manifest.insert("_id".to_string(), json!(id));
+    manifest.insert(
+        "dist".to_string(),
+        json!({
+            "shasum": packed.shasum,
+            "integrity": packed.integrity,
+            "tarball": format!("{}/{}/-/{}", registry, packed.name, attachment),
+            "fileCount": packed.entry_count,
+            "unpackedSize": packed.unpacked_size,
+        }),
+    );
+
+    json!({
+        "_id": packed.name,
+        "name": packed.name,
+        "description": manifest.get("description").cloned().unwrap_or(Value::Null),
+        "dist-tags": { tag: packed.version },
+        "versions": { packed.version.clone(): manifest },
+        "access": access,
+        "_attachments": {
+            attachment: {
+                "content_type": "application/octet-stream",
+                "data": base64::encode(&packed.tarball),
+                "length": packed.tarball.len(),
+            }
+        }
+    })
+}
 
 #[async_trait]
 impl VoltCommand for Publish {
-    /// Execute the `volt outdated` command
+    /// Execute the `volt publish` command
     ///
-    /// Check for outdated packages
+    /// Pack the package and publish it to the registry, running its `prepublishOnly`, `publish`
+    /// and `postpublish` scripts around it.
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global configuration of volt
     /// ## Examples
     /// ```
-    /// // Check for outdated packages
+    /// // Publish a prerelease
     /// // .exec() is an async call so you need to await it
-    /// Publish.exec(app).await;
+    /// Publish { tag: "next".into(), access: None, otp: None, dry_run: false }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
-        Ok(())
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+        let root = config.cwd()?;
+
+        let (package_json, package_json_path) = PackageJson::get_from_dir(&root)?;
+
+        if package_json.private == Some(true) {
+            return Err(VoltError::PrivatePackage {
+                name: package_json.name,
+            }
+            .into());
+        }
+
+        if Range::parse(&self.tag).is_ok() {
+            return Err(VoltError::InvalidDistTag { tag: self.tag }.into());
+        }
+
+        if !self.dry_run {
+            run_script(&root, &package_json, "prepublishOnly", &config)?;
+        }
+
+        let packed = pack(&root, &config, false)?;
+
+        // scripts may have changed package.json, what's published is what was packed
+        let (package_json, _) = PackageJson::get_from_dir(&root)?;
+
+        run_script(&root, &package_json, "postpack", &config)?;
+
+        let manifest: Value =
+            serde_json::from_str(&std::fs::read_to_string(&package_json_path).map_err(|e| {
+                VoltError::ReadFileError {
+                    source: e,
+                    name: package_json_path.display().to_string(),
+                }
+            })?)
+            .into_diagnostic()?;
+
+        let registry = config.registry();
+
+        write(&describe(&packed), &meta);
+        write(
+            &format!(
+                "Publishing to {} with tag {}{}{}",
+                registry.bright_cyan(),
+                self.tag.bright_green(),
+                match self.access {
+                    Some(Access::Public) => " and public access".to_string(),
+                    Some(Access::Restricted) => " and restricted access".to_string(),
+                    None => String::new(),
+                },
+                if self.dry_run { " (dry run)" } else { "" }
+            ),
+            &meta,
+        );
+
+        if !self.dry_run {
+            let document = publish_document(manifest, &packed, registry, &self.tag, self.access);
+            let body = serde_json::to_vec(&document).into_diagnostic()?;
+
+            let url = format!("{}/{}", registry, packed.name.replace('/', "%2f"));
+            let headers = write_headers(&config, &url, self.otp.as_deref())?;
+
+            let client = HttpClient::new(&config)?;
+
+            let response = client
+                .send_once(&url, |client| {
+                    client
+                        .put(&url)
+                        .headers(headers)
+                        .header(CONTENT_TYPE, "application/json")
+                        .body(body)
+                })
+                .await?;
+
+            check_write("PUT", &url, &response)?;
+
+            run_script(&root, &package_json, "publish", &config)?;
+            run_script(&root, &package_json, "postpublish", &config)?;
+
+            write(
+                &format!(
+                    "+ {}@{}",
+                    packed.name.bright_green().bold(),
+                    packed.version.bright_green().bold()
+                ),
+                &meta,
+            );
+        }
+
+        write_json(
+            &PublishOutput {
+                id: format!("{}@{}", packed.name, packed.version),
+                tag: self.tag,
+                access: self.access,
+                registry: registry.to_string(),
+                dry_run: self.dry_run,
+                packed,
+            },
+            &meta,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{publish_document, Access, Publish};
+    use crate::cli::{VoltCommand, VoltConfig};
+    use crate::commands::pack::Packed;
+    use crate::core::test_server::{json, serve};
+    use crate::core::utils::errors::VoltError;
+    use clap::Parser;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn attaches_the_tarball_to_the_document() {
+        let packed = Packed {
+            name: "@me/a".to_string(),
+            version: "1.0.0".to_string(),
+            filename: "me-a-1.0.0.tgz".to_string(),
+            files: vec![],
+            entry_count: 1,
+            unpacked_size: 2,
+            size: Some(3),
+            shasum: Some("abc".to_string()),
+            integrity: Some("sha512-abc".to_string()),
+            tarball: vec![1, 2, 3],
+        };
+
+        let document = publish_document(
+            json!({ "name": "@me/a", "version": "1.0.0", "description": "A" }),
+            &packed,
+            "https://r",
+            "next",
+            Some(Access::Public),
+        );
+
+        assert_eq!(document["dist-tags"], json!({ "next": "1.0.0" }));
+        assert_eq!(document["access"], "public");
+        assert_eq!(document["description"], "A");
+
+        let version = &document["versions"]["1.0.0"];
+
+        assert_eq!(version["_id"], "@me/a@1.0.0");
+        assert_eq!(
+            version["dist"]["tarball"],
+            "https://r/@me/a/-/@me/a-1.0.0.tgz"
+        );
+        assert_eq!(version["dist"]["integrity"], "sha512-abc");
+
+        let attachment = &document["_attachments"]["@me/a-1.0.0.tgz"];
+
+        assert_eq!(attachment["data"], "AQID");
+        assert_eq!(attachment["length"], 3);
+    }
+
+    #[tokio::test]
+    async fn publishes_to_the_registry_with_a_one_time_password() {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::write(
+            dir.path().join("package.json"),
+            r#"{ "name": "@me/thing", "version": "1.2.3" }"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("index.js"), "").unwrap();
+
+        let (registry, requests) = serve(vec![
+            json(
+                "401 Unauthorized",
+                &json!({ "error": "This operation requires a one-time password." }),
+            ),
+            json("200 OK", &json!({ "ok": true })),
+        ])
+        .await;
+
+        let cwd = dir.path().display().to_string();
+
+        let config = || {
+            VoltConfig::parse_from([
+                "volt",
+                "--cwd",
+                &cwd,
+                "--registry",
+                &registry,
+                "--token",
+                "s3cret",
+                "--fetch-retries",
+                "0",
+            ])
+        };
+
+        let publish = |otp: Option<&str>| Publish {
+            tag: "next".to_string(),
+            access: Some(Access::Public),
+            otp: otp.map(str::to_string),
+            dry_run: false,
+        };
+
+        let error = publish(None).exec(config()).await.unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<VoltError>(),
+            Some(VoltError::OtpRequired { .. })
+        ));
+
+        // never sent
+        let error = publish(Some("12\n34")).exec(config()).await.unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<VoltError>(),
+            Some(VoltError::InvalidOtp { .. })
+        ));
+
+        publish(Some("123456")).exec(config()).await.unwrap();
+
+        let requests = requests.await.unwrap();
+
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].header("npm-otp"), None);
+
+        let request = &requests[1];
+
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.path, "/@me%2fthing");
+        assert_eq!(request.header("authorization"), Some("Bearer s3cret"));
+        assert_eq!(request.header("npm-otp"), Some("123456"));
+        assert_eq!(request.header("content-type"), Some("application/json"));
+
+        let document = request.json();
+
+        assert_eq!(document["dist-tags"], json!({ "next": "1.2.3" }));
+        assert_eq!(document["access"], "public");
+        assert_eq!(
+            document["versions"]["1.2.3"]["dist"]["tarball"],
+            format!("{}/@me/thing/-/@me/thing-1.2.3.tgz", registry)
+        );
+        assert!(document["_attachments"]["@me/thing-1.2.3.tgz"]["data"].is_string());
     }
 }
@@ -15,6 +15,7 @@
 */
 
 use crate::cli::{VoltCommand, VoltConfig};
+use crate::core::io::write;
 
 use async_trait::async_trait;
 use clap::Parser;
@@ -48,11 +49,18 @@ impl VoltCommand for Run {
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
         if cfg!(target_os = "windows") {
             Command::new("cmd").args(&["/C", "babel"]).spawn().unwrap();
         } else if cfg!(target_os = "linux") {
-            println!("{}", format!("$ {}", self.script).truecolor(156, 156, 156));
+            write(
+                &format!("$ {}", self.script)
+                    .truecolor(156, 156, 156)
+                    .to_string(),
+                &meta,
+            );
 
             let mut child =
                 Command::new(Path::new("node_modules/").join(".bin/").join(self.script))
@@ -16,47 +16,157 @@
 
 //! Search for a package.
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::http::HttpClient,
+    core::io::{write, write_json},
+    core::utils::errors::VoltError,
+};
 
 use async_trait::async_trait;
 use clap::Parser;
 use comfy_table::{
-    modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL, Attribute, Cell, Color, ContentArrangement,
-    Table,
+    modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL, Attribute, Cell, CellAlignment, Color,
+    ContentArrangement, Table,
 };
-use isahc::AsyncReadResponseExt;
-use miette::Result;
+use miette::{IntoDiagnostic, Result};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
-pub struct Objects {
-    objects: Vec<SearchResults>,
+// https://github.com/npm/registry/blob/master/docs/REGISTRY-API.md#get-v1search
+#[derive(Debug, Deserialize)]
+struct Objects {
+    objects: Vec<SearchObject>,
+    total: usize,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct SearchResults {
+#[derive(Debug, Deserialize)]
+struct SearchObject {
     package: SearchResult,
+    score: Score,
+    downloads: Option<Downloads>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct SearchResult {
+#[derive(Debug, Deserialize)]
+struct SearchResult {
     name: String,
     version: String,
-    description: String,
+    description: Option<String>,
+    #[serde(default)]
+    keywords: Vec<String>,
+    date: Option<String>,
+    author: Option<Person>,
+    publisher: Option<Person>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Person {
+    name: Option<String>,
+    username: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Score {
+    #[serde(rename = "final")]
+    total: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Downloads {
+    weekly: u64,
 }
 
 /// Searches for a package
 #[derive(Debug, Parser)]
 pub struct Search {
     /// Search query
-    query: String,
+    #[clap(required_unless_present_any = &["keyword", "author", "scope"])]
+    query: Vec<String>,
+
+    /// Only show packages with this keyword
+    #[clap(long)]
+    keyword: Vec<String>,
+
+    /// Only show packages published by this user
+    #[clap(long)]
+    author: Option<String>,
+
+    /// Only show packages in this scope
+    #[clap(long)]
+    scope: Option<String>,
+
+    /// How many results to show, at most 250
+    #[clap(long, default_value = "20")]
+    limit: usize,
+
+    /// How many results to skip, to see the next pages
+    #[clap(long, default_value = "0")]
+    offset: usize,
 }
 
+/// Output of `volt search --json`.
 #[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct SearchData {
     pub name: String,
     pub version: String,
     pub description: Option<String>,
+    pub keywords: Vec<String>,
+    pub author: Option<String>,
+    pub date: Option<String>,
+    pub score: f64,
+    pub weekly_downloads: Option<u64>,
+}
+
+impl From<SearchObject> for SearchData {
+    fn from(object: SearchObject) -> Self {
+        let package = object.package;
+
+        let author = package
+            .author
+            .and_then(|author| author.name.or(author.username))
+            .or_else(|| package.publisher.and_then(|publisher| publisher.username));
+
+        SearchData {
+            name: package.name,
+            version: package.version,
+            description: package.description,
+            keywords: package.keywords,
+            author,
+            date: package.date,
+            score: object.score.total,
+            weekly_downloads: object.downloads.map(|downloads| downloads.weekly),
+        }
+    }
+}
+
+impl Search {
+    /// The search text: the query followed by the qualifiers of the filters.
+    fn text(&self) -> String {
+        let mut text = self.query.clone();
+
+        if !self.keyword.is_empty() {
+            text.push(format!("keywords:{}", self.keyword.join(",")));
+        }
+
+        if let Some(author) = &self.author {
+            text.push(format!("author:{}", author));
+        }
+
+        if let Some(scope) = &self.scope {
+            text.push(format!("scope:{}", scope.trim_start_matches('@')));
+        }
+
+        text.join(" ")
+    }
+}
+
+/// `text` cut to at most `length` characters.
+fn truncate(text: &str, length: usize) -> String {
+    if text.chars().count() > length {
+        format!("{}...", text.chars().take(length - 3).collect::<String>())
+    } else {
+        text.to_string()
+    }
 }
 
 #[async_trait]
@@ -65,27 +175,54 @@ impl VoltCommand for Search {
     ///
     /// Search for a package
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global configuration of volt
     /// ## Examples
     /// ```
     /// // Search for a package
     /// // .exec() is an async call so you need to await it
-    /// Search.exec(app).await;
+    /// Search { query: vec!["react".into()], limit: 20, .. }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
-        let response = isahc::get_async(format!(
-            "https://registry.npmjs.org/-/v1/search?text={}&popularity=1.0",
-            self.query
-        ))
-        .await
-        .unwrap()
-        .text()
-        .await
-        .unwrap();
-
-        let s: Objects = serde_json::from_str(&response).unwrap();
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+        let client = HttpClient::new(&config)?;
+
+        let text = self.text();
+
+        let url = format!(
+            "{}/-/v1/search?text={}&size={}&from={}&popularity=1.0",
+            config.registry(),
+            urlencoding::encode(&text),
+            self.limit.clamp(1, 250),
+            self.offset
+        );
+
+        let response = client.get(&url).await?;
+
+        if !response.status.is_success() {
+            return Err(VoltError::NetworkUnknownError {
+                url,
+                package_name: text,
+                code: response.status.as_str().to_string(),
+            }
+            .into());
+        }
+
+        let objects: Objects = serde_json::from_slice(&response.body).into_diagnostic()?;
+
+        let total = objects.total;
+        let results = objects
+            .objects
+            .into_iter()
+            .map(SearchData::from)
+            .collect::<Vec<_>>();
+
+        if results.is_empty() {
+            write(&format!("No packages found for `{}`", text), &meta);
+
+            return write_json(&results, &meta);
+        }
 
         let mut table = Table::new();
 
@@ -94,6 +231,10 @@ impl VoltCommand for Search {
             .apply_modifier(UTF8_ROUND_CORNERS)
             .set_content_arrangement(ContentArrangement::DynamicFullWidth);
 
+        if meta.no_color {
+            table.force_no_tty();
+        }
+
         table.set_header(vec![
             Cell::new("Name")
                 .fg(Color::Green)
@@ -104,25 +245,85 @@ impl VoltCommand for Search {
             Cell::new("Description")
                 .fg(Color::Yellow)
                 .add_attribute(Attribute::Bold),
+            Cell::new("Author")
+                .fg(Color::Cyan)
+                .add_attribute(Attribute::Bold),
+            Cell::new("Published")
+                .fg(Color::Magenta)
+                .add_attribute(Attribute::Bold),
+            Cell::new("Weekly")
+                .fg(Color::Green)
+                .add_attribute(Attribute::Bold),
+            Cell::new("Score")
+                .fg(Color::Blue)
+                .add_attribute(Attribute::Bold),
         ]);
 
-        for i in &s.objects {
-            let mut description: String = i.package.description.clone();
-
-            if i.package.description.len() > 150 {
-                description.truncate(147);
-                description = format!("{}...", description);
-            }
-
+        for result in &results {
             table.add_row(vec![
-                Cell::new(&i.package.name),
-                Cell::new(&i.package.version),
-                Cell::new(description),
+                Cell::new(&result.name),
+                Cell::new(&result.version),
+                Cell::new(truncate(
+                    result.description.as_deref().unwrap_or_default(),
+                    100,
+                )),
+                Cell::new(result.author.as_deref().unwrap_or("-")),
+                // the date part of an ISO 8601 timestamp
+                Cell::new(
+                    result
+                        .date
+                        .as_deref()
+                        .map_or("-", |date| date.get(..10).unwrap_or(date)),
+                ),
+                Cell::new(
+                    result
+                        .weekly_downloads
+                        .map_or_else(|| "-".to_string(), |weekly| weekly.to_string()),
+                )
+                .set_alignment(CellAlignment::Right),
+                Cell::new(format!("{:.2}", result.score)).set_alignment(CellAlignment::Right),
             ]);
         }
 
-        println!("{}", table);
+        write(&table.to_string(), &meta);
+
+        let shown = self.offset + results.len();
+
+        write(
+            &format!(
+                "Showing {}-{} of {} results{}",
+                self.offset + 1,
+                shown,
+                total,
+                if shown < total {
+                    format!(", see more with `--offset {}`", shown)
+                } else {
+                    String::new()
+                }
+            ),
+            &meta,
+        );
+
+        write_json(&results, &meta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{truncate, Search};
+    use clap::Parser;
+
+    #[test]
+    fn builds_the_search_text() {
+        let search = |args: &[&str]| Search::parse_from([&["search"], args].concat()).text();
+
+        assert_eq!(search(&["react", "hooks"]), "react hooks");
+        assert_eq!(
+            search(&["--keyword", "a", "--keyword", "b", "--scope", "@types"]),
+            "keywords:a,b scope:types"
+        );
+        assert_eq!(search(&["x", "--author", "me"]), "x author:me");
 
-        Ok(())
+        assert_eq!(truncate("ééééé", 4), "é...");
     }
 }
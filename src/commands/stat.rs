@@ -16,118 +16,392 @@
 
 //! Display stats on a specific package
 
-use crate::cli::{VoltCommand, VoltConfig};
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::http::HttpClient,
+    core::io::{write, write_json, write_verbose},
+    core::utils::errors::VoltError,
+};
 
 use async_trait::async_trait;
-use miette::Result;
+use clap::Parser;
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-/// Struct implementation for the `stat` command.
-pub struct Stat;
+/// Display download stats of a package
+#[derive(Debug, Parser)]
+pub struct Stat {
+    /// Package to display the downloads of
+    package: String,
+
+    /// Base url of the download counts API
+    #[clap(long, default_value = "https://api.npmjs.org")]
+    api: String,
+
+    /// How many of the most downloaded versions to list
+    #[clap(long, default_value = "10")]
+    versions: usize,
+}
+
+// https://github.com/npm/registry/blob/master/docs/download-counts.md
+#[derive(Debug, Deserialize)]
+struct Point {
+    downloads: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Day {
+    day: String,
+    downloads: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Range {
+    downloads: Vec<Day>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Versions {
+    downloads: BTreeMap<String, u64>,
+}
+
+/// Output of `volt stat --json`.
+#[derive(Debug, Serialize)]
+struct StatOutput {
+    package: String,
+    weekly: u64,
+    monthly: u64,
+    yearly: u64,
+    daily: Vec<Day>, // over the last month
+    #[serde(skip_serializing_if = "Option::is_none")]
+    versions: Option<BTreeMap<String, u64>>, // over the last week, if the API has them
+}
+
+/// `count` with a `,` between every group of three digits.
+fn thousands(count: u64) -> String {
+    let digits = count.to_string();
+    let mut grouped = String::new();
+
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index) % 3 == 0 {
+            grouped.push(',');
+        }
+
+        grouped.push(digit);
+    }
+
+    grouped
+}
+
+/// One bar per value, scaled between the lowest and the highest of them.
+fn sparkline(values: &[u64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let min = values.iter().copied().min().unwrap_or_default();
+    let max = values.iter().copied().max().unwrap_or_default();
+
+    values
+        .iter()
+        .map(|value| {
+            if max == min {
+                BARS[0]
+            } else {
+                BARS[((value - min) * (BARS.len() as u64 - 1) / (max - min)) as usize]
+            }
+        })
+        .collect()
+}
+
+impl Stat {
+    /// Fetch `path` from the download counts API.
+    async fn fetch<T: DeserializeOwned>(&self, client: &HttpClient, path: &str) -> Result<T> {
+        let url = format!("{}/{}", self.api.trim_end_matches('/'), path);
+
+        let response = client.get(&url).await?;
+
+        match response.status.as_u16() {
+            200 => serde_json::from_slice(&response.body).into_diagnostic(),
+            404 => Err(VoltError::PackageNotFound {
+                url,
+                package_name: self.package.clone(),
+            }
+            .into()),
+            code => Err(VoltError::NetworkUnknownError {
+                url,
+                package_name: self.package.clone(),
+                code: code.to_string(),
+            }
+            .into()),
+        }
+    }
+}
 
 #[async_trait]
 impl VoltCommand for Stat {
-    /// Display a help menu for the `volt stat` command.
     /// Execute the `volt stat` command
     ///
     /// Displays stats on a specific package
+    /// ## Arguments
+    /// * `config` - The global configuration of volt
     /// ## Examples
     /// ```
     /// // .exec() is an async call so you need to await it
-    /// Create.exec(app, vec![], vec!["--verbose"]).await;
+    /// Stat { package: "react".into(), api: "https://api.npmjs.org".into(), versions: 10 }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
-        // let args = &app.args;
-
-        // if args.len() <= 1 {
-        //     error!("Missing Package Name!");
-        //     process::exit(1);
-        // }
-
-        // let package = &args[1];
-
-        // println!(
-        //     "{}{}\n",
-        //     "Download stats for ".bright_cyan().bold(),
-        //     package.bright_cyan().bold()
-        // );
-
-        // // Get downloads for the past week
-        // let url = format!(
-        //     "https://api.npmjs.org/downloads/point/last-week/{}",
-        //     package
-        // );
-
-        // let response = get(url).await.unwrap_or_else(|e| {
-        //     eprintln!("{}", e.to_string());
-        //     std::process::exit(1)
-        // });
-
-        // let file_contents = response.text().await.unwrap_or_else(|e| {
-        //     eprintln!("{}", e.to_string());
-        //     std::process::exit(1)
-        // });
-
-        // let data: Value = from_str(&file_contents).unwrap();
-
-        // let downloads = &data["downloads"];
-        // println!(
-        //     "{} {}",
-        //     downloads.to_string().bright_green(),
-        //     "downloads in the past week!".bright_green()
-        // );
-
-        // // Get downloads for the past month
-        // let url = format!(
-        //     "https://api.npmjs.org/downloads/point/last-month/{}",
-        //     package
-        // );
-
-        // let response = get(url).await.unwrap_or_else(|e| {
-        //     eprintln!("{}", e.to_string());
-        //     std::process::exit(1)
-        // });
-
-        // let file_contents = response.text().await.unwrap_or_else(|e| {
-        //     eprintln!("{}", e.to_string());
-        //     std::process::exit(1)
-        // });
-
-        // let data: Value = serde_json::from_str(&file_contents).unwrap();
-
-        // let downloads = &data["downloads"];
-        // println!(
-        //     "{} {}",
-        //     downloads.to_string().bright_green(),
-        //     "downloads in the past month!".bright_green()
-        // );
-
-        // // Get downloads for the past year
-        // let url = format!(
-        //     "https://api.npmjs.org/downloads/point/last-year/{}",
-        //     package
-        // );
-
-        // let response = get(url).await.unwrap_or_else(|e| {
-        //     eprintln!("{}", e.to_string());
-        //     process::exit(1)
-        // });
-
-        // let file_contents = response.text().await.unwrap_or_else(|e| {
-        //     eprintln!("{}", e.to_string());
-        //     process::exit(1)
-        // });
-
-        // let data: Value = serde_json::from_str(&file_contents).unwrap();
-
-        // let downloads = &data["downloads"];
-        // println!(
-        //     "{} {}",
-        //     downloads.to_string().bright_green(),
-        //     "downloads in the past year!".bright_green()
-        // );
-
-        Ok(())
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+        let client = HttpClient::new(&config)?;
+
+        // the per-version counts need scoped names encoded, the other endpoints accept both
+        let package = self.package.replace('/', "%2F");
+
+        let week = format!("downloads/point/last-week/{}", package);
+        let month = format!("downloads/point/last-month/{}", package);
+        let year = format!("downloads/point/last-year/{}", package);
+        let days = format!("downloads/range/last-month/{}", package);
+        let by_version = format!("versions/{}/last-week", package);
+
+        // the breakdown by version is a bonus, the counts are shown without it
+        let (counts, versions) = futures::join!(
+            async {
+                futures::try_join!(
+                    self.fetch::<Point>(&client, &week),
+                    self.fetch::<Point>(&client, &month),
+                    self.fetch::<Point>(&client, &year),
+                    self.fetch::<Range>(&client, &days),
+                )
+            },
+            self.fetch::<Versions>(&client, &by_version),
+        );
+
+        let (weekly, monthly, yearly, range) = counts?;
+
+        let versions = match versions {
+            Ok(versions) => Some(versions.downloads),
+            Err(error) => {
+                write_verbose(
+                    &format!("Couldn't fetch the downloads by version: {}", error),
+                    &meta,
+                );
+
+                None
+            }
+        };
+
+        let mut lines = vec![
+            format!("Downloads of {}", self.package.bright_cyan().bold()),
+            format!(
+                "  last week   {}",
+                thousands(weekly.downloads).bright_green()
+            ),
+            format!(
+                "  last month  {}",
+                thousands(monthly.downloads).bright_green()
+            ),
+            format!(
+                "  last year   {}",
+                thousands(yearly.downloads).bright_green()
+            ),
+        ];
+
+        let daily = range
+            .downloads
+            .iter()
+            .map(|day| day.downloads)
+            .collect::<Vec<_>>();
+
+        if !daily.is_empty() {
+            lines.push(String::new());
+            lines.push(format!(
+                "  last {} days  {}  {}",
+                daily.len(),
+                sparkline(&daily).bright_cyan(),
+                format!(
+                    "{} – {} a day",
+                    thousands(daily.iter().copied().min().unwrap_or_default()),
+                    thousands(daily.iter().copied().max().unwrap_or_default())
+                )
+                .truecolor(125, 125, 125)
+            ));
+        }
+
+        let mut by_downloads = versions
+            .iter()
+            .flatten()
+            .filter(|(_, count)| **count > 0)
+            .collect::<Vec<_>>();
+
+        by_downloads.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+        let total = by_downloads.iter().map(|(_, count)| **count).sum::<u64>();
+
+        if total > 0 {
+            lines.push(String::new());
+            lines.push("Versions downloaded last week".bold().to_string());
+
+            let shown = by_downloads
+                .iter()
+                .take(self.versions)
+                .map(|(version, count)| (version, **count, thousands(**count)))
+                .collect::<Vec<_>>();
+
+            let width = shown
+                .iter()
+                .map(|(version, _, _)| version.len())
+                .max()
+                .unwrap_or_default();
+
+            let count_width = shown
+                .iter()
+                .map(|(_, _, formatted)| formatted.len())
+                .max()
+                .unwrap_or_default();
+
+            for (version, count, formatted) in &shown {
+                // padded before coloring, the escape codes would count towards the width
+                lines.push(format!(
+                    "  {:width$}  {}  {}",
+                    version,
+                    format!("{:>width$}", formatted, width = count_width).bright_green(),
+                    format!("{:>5.1}%", *count as f64 * 100.0 / total as f64)
+                        .truecolor(125, 125, 125),
+                    width = width
+                ));
+            }
+
+            if by_downloads.len() > self.versions {
+                lines.push(
+                    format!("  and {} more versions", by_downloads.len() - self.versions)
+                        .truecolor(125, 125, 125)
+                        .to_string(),
+                );
+            }
+        }
+
+        if versions.is_none() {
+            lines.push(String::new());
+            lines.push(
+                "Downloads by version aren't available"
+                    .truecolor(125, 125, 125)
+                    .to_string(),
+            );
+        }
+
+        write(&lines.join("\n"), &meta);
+
+        write_json(
+            &StatOutput {
+                package: self.package,
+                weekly: weekly.downloads,
+                monthly: monthly.downloads,
+                yearly: yearly.downloads,
+                daily: range.downloads,
+                versions,
+            },
+            &meta,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sparkline, thousands, Stat};
+    use crate::cli::{VoltCommand, VoltConfig};
+    use crate::core::test_server::{json, route};
+    use crate::core::utils::errors::VoltError;
+    use clap::Parser;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn formats_counts() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1000), "1,000");
+        assert_eq!(thousands(20123456), "20,123,456");
+
+        assert_eq!(sparkline(&[0, 7, 14]), "▁▄█");
+        assert_eq!(sparkline(&[5, 5]), "▁▁");
+    }
+
+    fn stat(api: &str) -> Stat {
+        Stat {
+            package: "@me/thing".to_string(),
+            api: api.to_string(),
+            versions: 10,
+        }
+    }
+
+    #[tokio::test]
+    async fn shows_the_counts_without_the_versions() {
+        let point = |downloads: u64| json("200 OK", &json!({ "downloads": downloads }));
+
+        // the versions endpoint isn't routed, it answers 404
+        let (api, requests) = route(
+            5,
+            HashMap::from([
+                (
+                    "/downloads/point/last-week/@me%2Fthing".to_string(),
+                    point(7),
+                ),
+                (
+                    "/downloads/point/last-month/@me%2Fthing".to_string(),
+                    point(30),
+                ),
+                (
+                    "/downloads/point/last-year/@me%2Fthing".to_string(),
+                    point(365),
+                ),
+                (
+                    "/downloads/range/last-month/@me%2Fthing".to_string(),
+                    json(
+                        "200 OK",
+                        &json!({ "downloads": [{ "day": "2022-01-01", "downloads": 1 }] }),
+                    ),
+                ),
+            ]),
+        )
+        .await;
+
+        let config = VoltConfig::parse_from(["volt", "--fetch-retries", "0"]);
+
+        stat(&api).exec(config).await.unwrap();
+
+        let mut paths = requests
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|request| request.path)
+            .collect::<Vec<_>>();
+
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            [
+                "/downloads/point/last-month/@me%2Fthing",
+                "/downloads/point/last-week/@me%2Fthing",
+                "/downloads/point/last-year/@me%2Fthing",
+                "/downloads/range/last-month/@me%2Fthing",
+                "/versions/@me%2Fthing/last-week",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_for_unknown_packages() {
+        let (api, _) = route(5, HashMap::new()).await;
+
+        let config = VoltConfig::parse_from(["volt", "--fetch-retries", "0"]);
+        let error = stat(&api).exec(config).await.unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<VoltError>(),
+            Some(VoltError::PackageNotFound { package_name, .. }) if package_name == "@me/thing"
+        ));
     }
 }
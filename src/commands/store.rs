@@ -0,0 +1,60 @@
+/*
+ *    Copyright 2021 Volt Contributors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Manage the global package store
+
+mod store_path;
+mod store_prune;
+mod store_status;
+mod store_verify;
+
+pub use store_path::*;
+pub use store_prune::*;
+pub use store_status::*;
+pub use store_verify::*;
+
+use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use miette::Result;
+
+use crate::cli::{VoltCommand, VoltConfig};
+
+/// Manage the global package store
+#[derive(Debug, Parser)]
+pub struct Store {
+    #[clap(subcommand)]
+    cmd: StoreCommand,
+}
+
+#[async_trait]
+impl VoltCommand for Store {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        match self.cmd {
+            StoreCommand::Path(x) => x.exec(config).await,
+            StoreCommand::Status(x) => x.exec(config).await,
+            StoreCommand::Verify(x) => x.exec(config).await,
+            StoreCommand::Prune(x) => x.exec(config).await,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum StoreCommand {
+    Path(StorePath),
+    Status(StoreStatus),
+    Verify(StoreVerify),
+    Prune(StorePrune),
+}
@@ -0,0 +1,21 @@
+use async_trait::async_trait;
+use clap::Parser;
+use miette::Result;
+
+use crate::cli::{VoltCommand, VoltConfig};
+use crate::core::io::write;
+
+/// Print the location of the global store
+#[derive(Debug, Parser)]
+pub struct StorePath {}
+
+#[async_trait]
+impl VoltCommand for StorePath {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
+        write(&config.volt_home()?.display().to_string(), &meta);
+
+        Ok(())
+    }
+}
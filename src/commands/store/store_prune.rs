@@ -0,0 +1,70 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use indicatif::HumanBytes;
+use miette::{IntoDiagnostic, Result};
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::io::write,
+    core::store::prune,
+};
+
+/// Remove packages that haven't been used by any project recently
+#[derive(Debug, Parser)]
+pub struct StorePrune {
+    /// Keep packages used by a project installed in the last <DAYS> days
+    #[clap(long, default_value = "30")]
+    days: u64,
+
+    /// Only show what would be removed
+    #[clap(long)]
+    dry_run: bool,
+}
+
+#[async_trait]
+impl VoltCommand for StorePrune {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
+        let store = config.volt_home()?;
+        let cutoff = SystemTime::now()
+            .checked_sub(Duration::from_secs(self.days * 24 * 60 * 60))
+            .unwrap_or(UNIX_EPOCH);
+        let dry_run = self.dry_run;
+
+        let report = tokio::task::spawn_blocking(move || prune(&store, cutoff, dry_run))
+            .await
+            .into_diagnostic()??;
+
+        for project in &report.projects {
+            write(
+                &format!(
+                    "{} {} (no longer exists)",
+                    "-".bright_red(),
+                    project.display()
+                ),
+                &meta,
+            );
+        }
+
+        for package in &report.packages {
+            write(&format!("{} {}", "-".bright_red(), package), &meta);
+        }
+
+        write(
+            &format!(
+                "{} {} packages, {} files ({})",
+                if dry_run { "Would remove" } else { "Removed" },
+                report.packages.len().to_string().bright_green(),
+                report.content_files,
+                HumanBytes(report.freed).to_string().bright_green(),
+            ),
+            &meta,
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use indicatif::{HumanBytes, HumanDuration};
+use miette::{IntoDiagnostic, Result};
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::io::write,
+    core::store::{disk_usage, registered_projects, store_entries},
+};
+
+/// Show how much space the global store uses and what it contains
+#[derive(Debug, Parser)]
+pub struct StoreStatus {}
+
+#[async_trait]
+impl VoltCommand for StoreStatus {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
+        let store = config.volt_home()?;
+
+        let (size, unpacked_size, entries, projects) =
+            tokio::task::spawn_blocking(move || -> Result<_> {
+                Ok((
+                    disk_usage(&store),
+                    disk_usage(&store.join("unpacked")),
+                    store_entries(&store)?,
+                    registered_projects(&store)?,
+                ))
+            })
+            .await
+            .into_diagnostic()??;
+
+        let packages = entries
+            .iter()
+            .filter(|entry| entry.directory_name().is_some())
+            .count();
+
+        let last_used = projects
+            .iter()
+            .map(|project| project.last_used)
+            .chain(entries.iter().map(|entry| entry.time))
+            .max();
+
+        write(
+            &format!("{:12} {}", "Path".bold(), config.volt_home()?.display()),
+            &meta,
+        );
+        write(&format!("{:12} {}", "Size".bold(), HumanBytes(size)), &meta);
+
+        if unpacked_size > 0 {
+            write(
+                &format!("{:12} {}", "Unpacked".bold(), HumanBytes(unpacked_size)),
+                &meta,
+            );
+        }
+
+        write(&format!("{:12} {}", "Packages".bold(), packages), &meta);
+        write(
+            &format!("{:12} {}", "Projects".bold(), projects.len()),
+            &meta,
+        );
+
+        match last_used.map(|time| SystemTime::now().duration_since(time)) {
+            Some(Ok(elapsed)) => write(
+                &format!("{:12} {} ago", "Last used".bold(), HumanDuration(elapsed)),
+                &meta,
+            ),
+            Some(Err(_)) => write(&format!("{:12} just now", "Last used".bold()), &meta),
+            None => write(&format!("{:12} never", "Last used".bold()), &meta),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use miette::{IntoDiagnostic, Result};
+use rayon::prelude::*;
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::io::write,
+    core::store::{remove_corrupted, store_entries, verify_entry},
+};
+
+/// Check the integrity of every file in the global store
+#[derive(Debug, Parser)]
+pub struct StoreVerify {
+    /// Remove corrupted packages from the store so that they are downloaded again
+    #[clap(long)]
+    fix: bool,
+}
+
+#[async_trait]
+impl VoltCommand for StoreVerify {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
+        let store = config.volt_home()?;
+
+        let corrupted = tokio::task::spawn_blocking(move || -> Result<_> {
+            let entries = store_entries(&store)?;
+
+            let bar = if meta.progress() {
+                ProgressBar::new(entries.len() as u64)
+            } else {
+                ProgressBar::hidden()
+            }
+            .with_style(
+                ProgressStyle::default_bar()
+                    .template("Verifying - [{bar:40.cyan/blue}] {pos:>7}/{len:7}")
+                    .progress_chars("=>-"),
+            );
+
+            let corrupted = entries
+                .par_iter()
+                .flat_map(|entry| {
+                    let corrupted = verify_entry(&store, entry);
+                    bar.inc(1);
+                    corrupted
+                })
+                .collect::<Vec<_>>();
+
+            bar.finish_and_clear();
+
+            if self.fix {
+                for corruption in &corrupted {
+                    remove_corrupted(&store, corruption)?;
+                }
+            }
+
+            Ok((entries.len(), corrupted))
+        })
+        .await
+        .into_diagnostic()??;
+
+        let (total, corrupted) = corrupted;
+
+        if corrupted.is_empty() {
+            write(
+                &format!("{} Verified {} entries", "✓".bright_green(), total),
+                &meta,
+            );
+
+            return Ok(());
+        }
+
+        for corruption in &corrupted {
+            write(
+                &format!(
+                    "{} {}{}: {}",
+                    "✗".bright_red(),
+                    corruption.key,
+                    corruption
+                        .path
+                        .as_ref()
+                        .map(|path| format!(" ({})", path))
+                        .unwrap_or_default(),
+                    corruption.error
+                ),
+                &meta,
+            );
+        }
+
+        if self.fix {
+            write(&format!("Removed {} corrupted entries, they will be downloaded again on the next install",
+                corrupted.len()), &meta);
+
+            return Ok(());
+        }
+
+        miette::bail!(
+            "found {} corrupted entries in the store, run `volt store verify --fix` to remove them",
+            corrupted.len()
+        )
+    }
+}
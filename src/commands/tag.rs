@@ -14,31 +14,453 @@
     limitations under the License.
 */
 
-//! Check for outdated packages.
+//! Manage the dist-tags of a package.
 
-use crate::cli::{VoltCommand, VoltConfig};
+use std::collections::BTreeMap;
 
 use async_trait::async_trait;
-use miette::Result;
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use node_semver::Range;
+use oro_node_semver::Version;
+use package_spec::{PackageSpec, VersionSpec};
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use serde_json::Value;
 
-pub struct Tag {}
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::info::select_version,
+    core::http::HttpClient,
+    core::io::{write, write_json},
+    core::net::{auth_headers, check_write, fetch_packument, write_headers},
+    core::utils::errors::VoltError,
+    core::utils::package::PackageJson,
+};
+
+/// Manage the dist-tags of a package
+#[derive(Debug, Parser)]
+pub struct Tag {
+    #[clap(subcommand)]
+    cmd: TagCommand,
+}
 
 #[async_trait]
 impl VoltCommand for Tag {
-    /// Execute the `volt outdated` command
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        match self.cmd {
+            TagCommand::Add(x) => x.exec(config).await,
+            TagCommand::Rm(x) => x.exec(config).await,
+            TagCommand::Ls(x) => x.exec(config).await,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TagCommand {
+    Add(TagAdd),
+    Rm(TagRm),
+    Ls(TagLs),
+}
+
+/// Point a dist-tag at a version of a package
+#[derive(Debug, Parser)]
+pub struct TagAdd {
+    /// Package and version to tag, the version can also be a range or another dist-tag. Defaults
+    /// to the version in package.json for the package in the current directory
+    package: PackageSpec,
+
+    /// Dist-tag to add or move
+    tag: String,
+
+    /// One-time password from your authenticator app
+    #[clap(long)]
+    otp: Option<String>,
+}
+
+/// Remove a dist-tag from a package
+#[derive(Debug, Parser)]
+pub struct TagRm {
+    /// Package to remove the dist-tag from
+    package: String,
+
+    /// Dist-tag to remove
+    tag: String,
+
+    /// One-time password from your authenticator app
+    #[clap(long)]
+    otp: Option<String>,
+}
+
+/// List the dist-tags of a package
+#[derive(Debug, Parser)]
+pub struct TagLs {
+    /// Package to list the dist-tags of
+    package: String,
+}
+
+/// Output of `volt tag add --json` and `volt tag rm --json`.
+#[derive(Debug, Serialize)]
+struct TagOutput {
+    name: String,
+    tag: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+}
+
+/// Url of the dist-tags of `name`, or of one of them.
+fn dist_tags_url(config: &VoltConfig, name: &str, tag: Option<&str>) -> String {
+    let url = format!(
+        "{}/-/package/{}/dist-tags",
+        config.registry(),
+        name.replace('/', "%2f")
+    );
+
+    match tag {
+        Some(tag) => format!("{}/{}", url, urlencoding::encode(tag)),
+        None => url,
+    }
+}
+
+/// The version of `name` in the current directory, which is tagged when no version is given.
+fn local_version(config: &VoltConfig, name: &str) -> Result<VersionSpec> {
+    let cwd = config.cwd()?;
+
+    if cwd.join("package.json").exists() {
+        let (package_json, _) = PackageJson::get_from_dir(&cwd)?;
+
+        if package_json.name == name {
+            return Version::parse(&package_json.version)
+                .map(VersionSpec::Version)
+                .map_err(|_| {
+                    VoltError::PackageSpecificationError {
+                        spec: format!("{}@{}", name, package_json.version),
+                    }
+                    .into()
+                });
+        }
+    }
+
+    Err(VoltError::DistTagVersionMissing {
+        name: name.to_string(),
+    }
+    .into())
+}
+
+#[async_trait]
+impl VoltCommand for TagAdd {
+    /// Execute the `volt tag add` command
     ///
-    /// Check for outdated packages
+    /// Point a dist-tag at a version of a package
     /// ## Arguments
-    /// * `error` - Instance of the command (`Arc<App>`)
+    /// * `config` - The global configuration of volt
     /// ## Examples
     /// ```
-    /// // Check for outdated packages
+    /// // Promote the version tagged `next` to `latest`
     /// // .exec() is an async call so you need to await it
-    /// Tag.exec(app).await;
+    /// TagAdd { package: "react@next".parse()?, tag: "latest".into(), otp: None }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
-        Ok(())
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
+        let (name, requested) = match &self.package {
+            PackageSpec::Npm {
+                name, requested, ..
+            } => (name, requested.as_ref()),
+            spec => {
+                return Err(VoltError::PackageSpecificationError {
+                    spec: spec.to_string(),
+                }
+                .into())
+            }
+        };
+
+        if Range::parse(&self.tag).is_ok() {
+            return Err(VoltError::InvalidDistTag { tag: self.tag }.into());
+        }
+
+        // like npm, the package in the current directory is tagged at its version
+        let local = match requested {
+            Some(_) => None,
+            None => Some(local_version(&config, name)?),
+        };
+
+        let requested = local.as_ref().or(requested);
+
+        let client = HttpClient::new(&config)?;
+
+        let packument = fetch_packument::<Value>(&client, &config, name, requested, false)
+            .await?
+            .ok_or_else(|| VoltError::PackageNotFound {
+                url: format!("{}/{}", config.registry(), name.replace('/', "%2f")),
+                package_name: name.clone(),
+            })?;
+
+        let version =
+            select_version(&packument, requested).ok_or_else(|| VoltError::VersionNotFound {
+                name: name.clone(),
+                requested: requested
+                    .map(|requested| requested.to_string())
+                    .unwrap_or_else(|| "latest".to_string()),
+            })?;
+
+        let url = dist_tags_url(&config, name, Some(&self.tag));
+        let headers = write_headers(&config, &url, self.otp.as_deref())?;
+        let body = serde_json::to_vec(&version).into_diagnostic()?;
+
+        let response = client
+            .send(&url, |client| {
+                client
+                    .put(&url)
+                    .headers(headers.clone())
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body.clone())
+            })
+            .await?;
+
+        check_write("PUT", &url, &response)?;
+
+        write(
+            &format!(
+                "+{}: {}@{}",
+                self.tag.bright_green(),
+                name.bright_cyan(),
+                version
+            ),
+            &meta,
+        );
+
+        write_json(
+            &TagOutput {
+                name: name.clone(),
+                tag: self.tag,
+                version: Some(version),
+            },
+            &meta,
+        )
+    }
+}
+
+#[async_trait]
+impl VoltCommand for TagRm {
+    /// Execute the `volt tag rm` command
+    ///
+    /// Remove a dist-tag from a package
+    /// ## Arguments
+    /// * `config` - The global configuration of volt
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// TagRm { package: "react".into(), tag: "beta".into(), otp: None }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+        let client = HttpClient::new(&config)?;
+
+        let url = dist_tags_url(&config, &self.package, Some(&self.tag));
+        let headers = write_headers(&config, &url, self.otp.as_deref())?;
+
+        let response = client
+            .send(&url, |client| client.delete(&url).headers(headers.clone()))
+            .await?;
+
+        check_write("DELETE", &url, &response)?;
+
+        write(
+            &format!("-{}: {}", self.tag.bright_red(), self.package.bright_cyan()),
+            &meta,
+        );
+
+        write_json(
+            &TagOutput {
+                name: self.package,
+                tag: self.tag,
+                version: None,
+            },
+            &meta,
+        )
+    }
+}
+
+#[async_trait]
+impl VoltCommand for TagLs {
+    /// Execute the `volt tag ls` command
+    ///
+    /// List the dist-tags of a package
+    /// ## Arguments
+    /// * `config` - The global configuration of volt
+    /// ## Examples
+    /// ```
+    /// // .exec() is an async call so you need to await it
+    /// TagLs { package: "react".into() }.exec(config).await;
+    /// ```
+    /// ## Returns
+    /// * `Result<()>`
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+        let client = HttpClient::new(&config)?;
+
+        let url = dist_tags_url(&config, &self.package, None);
+
+        let response = client
+            .get_with_headers(&url, auth_headers(&config, &url))
+            .await?;
+
+        match response.status.as_u16() {
+            200 => {}
+            404 => {
+                return Err(VoltError::PackageNotFound {
+                    url,
+                    package_name: self.package,
+                }
+                .into())
+            }
+            code => {
+                return Err(VoltError::NetworkUnknownError {
+                    url,
+                    package_name: self.package,
+                    code: code.to_string(),
+                }
+                .into())
+            }
+        }
+
+        let tags: BTreeMap<String, String> =
+            serde_json::from_slice(&response.body).into_diagnostic()?;
+
+        write(
+            &tags
+                .iter()
+                .map(|(tag, version)| format!("{}: {}", tag.bright_cyan(), version))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            &meta,
+        );
+
+        write_json(&tags, &meta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dist_tags_url, TagAdd, TagLs, TagRm};
+    use crate::cli::{VoltCommand, VoltConfig};
+    use crate::core::test_server::{json, serve};
+    use crate::core::utils::errors::VoltError;
+    use clap::Parser;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn encodes_the_dist_tags_url() {
+        let config = VoltConfig::parse_from(["volt", "--registry", "https://r/"]);
+
+        assert_eq!(
+            dist_tags_url(&config, "@me/a", None),
+            "https://r/-/package/@me%2fa/dist-tags"
+        );
+        assert_eq!(
+            dist_tags_url(&config, "a", Some("beta/1 x")),
+            "https://r/-/package/a/dist-tags/beta%2F1%20x"
+        );
+    }
+
+    #[tokio::test]
+    async fn manages_the_dist_tags_on_the_registry() {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::write(
+            dir.path().join("package.json"),
+            r#"{ "name": "@me/thing", "version": "1.2.3" }"#,
+        )
+        .unwrap();
+
+        let (registry, requests) = serve(vec![
+            json(
+                "200 OK",
+                &json!({
+                    "name": "@me/thing",
+                    "dist-tags": { "latest": "2.0.0" },
+                    "versions": { "1.2.3": {}, "2.0.0": {} }
+                }),
+            ),
+            json("200 OK", &json!({ "ok": true })),
+            json("200 OK", &json!({ "ok": true })),
+            json("200 OK", &json!({ "latest": "2.0.0", "next": "1.2.3" })),
+        ])
+        .await;
+
+        let cwd = dir.path().display().to_string();
+
+        let config = || {
+            VoltConfig::parse_from([
+                "volt",
+                "--cwd",
+                &cwd,
+                "--registry",
+                &registry,
+                "--token",
+                "s3cret",
+                "--fetch-retries",
+                "0",
+            ])
+        };
+
+        // tags the version in package.json rather than the latest one
+        TagAdd::parse_from(["add", "@me/thing", "next", "--otp", "123456"])
+            .exec(config())
+            .await
+            .unwrap();
+
+        TagRm::parse_from(["rm", "@me/thing", "next"])
+            .exec(config())
+            .await
+            .unwrap();
+
+        TagLs::parse_from(["ls", "@me/thing"])
+            .exec(config())
+            .await
+            .unwrap();
+
+        // another package needs its version
+        let error = TagAdd::parse_from(["add", "other", "next"])
+            .exec(config())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<VoltError>(),
+            Some(VoltError::DistTagVersionMissing { name }) if name == "other"
+        ));
+
+        let requests = requests.await.unwrap();
+
+        let sent = requests
+            .iter()
+            .map(|request| (request.method.as_str(), request.path.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            sent,
+            [
+                ("GET", "/@me%2fthing"),
+                ("PUT", "/-/package/@me%2fthing/dist-tags/next"),
+                ("DELETE", "/-/package/@me%2fthing/dist-tags/next"),
+                ("GET", "/-/package/@me%2fthing/dist-tags"),
+            ]
+        );
+
+        assert_eq!(requests[1].json(), json!("1.2.3"));
+        assert_eq!(requests[1].header("npm-otp"), Some("123456"));
+        assert_eq!(requests[1].header("content-type"), Some("application/json"));
+
+        for request in &requests {
+            assert_eq!(request.header("authorization"), Some("Bearer s3cret"));
+        }
     }
 }
@@ -14,53 +14,329 @@
     limitations under the License.
 */
 
-use crate::cli::{VoltCommand, VoltConfig};
+//! Update the dependencies of the project.
+
+use std::{borrow::Cow, path::Path};
 
 use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
 use miette::Result;
+use node_semver::Version;
+use package_spec::PackageSpec;
+use serde::Serialize;
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    commands::add::{install, AddOutput},
+    commands::outdated::fetch_packuments,
+    core::http::HttpClient,
+    core::io::{write, write_json},
+    core::model::lock_file::LockFile,
+    core::prompt::prompts::MultiSelect,
+    core::utils::errors::VoltError,
+    core::utils::package::{edit_package_json, DependencyKind, PackageJson},
+};
+
+/// Update dependencies to the highest version their range allows
+#[derive(Debug, Parser)]
+pub struct Update {
+    /// Only update these packages
+    packages: Vec<String>,
+
+    /// Update to the latest release instead, rewriting the ranges in package.json
+    #[clap(long)]
+    latest: bool,
 
-/// Struct implementation for the `Update` command.
-pub struct Update;
+    /// Choose the packages to update from a list
+    #[clap(short, long)]
+    interactive: bool,
+}
+
+/// Output of `volt update --json`.
+#[derive(Debug, Serialize)]
+struct UpdateOutput {
+    updated: Vec<UpdatedPackage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    install: Option<AddOutput>,
+}
+
+#[derive(Debug, Serialize)]
+struct UpdatedPackage {
+    name: String,
+    from: Option<String>, // locked version, `None` if it wasn't locked
+    to: String,
+    range: String, // range in package.json after the update
+    #[serde(rename = "type")]
+    kind: DependencyKind,
+}
+
+/// The range to declare `version` with in place of `range`, keeping its `^` or `~` operator and
+/// pinned versions pinned.
+pub(crate) fn bump_range(range: &str, version: &Version) -> String {
+    let range = range.trim();
+
+    let is_version = |range: &str| {
+        !range.is_empty()
+            && range
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+    };
+
+    for operator in ["^", "~"] {
+        if let Some(rest) = range.strip_prefix(operator) {
+            if is_version(rest.trim_start()) {
+                return format!("{}{}", operator, version);
+            }
+        }
+    }
+
+    if range.parse::<Version>().is_ok() {
+        version.to_string()
+    } else {
+        format!("^{}", version)
+    }
+}
+
+/// Write the new ranges of `updated` to the package.json at `path`, leaving the rest of it as is.
+fn save_ranges(path: &Path, updated: &[UpdatedPackage]) -> Result<()> {
+    edit_package_json(path, |package_json| {
+        for package in updated {
+            if let Some(range) = package_json
+                .get_mut(package.kind.field())
+                .and_then(|dependencies| dependencies.get_mut(&package.name))
+            {
+                *range = serde_json::Value::String(package.range.clone());
+            }
+        }
+    })
+}
 
 #[async_trait]
 impl VoltCommand for Update {
-    /// Display a help menu for the `volt update` command.
-    //     fn help() -> String {
-    //         format!(
-    //             r#"volt {}
-    //
-    // Update project dependencies
-    //
-    // Usage: {} {} {}
-    //
-    // Options:
-    //
-    //   {} {} Output verbose messages on internal operations."#,
-    //             VERSION.bright_green().bold(),
-    //             "volt".bright_green().bold(),
-    //             "update".bright_purple(),
-    //             "file-name".white(),
-    //             "--verbose".blue(),
-    //             "(-v)".yellow()
-    //         )
-    //     }
-
     /// Execute the `volt update` command
     ///
-    /// update project dependencies
+    /// Update the dependencies of the project and refresh the lockfile.
     /// ## Arguments
-    /// * `app` - Instance of the command (`Arc<App>`)
-    /// * `packages` - List of packages to add (`Vec<String>`)
-    /// * `flags` - List of flags passed in through the CLI (`Vec<String>`)
+    /// * `config` - The global configuration of volt
     /// ## Examples
     /// ```
-    /// // update project dependencies
+    /// // update react to its latest release
     /// // .exec() is an async call so you need to await it
-    /// update.exec(app, vec![], vec!["--yes"]).await;
+    /// Update { packages: vec!["react".into()], latest: true, interactive: false }.exec(config).await;
     /// ```
     /// ## Returns
     /// * `Result<()>`
-    async fn exec(self, _config: VoltConfig) -> Result<()> {
-        Ok(())
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
+        let (package_json, package_json_path) = PackageJson::get_from_dir(&config.cwd()?)?;
+
+        let declared = package_json
+            .dependencies_by_kind()
+            .into_iter()
+            .filter(|(_, name, _)| {
+                self.packages.is_empty() || self.packages.iter().any(|p| p == name)
+            })
+            .collect::<Vec<_>>();
+
+        for name in &self.packages {
+            if !declared.iter().any(|(_, declared, _)| declared == name) {
+                return Err(VoltError::PackageNotInstalled { spec: name.clone() }.into());
+            }
+        }
+
+        let mut lock_file = LockFile::load(config.lockfile()?)?;
+
+        let client = HttpClient::new(&config)?;
+
+        let packuments =
+            fetch_packuments(&client, &config, declared.iter().map(|(_, name, _)| *name)).await?;
+
+        let mut updates = vec![];
+
+        for (kind, name, range) in declared {
+            let packument = &packuments[name];
+
+            let target = if self.latest {
+                packument.latest()
+            } else {
+                packument.wanted(range)
+            };
+
+            let target = match target {
+                Some(target) => target,
+                None => continue,
+            };
+
+            let current = lock_file.dependencies.get(name).cloned();
+
+            if current.as_deref() == Some(target.to_string().as_str()) {
+                continue;
+            }
+
+            // dist-tags and `*` keep resolving to the latest release on their own
+            let range = if self.latest && !(packument.dist_tags.contains_key(range) || range == "*")
+            {
+                bump_range(range, &target)
+            } else {
+                range.to_string()
+            };
+
+            updates.push(UpdatedPackage {
+                name: name.to_string(),
+                from: current,
+                to: target.to_string(),
+                range,
+                kind,
+            });
+        }
+
+        if updates.is_empty() {
+            write("All packages are up to date!", &meta);
+
+            return write_json(
+                &UpdateOutput {
+                    updated: updates,
+                    install: None,
+                },
+                &meta,
+            );
+        }
+
+        let width = updates
+            .iter()
+            .map(|package| package.name.len())
+            .max()
+            .unwrap_or_default();
+
+        let lines = updates
+            .iter()
+            .map(|package| {
+                format!(
+                    "{} {} {} {} {}",
+                    format!("{:width$}", package.name, width = width).bright_cyan(),
+                    package.from.as_deref().unwrap_or("MISSING").bright_red(),
+                    "→".truecolor(125, 125, 125),
+                    package.to.bright_green(),
+                    format!("({})", package.kind.field()).truecolor(125, 125, 125),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if self.interactive {
+            let checked = MultiSelect {
+                message: "Choose the packages to update".into(),
+                items: lines.into_iter().map(Cow::Owned).collect(),
+                checked: vec![true; updates.len()],
+            }
+            .run()
+            .map_err(VoltError::PromptError)?;
+
+            updates = updates
+                .into_iter()
+                .enumerate()
+                .filter(|(index, _)| checked.contains(index))
+                .map(|(_, package)| package)
+                .collect();
+
+            if updates.is_empty() {
+                write("No packages were chosen", &meta);
+
+                return write_json(
+                    &UpdateOutput {
+                        updated: updates,
+                        install: None,
+                    },
+                    &meta,
+                );
+            }
+        } else {
+            write(
+                &format!(
+                    "Updating {} packages:\n  {}",
+                    updates.len().to_string().bright_yellow(),
+                    lines.join("\n  ")
+                ),
+                &meta,
+            );
+        }
+
+        let specs = updates
+            .iter()
+            .map(|package| {
+                let spec = format!("{}@{}", package.name, package.to);
+
+                spec.parse::<PackageSpec>()
+                    .map_err(|_| VoltError::PackageSpecificationError { spec }.into())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let output = install(&config, &mut lock_file, &specs).await?;
+
+        // package.json first, so that a failure leaves the lockfile behind it rather than
+        // locking versions its ranges don't allow
+        if self.latest {
+            save_ranges(&package_json_path, &updates)?;
+
+            write(&format!("Updated {}", package_json_path.display()), &meta);
+        }
+
+        // the previous versions and the dependencies only they had are no longer needed
+        lock_file.prune();
+        lock_file.save()?;
+
+        write_json(
+            &UpdateOutput {
+                updated: updates,
+                install: Some(output),
+            },
+            &meta,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bump_range, save_ranges, UpdatedPackage};
+    use crate::core::utils::package::DependencyKind;
+
+    #[test]
+    fn bumps_ranges_keeping_their_operator() {
+        let version = "2.1.0".parse().unwrap();
+
+        assert_eq!(bump_range("^1.0.0", &version), "^2.1.0");
+        assert_eq!(bump_range("~1.2", &version), "~2.1.0");
+        assert_eq!(bump_range("1.0.0", &version), "2.1.0");
+        assert_eq!(bump_range(">=1.0.0 <2.0.0", &version), "^2.1.0");
+    }
+
+    #[test]
+    fn saves_ranges_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("package.json");
+
+        std::fs::write(
+            &path,
+            r#"{"name":"app","devDependencies":{"b":"^1.0.0","a":"^1.0.0"},"private":true}"#,
+        )
+        .unwrap();
+
+        save_ranges(
+            &path,
+            &[UpdatedPackage {
+                name: "b".to_string(),
+                from: Some("1.0.0".to_string()),
+                to: "2.0.0".to_string(),
+                range: "^2.0.0".to_string(),
+                kind: DependencyKind::DevDependencies,
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "{\n  \"name\": \"app\",\n  \"devDependencies\": {\n    \"b\": \"^2.0.0\",\n    \"a\": \"^1.0.0\"\n  },\n  \"private\": true\n}\n"
+        );
     }
 }
@@ -0,0 +1,187 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Explain why a package is installed.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use clap::Parser;
+use colored::Colorize;
+use miette::Result;
+use package_spec::PackageSpec;
+use serde::Serialize;
+
+use crate::{
+    cli::{VoltCommand, VoltConfig},
+    core::io::{write, write_json},
+    core::model::lock_file::LockFile,
+    core::net::satisfies_spec,
+    core::utils::errors::VoltError,
+    core::utils::package::{DependencyKind, PackageJson},
+};
+
+/// Show every dependency path that leads to a package
+#[derive(Debug, Parser)]
+pub struct Why {
+    /// Package to explain, optionally with a version range (`name@range`).
+    package: PackageSpec,
+}
+
+/// An entry of `volt why --json`, one per installed version of the package.
+#[derive(Debug, Serialize)]
+struct WhyOutput {
+    name: String,
+    version: String,
+    paths: Vec<DependencyPath>,
+}
+
+#[derive(Debug, Serialize)]
+struct DependencyPath {
+    #[serde(rename = "type")]
+    kind: DependencyKind, // field of package.json the path starts from
+    packages: Vec<PathStep>, // from the dependency of the project to the package
+}
+
+#[derive(Debug, Serialize)]
+struct PathStep {
+    name: String,
+    version: String,
+    requested: String, // range the package was required with
+}
+
+#[async_trait]
+impl VoltCommand for Why {
+    async fn exec(self, config: VoltConfig) -> Result<()> {
+        let meta = config.meta();
+
+        let (name, requested) = match &self.package {
+            PackageSpec::Npm {
+                name, requested, ..
+            } => (name, requested.as_ref()),
+            spec => {
+                return Err(VoltError::PackageSpecificationError {
+                    spec: spec.to_string(),
+                }
+                .into())
+            }
+        };
+
+        let lock_file = LockFile::load(config.lockfile()?)?;
+        let cwd = config.cwd()?;
+
+        // walk from the dependencies in package.json, or from the locked ones without it
+        let (project, roots) = if cwd.join("package.json").exists() {
+            let (package_json, _) = PackageJson::get_from_dir(&cwd)?;
+
+            (
+                package_json.name.clone(),
+                package_json
+                    .dependencies_by_kind()
+                    .into_iter()
+                    .map(|(kind, name, range)| (kind, name.to_string(), range.to_string()))
+                    .collect::<Vec<_>>(),
+            )
+        } else {
+            (
+                cwd.file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                lock_file
+                    .dependencies
+                    .iter()
+                    .map(|(name, version)| {
+                        (DependencyKind::Dependencies, name.clone(), version.clone())
+                    })
+                    .collect(),
+            )
+        };
+
+        // <name@version, output>
+        let mut installed: BTreeMap<String, WhyOutput> = BTreeMap::new();
+
+        for (kind, root, range) in &roots {
+            let paths = lock_file.paths(root, range, |package| {
+                package.name == *name && satisfies_spec(requested, &package.version)
+            });
+
+            for path in paths {
+                let (_, package) = path[path.len() - 1];
+
+                installed
+                    .entry(format!("{}@{}", package.name, package.version))
+                    .or_insert_with(|| WhyOutput {
+                        name: package.name.clone(),
+                        version: package.version.clone(),
+                        paths: vec![],
+                    })
+                    .paths
+                    .push(DependencyPath {
+                        kind: *kind,
+                        packages: path
+                            .iter()
+                            .map(|(requested, package)| PathStep {
+                                name: package.name.clone(),
+                                version: package.version.clone(),
+                                requested: requested.to_string(),
+                            })
+                            .collect(),
+                    });
+            }
+        }
+
+        if installed.is_empty() {
+            return Err(VoltError::PackageNotInstalled {
+                spec: self.package.to_string(),
+            }
+            .into());
+        }
+
+        for (key, output) in &installed {
+            write(&key.bold().to_string(), &meta);
+
+            for path in &output.paths {
+                let root = match path.kind {
+                    DependencyKind::Dependencies => project.clone(),
+                    DependencyKind::DevDependencies => format!("{} (dev)", project),
+                    DependencyKind::OptionalDependencies => format!("{} (optional)", project),
+                    DependencyKind::PeerDependencies => format!("{} (peer)", project),
+                };
+
+                let steps = path
+                    .packages
+                    .iter()
+                    .map(|step| {
+                        format!(
+                            "{}@{} {}",
+                            step.name,
+                            step.version,
+                            format!("({})", step.requested).truecolor(125, 125, 125)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                write(&format!("  {} › {}", root.cyan(), steps.join(" › ")), &meta);
+            }
+
+            write("", &meta);
+        }
+
+        write_json(&installed.into_values().collect::<Vec<_>>(), &meta)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Registry metadata cached in the global store, kept for as long as `Cache-Control` allows and
+//! revalidated using `ETag` and `Last-Modified`.
+
+use miette::IntoDiagnostic;
+use reqwest::header::{
+    HeaderMap, HeaderValue, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Prefix of the keys under which registry metadata is cached.
+pub const METADATA_PREFIX: &str = "metadata::";
+
+/// Key of the cached metadata of `spec` on `registry`, a package name or a package name along
+/// with the requested version (trees resolved for different specs are cached separately).
+pub fn metadata_key(registry: &str, spec: &str) -> String {
+    format!("{}{}::{}", METADATA_PREFIX, registry, spec)
+}
+
+/// Key of the cached packument of `name` on `registry` (`full` packuments are cached separately
+/// from abbreviated ones).
+pub fn packument_key(registry: &str, name: &str, full: bool) -> String {
+    if full {
+        format!("{}::full", metadata_key(registry, name))
+    } else {
+        metadata_key(registry, name)
+    }
+}
+
+/// Where cached metadata was fetched from, for how long it can be used and how to revalidate it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CachePolicy {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    #[serde(default)]
+    pub fetched_at: u64, // seconds since the unix epoch
+    #[serde(default)]
+    pub max_age: Option<u64>, // seconds the response can be used without revalidating it
+    #[serde(default)]
+    pub no_cache: bool, // whether the response must be revalidated every time
+    #[serde(skip)]
+    pub no_store: bool, // whether the response must not be cached at all
+}
+
+impl CachePolicy {
+    /// Build the policy of a response fetched from `url`.
+    pub fn from_headers(url: &str, headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        let mut policy = Self {
+            url: url.to_string(),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            fetched_at: now(),
+            ..Default::default()
+        };
+
+        for directive in header(CACHE_CONTROL).unwrap_or_default().split(',') {
+            let directive = directive.trim().to_ascii_lowercase();
+
+            match directive.split_once('=') {
+                Some(("max-age", age)) => policy.max_age = age.trim_matches('"').parse().ok(),
+                None if directive == "no-cache" => policy.no_cache = true,
+                None if directive == "no-store" => policy.no_store = true,
+                _ => {}
+            }
+        }
+
+        policy
+    }
+
+    /// Whether the metadata fetched from `url` can still be used without asking the registry.
+    pub fn is_fresh(&self, url: &str) -> bool {
+        self.url == url
+            && !self.no_cache
+            && self
+                .max_age
+                .map_or(false, |age| now() < self.fetched_at.saturating_add(age))
+    }
+
+    /// Headers making a request for `url` conditional, so that the registry answers with
+    /// `304 Not Modified` if the cached metadata is still up to date.
+    pub fn conditional_headers(&self, url: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        // validators only apply to the url they were returned for
+        if self.url != url {
+            return headers;
+        }
+
+        if let Some(etag) = self
+            .etag
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(IF_NONE_MATCH, etag);
+        }
+
+        if let Some(last_modified) = self
+            .last_modified
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(IF_MODIFIED_SINCE, last_modified);
+        }
+
+        headers
+    }
+
+    /// The policy of a `304 Not Modified` response to a request revalidating this policy.
+    pub fn revalidated(&self, headers: &HeaderMap) -> Self {
+        let mut policy = Self::from_headers(&self.url, headers);
+
+        // a 304 doesn't always repeat the validators
+        policy.etag = policy.etag.or_else(|| self.etag.clone());
+        policy.last_modified = policy.last_modified.or_else(|| self.last_modified.clone());
+
+        policy
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Metadata cached in the global store.
+#[derive(Debug, Clone)]
+pub struct CachedMetadata {
+    pub policy: CachePolicy,
+    pub data: Vec<u8>,
+}
+
+impl CachedMetadata {
+    /// Read the metadata cached under `key`, if there is any (and it isn't corrupted).
+    pub fn read(store: &Path, key: &str) -> Option<Self> {
+        let read = || -> Result<Option<Self>, Box<dyn std::error::Error>> {
+            let metadata = match cacache::metadata_sync(store, key)? {
+                Some(metadata) => metadata,
+                None => return Ok(None),
+            };
+
+            Ok(Some(Self {
+                policy: serde_json::from_value(metadata.metadata)?,
+                data: cacache::read_hash_sync(store, &metadata.integrity)?,
+            }))
+        };
+
+        read().unwrap_or_else(|e| {
+            tracing::debug!("ignoring cached metadata for {}: {}", key, e);
+            None
+        })
+    }
+
+    /// Cache `data` under `key`, unless the registry asked for it not to be stored.
+    pub fn write(store: &Path, key: &str, policy: &CachePolicy, data: &[u8]) -> miette::Result<()> {
+        if policy.no_store {
+            return Ok(());
+        }
+
+        let mut writer = cacache::WriteOpts::new()
+            .metadata(serde_json::to_value(policy).into_diagnostic()?)
+            .open_sync(store, key)
+            .into_diagnostic()?;
+
+        writer.write_all(data).into_diagnostic()?;
+        writer.commit().into_diagnostic()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{metadata_key, CachePolicy, CachedMetadata};
+
+    use reqwest::header::{self, HeaderMap, HeaderValue};
+    use tempfile::tempdir;
+
+    #[test]
+    fn metadata_round_trips_through_the_store() {
+        let dir = tempdir().unwrap();
+        let key = metadata_key("https://registry.example.com", "react");
+
+        assert!(CachedMetadata::read(dir.path(), &key).is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, HeaderValue::from_static("\"abc\""));
+
+        let policy = CachePolicy::from_headers("https://registry.example.com/react", &headers);
+
+        CachedMetadata::write(dir.path(), &key, &policy, b"metadata").unwrap();
+
+        let cached = CachedMetadata::read(dir.path(), &key).unwrap();
+
+        assert_eq!(cached.policy, policy);
+        assert_eq!(cached.data, b"metadata");
+    }
+
+    #[test]
+    fn only_revalidates_the_same_url() {
+        let policy = CachePolicy {
+            url: "https://registry.example.com/react".to_string(),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+            ..Default::default()
+        };
+
+        let headers = policy.conditional_headers("https://registry.example.com/react");
+
+        assert_eq!(headers[header::IF_NONE_MATCH], "\"abc\"");
+        assert_eq!(
+            headers[header::IF_MODIFIED_SINCE],
+            "Wed, 21 Oct 2015 07:28:00 GMT"
+        );
+
+        assert!(policy
+            .conditional_headers("https://registry.example.com/vue")
+            .is_empty());
+    }
+
+    #[test]
+    fn honors_cache_control() {
+        let url = "https://registry.example.com/react";
+
+        let policy = |cache_control: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::CACHE_CONTROL,
+                HeaderValue::from_static(cache_control),
+            );
+
+            CachePolicy::from_headers(url, &headers)
+        };
+
+        assert!(policy("public, max-age=300").is_fresh(url));
+        assert!(!policy("public, max-age=300").is_fresh("https://registry.example.com/vue"));
+        assert!(!policy("max-age=0").is_fresh(url));
+        assert!(!policy("max-age=300, no-cache").is_fresh(url));
+        assert!(!policy("public").is_fresh(url));
+        assert!(policy("no-store").no_store);
+    }
+}
@@ -14,11 +14,27 @@
     limitations under the License.
 */
 
-// TODO: Actually use this somewhere
-#[allow(dead_code)]
+/// How output is printed, set by the global `--json`, `--silent`, `--verbose`, `--debug` and
+/// `--no-color` flags.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Meta {
     pub verbose: bool,
     pub debug: bool,
     pub silent: bool,
     pub no_color: bool,
+    pub json: bool,
+}
+
+impl Meta {
+    /// Whether human readable output is printed (it isn't with `--silent` or `--json`)
+    pub fn human(&self) -> bool {
+        !self.silent && !self.json
+    }
+
+    /// Whether progress bars and spinners are drawn: only for human readable output going to a
+    /// terminal, so that CI logs and redirected output stay readable. They're drawn on stderr,
+    /// which has to be a terminal too
+    pub fn progress(&self) -> bool {
+        self.human() && atty::is(atty::Stream::Stdout) && atty::is(atty::Stream::Stderr)
+    }
 }
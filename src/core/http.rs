@@ -0,0 +1,928 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! The HTTP client used for all registry traffic, retrying failed requests with exponential
+//! backoff and capping how many requests are in flight at once. Proxies, extra certificate
+//! authorities and client certificates are configured here so that every request uses them.
+
+use crate::{cli::VoltConfig, core::utils::errors::VoltError};
+
+use bytes::{Bytes, BytesMut};
+use indicatif::ProgressBar;
+use ipnet::IpNet;
+use rand::Rng;
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    Certificate, Client, ClientBuilder, Identity, Proxy, Request, RequestBuilder, StatusCode, Url,
+};
+use tokio::sync::Semaphore;
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// How failed requests are retried.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub retries: u32,        // how many times a request is retried before giving up
+    pub min_delay: Duration, // delay before the first retry
+    pub max_delay: Duration, // upper bound of the delay between two attempts
+}
+
+impl RetryPolicy {
+    /// Delay before retrying for the `attempt`th time (starting at 0): exponential backoff with
+    /// jitter, so that concurrent requests don't all retry at the same time.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .min_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+
+        // "equal jitter": somewhere between half of the delay and the full delay
+        let half = delay / 2;
+
+        half + half.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+}
+
+/// A fully read response.
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+/// How many requests may be in flight at once, overall and per host.
+#[derive(Debug)]
+struct Limits {
+    network: Semaphore,
+    per_host: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl Limits {
+    fn new(network: usize, per_host: usize) -> Self {
+        Self {
+            network: Semaphore::new(network),
+            per_host,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The semaphore limiting requests to the host (and port) of `url`.
+    fn host(&self, url: &str) -> Arc<Semaphore> {
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|url| {
+                let port = url.port_or_known_default()?;
+
+                url.host_str().map(|host| format!("{}:{}", host, port))
+            })
+            .unwrap_or_default();
+
+        self.hosts
+            .lock()
+            .unwrap()
+            .entry(host)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.per_host)))
+            .clone()
+    }
+}
+
+/// Client shared by every request of a command. Cloning it is cheap, clones share the same
+/// connection pool and the same concurrency limits.
+///
+/// Connections are kept alive and reused, and HTTP/2 is negotiated with servers supporting it so
+/// that concurrent requests to the registry are multiplexed over a single connection.
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    client: Client,
+    policy: RetryPolicy,
+    limits: Arc<Limits>,
+}
+
+impl HttpClient {
+    /// Build a client using the timeouts, retry and concurrency limits of `config`.
+    pub fn new(config: &VoltConfig) -> miette::Result<Self> {
+        let client = Client::builder()
+            .use_rustls_tls()
+            .user_agent(concat!("volt/", env!("CARGO_PKG_VERSION")))
+            .connect_timeout(config.connect_timeout())
+            .timeout(config.fetch_timeout())
+            .pool_max_idle_per_host(config.max_connections_per_host())
+            .http2_adaptive_window(true);
+
+        let client = with_tls(with_proxies(client, config)?, config)?
+            .build()
+            .map_err(VoltError::ClientBuildError)?;
+
+        Ok(Self {
+            client,
+            policy: config.retry_policy(),
+            limits: Arc::new(Limits::new(
+                config.network_concurrency(),
+                config.max_connections_per_host(),
+            )),
+        })
+    }
+
+    /// Send a `GET` request to `url`.
+    pub async fn get(&self, url: &str) -> miette::Result<HttpResponse> {
+        self.send(url, |client| client.get(url)).await
+    }
+
+    /// Send a `GET` request to `url` with extra headers.
+    pub async fn get_with_headers(
+        &self,
+        url: &str,
+        headers: HeaderMap,
+    ) -> miette::Result<HttpResponse> {
+        self.send(url, |client| client.get(url).headers(headers.clone()))
+            .await
+    }
+
+    /// Send a `GET` request to `url`, counting the bytes of the body on `progress` as they
+    /// arrive. The length of `progress` grows by the `Content-Length` of every response.
+    pub async fn get_with_progress(
+        &self,
+        url: &str,
+        progress: &ProgressBar,
+    ) -> miette::Result<HttpResponse> {
+        self.send_with_progress(url, |client| client.get(url), Some(progress))
+            .await
+    }
+
+    /// Send the request built by `request` and read its whole body, retrying on connection
+    /// errors, timeouts (including while reading the body) and on responses asking to try
+    /// again later.
+    ///
+    /// Once every retry has been used up the last response is returned as is, so callers still
+    /// get to report its status.
+    pub async fn send(
+        &self,
+        url: &str,
+        request: impl Fn(&Client) -> RequestBuilder,
+    ) -> miette::Result<HttpResponse> {
+        self.send_with_progress(url, request, None).await
+    }
+
+    /// Send the request built by `request` once, without retrying. Used for requests that
+    /// aren't safe to repeat, a registry may have applied a publish whose response was lost.
+    pub async fn send_once(
+        &self,
+        url: &str,
+        request: impl FnOnce(&Client) -> RequestBuilder,
+    ) -> miette::Result<HttpResponse> {
+        let request_error = |e| VoltError::RequestError {
+            source: e,
+            url: url.to_string(),
+        };
+
+        let request = request(&self.client).build().map_err(request_error)?;
+
+        Ok(self
+            .attempt(url, request, None)
+            .await
+            .map_err(request_error)?)
+    }
+
+    async fn send_with_progress(
+        &self,
+        url: &str,
+        request: impl Fn(&Client) -> RequestBuilder,
+        progress: Option<&ProgressBar>,
+    ) -> miette::Result<HttpResponse> {
+        let mut attempt = 0;
+        let mut progress = progress.map(RequestProgress::new);
+
+        loop {
+            let retries_left = attempt < self.policy.retries;
+
+            let request = request(&self.client)
+                .build()
+                .map_err(|e| VoltError::RequestError {
+                    source: e,
+                    url: url.to_string(),
+                })?;
+            let method = request.method().clone();
+
+            let delay = match self.attempt(url, request, progress.as_mut()).await {
+                Ok(response) if retries_left && is_retryable(response.status) => {
+                    let delay = retry_after(&response.headers)
+                        .map(|delay| delay.min(self.policy.max_delay))
+                        .unwrap_or_else(|| self.policy.backoff(attempt));
+
+                    tracing::debug!(
+                        "{} {} - {}, retrying in {:?}",
+                        method,
+                        url,
+                        response.status,
+                        delay
+                    );
+
+                    delay
+                }
+                Ok(response) => return Ok(response),
+                Err(e) if retries_left => {
+                    let delay = self.policy.backoff(attempt);
+
+                    tracing::debug!("{} {} failed ({}), retrying in {:?}", method, url, e, delay);
+
+                    delay
+                }
+                Err(e) => {
+                    return Err(VoltError::RequestError {
+                        source: e,
+                        url: url.to_string(),
+                    }
+                    .into())
+                }
+            };
+
+            tokio::time::sleep(delay).await;
+
+            attempt += 1;
+        }
+    }
+
+    /// Send a request once. Permits are only held for the attempt itself, requests waiting to
+    /// be retried don't count against the limits.
+    async fn attempt(
+        &self,
+        url: &str,
+        request: Request,
+        mut progress: Option<&mut RequestProgress<'_>>,
+    ) -> reqwest::Result<HttpResponse> {
+        let host = self.limits.host(url);
+
+        // the host permit is taken first, so requests queued for a busy host don't hold on to
+        // permits that requests to other hosts could use
+        let _host = host.acquire().await.expect("semaphore is never closed");
+        let _network = self
+            .limits
+            .network
+            .acquire()
+            .await
+            .expect("semaphore is never closed");
+
+        let mut response = self.client.execute(request).await?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+
+        let length = response.content_length();
+        // don't trust the announced length blindly when allocating
+        let mut body = BytesMut::with_capacity(length.unwrap_or_default().min(1 << 24) as usize);
+
+        if let (Some(progress), Some(length)) = (progress.as_mut(), length) {
+            progress.grow_to(length);
+        }
+
+        // reading the body is part of the attempt, a connection reset halfway through is
+        // retried like any other connection error
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+
+            if let Some(progress) = progress.as_mut() {
+                if length.is_none() {
+                    progress.grow_to(body.len() as u64);
+                }
+
+                progress.advance_to(body.len() as u64);
+            }
+        }
+
+        Ok(HttpResponse {
+            status,
+            headers,
+            body: body.freeze(),
+        })
+    }
+}
+
+/// What a request counted on a shared progress bar. Retried attempts read the body again from
+/// the start, they only move the bar once they get past what earlier attempts counted.
+struct RequestProgress<'a> {
+    bar: &'a ProgressBar,
+    length: u64,
+    position: u64,
+}
+
+impl<'a> RequestProgress<'a> {
+    fn new(bar: &'a ProgressBar) -> Self {
+        Self {
+            bar,
+            length: 0,
+            position: 0,
+        }
+    }
+
+    /// The body is (at least) `length` bytes long.
+    fn grow_to(&mut self, length: u64) {
+        if length > self.length {
+            self.bar.inc_length(length - self.length);
+            self.length = length;
+        }
+    }
+
+    /// `position` bytes of the body were read.
+    fn advance_to(&mut self, position: u64) {
+        if position > self.position {
+            self.bar.inc(position - self.position);
+            self.position = position;
+        }
+    }
+}
+
+/// Route requests through the configured proxies, replacing the ones reqwest would pick up from
+/// the environment on its own (those ignore `--noproxy`).
+fn with_proxies(builder: ClientBuilder, config: &VoltConfig) -> miette::Result<ClientBuilder> {
+    let parse = |url: Option<String>| -> miette::Result<Option<String>> {
+        url.map(|url| {
+            // `host:port` is a common way to write a proxy, assume it's spoken to over http
+            let absolute = if url.contains("://") {
+                url.clone()
+            } else {
+                format!("http://{}", url)
+            };
+
+            // make sure the url is valid now rather than failing every request later on
+            match Proxy::all(absolute.as_str()) {
+                Ok(_) => Ok(absolute),
+                Err(source) => Err(VoltError::InvalidProxy { source, url }.into()),
+            }
+        })
+        .transpose()
+    };
+
+    let http = parse(config.proxy())?;
+    let https = parse(config.https_proxy())?;
+    let noproxy = config.noproxy();
+
+    let builder = builder.no_proxy();
+
+    if http.is_none() && https.is_none() {
+        return Ok(builder);
+    }
+
+    Ok(builder.proxy(Proxy::custom(move |url| {
+        if bypasses_proxy(&noproxy, url) {
+            return None;
+        }
+
+        match url.scheme() {
+            "https" => https.clone(),
+            _ => http.clone(),
+        }
+    })))
+}
+
+/// Whether `url` matches one of the `noproxy` entries: `*`, a domain (matching its subdomains
+/// too), an IP address or range, each optionally followed by a port.
+fn bypasses_proxy(noproxy: &[String], url: &Url) -> bool {
+    let host = match url.host_str() {
+        Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+        None => return false,
+    };
+    let port = url.port_or_known_default();
+
+    noproxy.iter().any(|entry| {
+        if entry == "*" {
+            return true;
+        }
+
+        if let Ok(net) = entry.parse::<IpNet>() {
+            return host.parse::<IpAddr>().map_or(false, |ip| net.contains(&ip));
+        }
+
+        if let Ok(ip) = entry
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+        {
+            return host.parse::<IpAddr>().ok() == Some(ip);
+        }
+
+        // `host:port` only matches that port
+        let (domain, entry_port) = match entry.rsplit_once(':') {
+            Some((domain, entry_port)) if !domain.contains(':') => {
+                (domain, entry_port.parse::<u16>().ok())
+            }
+            _ => (entry.as_str(), None),
+        };
+
+        if entry_port.is_some() && entry_port != port {
+            return false;
+        }
+
+        let domain = domain.trim_start_matches('.').to_lowercase();
+        let host = host.to_lowercase();
+
+        host == domain || host.ends_with(&format!(".{}", domain))
+    })
+}
+
+/// Trust the configured certificate authorities and present the client certificate, if any.
+fn with_tls(builder: ClientBuilder, config: &VoltConfig) -> miette::Result<ClientBuilder> {
+    let mut builder = builder.danger_accept_invalid_certs(!config.strict_ssl());
+
+    if let Some(cafile) = config.cafile() {
+        let pem = read_pem(cafile)?;
+        let invalid = |source| VoltError::InvalidCertificate {
+            source,
+            path: cafile.display().to_string(),
+        };
+
+        let certificates = pem_certificates(&pem);
+
+        // a file without any certificate is still parsed, for the error
+        if certificates.is_empty() {
+            Certificate::from_pem(&pem).map_err(invalid)?;
+        }
+
+        for certificate in certificates {
+            builder =
+                builder.add_root_certificate(Certificate::from_pem(certificate).map_err(invalid)?);
+        }
+    }
+
+    if let Some((cert, key)) = config.client_cert() {
+        // the identity is read from a single PEM holding both the certificates and the key
+        let mut pem = read_pem(cert)?;
+
+        pem.push(b'\n');
+        pem.extend(read_pem(key)?);
+
+        let identity =
+            Identity::from_pem(&pem).map_err(|source| VoltError::InvalidCertificate {
+                source,
+                path: cert.display().to_string(),
+            })?;
+
+        builder = builder.identity(identity);
+    }
+
+    Ok(builder)
+}
+
+/// Every certificate of a PEM bundle, `Certificate::from_pem` only reads the first one.
+fn pem_certificates(pem: &[u8]) -> Vec<&[u8]> {
+    const BEGIN: &[u8] = b"-----BEGIN CERTIFICATE-----";
+    const END: &[u8] = b"-----END CERTIFICATE-----";
+
+    let find = |haystack: &[u8], needle: &[u8]| {
+        haystack
+            .windows(needle.len())
+            .position(|window| window == needle)
+    };
+
+    let mut certificates = vec![];
+    let mut rest = pem;
+
+    while let Some(start) = find(rest, BEGIN) {
+        let end = match find(&rest[start..], END) {
+            Some(end) => start + end + END.len(),
+            None => break,
+        };
+
+        certificates.push(&rest[start..end]);
+        rest = &rest[end..];
+    }
+
+    certificates
+}
+
+fn read_pem(path: &Path) -> miette::Result<Vec<u8>> {
+    Ok(
+        std::fs::read(path).map_err(|source| VoltError::ReadFileError {
+            source,
+            name: path.display().to_string(),
+        })?,
+    )
+}
+
+/// Whether a response with this status is worth retrying.
+fn is_retryable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Parse a `Retry-After` header, given either in seconds or as an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    httpdate::parse_http_date(value)
+        .ok()?
+        .duration_since(SystemTime::now())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bypasses_proxy, retry_after, HttpClient, Limits, RetryPolicy};
+    use crate::cli::VoltConfig;
+
+    use clap::Parser;
+    use indicatif::{ProgressBar, ProgressDrawTarget};
+
+    use reqwest::{
+        header::{HeaderMap, HeaderValue, RETRY_AFTER},
+        Client, StatusCode, Url,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use tokio_rustls::{
+        rustls::{self, ServerConfig},
+        TlsAcceptor,
+    };
+
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    /// Serve `responses` (raw HTTP responses) in order, one per connection, and count requests.
+    async fn serve(responses: Vec<&'static str>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        tokio::spawn({
+            let requests = requests.clone();
+
+            async move {
+                for response in responses {
+                    let (mut socket, _) = listener.accept().await.unwrap();
+                    let mut buffer = [0; 4096];
+
+                    let _ = socket.read(&mut buffer).await.unwrap();
+                    requests.fetch_add(1, Ordering::SeqCst);
+
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                    socket.shutdown().await.unwrap();
+                }
+            }
+        });
+
+        (url, requests)
+    }
+
+    fn client(retries: u32) -> HttpClient {
+        HttpClient {
+            client: Client::new(),
+            policy: RetryPolicy {
+                retries,
+                min_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(10),
+            },
+            limits: Arc::new(Limits::new(16, 8)),
+        }
+    }
+
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+
+    #[tokio::test]
+    async fn retries_server_errors_and_rate_limits() {
+        let (url, requests) = serve(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            OK,
+        ])
+        .await;
+
+        let response = client(4).get(&url).await.unwrap();
+
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(&response.body[..], b"ok");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_bodies_cut_short() {
+        let (url, requests) = serve(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 100\r\nConnection: close\r\n\r\npartial",
+            OK,
+        ])
+        .await;
+
+        let response = client(4).get(&url).await.unwrap();
+
+        assert_eq!(&response.body[..], b"ok");
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_missing_packages() {
+        let (url, requests) = serve(vec![
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            OK,
+        ])
+        .await;
+
+        let response = client(4).get(&url).await.unwrap();
+
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn returns_the_last_response_once_retries_are_used_up() {
+        let (url, requests) = serve(vec![
+            "HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ])
+        .await;
+
+        let response = client(1).get(&url).await.unwrap();
+
+        assert_eq!(response.status, StatusCode::BAD_GATEWAY);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn sends_requests_once_without_retrying() {
+        let (url, requests) = serve(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            OK,
+        ])
+        .await;
+
+        let response = client(4)
+            .send_once(&url, |client| client.put(&url))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn reports_download_progress() {
+        let (url, _) = serve(vec![
+            OK,
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+        ])
+        .await;
+
+        let progress = ProgressBar::with_draw_target(0, ProgressDrawTarget::hidden());
+        let client = client(0);
+
+        client.get_with_progress(&url, &progress).await.unwrap();
+
+        assert_eq!((progress.position(), progress.length()), (2, 2));
+
+        // without a `Content-Length` the length grows as the body comes in
+        client.get_with_progress(&url, &progress).await.unwrap();
+
+        assert_eq!((progress.position(), progress.length()), (5, 5));
+    }
+
+    #[tokio::test]
+    async fn counts_retried_downloads_once() {
+        let (url, _) = serve(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\nok",
+            "HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\nokok",
+        ])
+        .await;
+
+        let progress = ProgressBar::with_draw_target(0, ProgressDrawTarget::hidden());
+
+        client(1).get_with_progress(&url, &progress).await.unwrap();
+
+        assert_eq!((progress.position(), progress.length()), (4, 4));
+    }
+
+    #[tokio::test]
+    async fn limits_requests_in_flight() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let most_in_flight = Arc::new(AtomicUsize::new(0));
+
+        tokio::spawn({
+            let in_flight = in_flight.clone();
+            let most_in_flight = most_in_flight.clone();
+
+            async move {
+                loop {
+                    let (mut socket, _) = listener.accept().await.unwrap();
+                    let in_flight = in_flight.clone();
+                    let most_in_flight = most_in_flight.clone();
+
+                    tokio::spawn(async move {
+                        let mut buffer = [0; 4096];
+                        let _ = socket.read(&mut buffer).await.unwrap();
+
+                        let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        most_in_flight.fetch_max(current, Ordering::SeqCst);
+
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+                        socket.write_all(OK.as_bytes()).await.unwrap();
+                        socket.shutdown().await.unwrap();
+                    });
+                }
+            }
+        });
+
+        let client = HttpClient {
+            limits: Arc::new(Limits::new(4, 2)),
+            ..client(0)
+        };
+
+        let responses = futures::future::join_all((0..8).map(|_| client.get(&url))).await;
+
+        assert!(responses.into_iter().all(|response| response.is_ok()));
+        assert_eq!(most_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn sends_requests_through_the_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = format!("{}", listener.local_addr().unwrap());
+
+        let request_line = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 4096];
+
+            let read = socket.read(&mut buffer).await.unwrap();
+            socket.write_all(OK.as_bytes()).await.unwrap();
+            socket.shutdown().await.unwrap();
+
+            String::from_utf8_lossy(&buffer[..read])
+                .lines()
+                .next()
+                .unwrap()
+                .to_string()
+        });
+
+        let config = VoltConfig::parse_from(["volt", "--proxy", &proxy, "--fetch-retries", "0"]);
+        let response = HttpClient::new(&config)
+            .unwrap()
+            .get("http://registry.invalid/volt")
+            .await
+            .unwrap();
+
+        assert_eq!(&response.body[..], b"ok");
+        assert_eq!(
+            request_line.await.unwrap(),
+            "GET http://registry.invalid/volt HTTP/1.1"
+        );
+    }
+
+    #[tokio::test]
+    async fn trusts_every_certificate_of_the_cafile() {
+        let fixture =
+            |name: &str| format!("{}/tests/fixtures/tls/{}", env!("CARGO_MANIFEST_DIR"), name);
+
+        let read = |name: &str| std::fs::read(fixture(name)).unwrap();
+
+        let certificates = rustls_pemfile::certs(&mut &read("server.pem")[..]).unwrap();
+        let key = rustls_pemfile::pkcs8_private_keys(&mut &read("server.key")[..])
+            .unwrap()
+            .remove(0);
+
+        let acceptor = TlsAcceptor::from(Arc::new(
+            ServerConfig::builder()
+                .with_safe_defaults()
+                .with_no_client_auth()
+                .with_single_cert(
+                    certificates.into_iter().map(rustls::Certificate).collect(),
+                    rustls::PrivateKey(key),
+                )
+                .unwrap(),
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "https://localhost:{}/",
+            listener.local_addr().unwrap().port()
+        );
+
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+
+                tokio::spawn(async move {
+                    // clients that don't trust the certificate end the handshake
+                    if let Ok(mut stream) = acceptor.accept(socket).await {
+                        let mut buffer = [0; 4096];
+                        let _ = stream.read(&mut buffer).await.unwrap();
+
+                        stream.write_all(OK.as_bytes()).await.unwrap();
+                        stream.shutdown().await.unwrap();
+                    }
+                });
+            }
+        });
+
+        // the CA that signed the certificate is the second one of the bundle
+        let bundle = fixture("bundle.pem");
+        let config = VoltConfig::parse_from(["volt", "--cafile", &bundle, "--fetch-retries", "0"]);
+        let response = HttpClient::new(&config).unwrap().get(&url).await.unwrap();
+
+        assert_eq!(&response.body[..], b"ok");
+
+        let config = VoltConfig::parse_from(["volt", "--fetch-retries", "0"]);
+
+        assert!(HttpClient::new(&config).unwrap().get(&url).await.is_err());
+    }
+
+    #[test]
+    fn matches_noproxy_entries() {
+        let noproxy = [
+            ".internal.dev".to_string(),
+            "example.com".to_string(),
+            "registry.local:8443".to_string(),
+            "10.0.0.0/8".to_string(),
+            "::1".to_string(),
+        ];
+        let bypasses = |url: &str| bypasses_proxy(&noproxy, &Url::parse(url).unwrap());
+
+        assert!(bypasses("https://registry.internal.dev/volt"));
+        assert!(bypasses("https://example.com/volt"));
+        assert!(bypasses("https://www.example.com/volt"));
+        assert!(bypasses("https://registry.local:8443/volt"));
+        assert!(bypasses("http://10.1.2.3/volt"));
+        assert!(bypasses("http://[::1]:4873/volt"));
+
+        assert!(!bypasses("https://notexample.com/volt"));
+        assert!(!bypasses("https://registry.local/volt"));
+        assert!(!bypasses("http://11.1.2.3/volt"));
+        assert!(!bypasses_proxy(
+            &[],
+            &Url::parse("https://example.com").unwrap()
+        ));
+        assert!(bypasses_proxy(
+            &["*".to_string()],
+            &Url::parse("https://example.com").unwrap()
+        ));
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_maximum() {
+        let policy = RetryPolicy {
+            retries: 10,
+            min_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        };
+
+        for attempt in 0..10 {
+            let expected =
+                (Duration::from_millis(100) * 2u32.pow(attempt)).min(Duration::from_secs(1));
+            let delay = policy.backoff(attempt);
+
+            assert!(delay >= expected / 2 && delay <= expected, "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        let mut headers = HeaderMap::new();
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+}
@@ -16,141 +16,749 @@
 
 use crate::{
     cli::VoltConfig,
-    core::{classes::meta::Meta, utils::voltapi::VoltPackage},
+    core::{
+        classes::meta::Meta,
+        store::{
+            link_from_store, normalize_mode, IndexedFile, IndexedSymlink, Linker, PackageIndex,
+        },
+        utils::{errors::VoltError, voltapi::VoltPackage},
+    },
 };
 
 use colored::Colorize;
 use miette::IntoDiagnostic;
-use ssri::Integrity;
-use tar::Archive;
+use serde::Serialize;
+use tar::{Archive, EntryType};
 
 use std::{
-    collections::HashMap,
-    io::{Cursor, Read, Write},
-    path::PathBuf,
+    collections::{HashMap, VecDeque},
+    ffi::OsStr,
+    fs,
+    io::{Cursor, Read},
+    path::{Component, Path, PathBuf},
 };
 
-pub fn _write(text: &str, metadata: &Meta) {
-    if !metadata.silent {
-        if metadata.no_color {
-            println!("{}", text.bright_white());
-        } else {
-            println!("{}", text);
-        }
+/// Print human readable output, unless `--silent` or `--json` was given.
+pub fn write(text: &str, metadata: &Meta) {
+    if metadata.human() {
+        println!("{}", text);
     }
 }
 
-pub fn _write_verbose(text: &str, metadata: &Meta) {
-    if !metadata.silent && metadata.verbose {
-        if metadata.no_color {
-            println!(
-                "{}: {}",
-                "verbose".bright_white().bold(),
-                text.bright_white()
-            );
-        } else {
-            println!(
-                "{}: {}",
-                "verbose".bright_green().bold(),
-                text.bright_white()
-            );
-        }
+/// Print details about what volt is doing to stderr with `--verbose`.
+pub fn write_verbose(text: &str, metadata: &Meta) {
+    if metadata.human() && metadata.verbose {
+        eprintln!("{}: {}", "verbose".bright_green().bold(), text);
     }
 }
 
-pub fn _write_debug(text: &str, metadata: &Meta) {
-    if !metadata.silent && metadata.debug {
-        if metadata.no_color {
-            println!("{}: {}", "debug".bright_white().bold(), text.bright_white());
-        } else {
-            println!(
-                "{}: {}",
-                "debug".bright_yellow().bold(),
-                text.bright_white()
-            );
-        }
+/// Print debugging information to stderr with `--debug`.
+pub fn write_debug(text: &str, metadata: &Meta) {
+    if metadata.human() && metadata.debug {
+        eprintln!("{}: {}", "debug".bright_yellow().bold(), text);
     }
 }
 
+/// Print `value` as JSON with `--json`.
+///
+/// Every command printing JSON prints a single document, so the output can be parsed as a whole.
+pub fn write_json<T: Serialize>(value: &T, metadata: &Meta) -> miette::Result<()> {
+    if metadata.json {
+        println!("{}", serde_json::to_string_pretty(value).into_diagnostic()?);
+    }
+
+    Ok(())
+}
+
+/// Path a package is installed at, e.g. `node_modules/.volt/send@0.17.2/node_modules/send`
+pub fn package_root(package: &VoltPackage, config: &VoltConfig) -> miette::Result<PathBuf> {
+    Ok(config
+        .node_modules()?
+        .join(VoltConfig::VOLT_HOME)
+        .join(package.directory_name())
+        .join("node_modules")
+        .join(&package.name))
+}
+
 pub fn extract_tarball(
     data: Vec<u8>,
     package: &VoltPackage,
     config: &VoltConfig,
+    linker: &Linker,
 ) -> miette::Result<()> {
+    let package_root = package_root(package, config)?;
+
+    let volt_home = config.volt_home()?;
+
+    // extract to the global store and link the files into node_modules (pnpm linking algorithm)
+    let index = unpack_tarball(data, &package_root, &volt_home, linker)?;
+
+    // Write the package's index to the content-addressable store
+    index.write(&volt_home, &package.cacache_key())
+}
+
+/// Unpack a decompressed package tarball into `destination`, writing every regular file into
+/// the content-addressable store located at `store` and linking it from there.
+///
+/// The single top-level directory of the tarball (usually `package/`, but any name is accepted)
+/// is stripped. Entries that would be written outside of `destination` are rejected, symlinks
+/// and hardlinks are recreated as long as they point inside the package, and the executable
+/// bit of regular files is preserved. Device files, fifos and other special entries are skipped.
+///
+/// ## Returns
+/// * The index of the package, listing every file and symlink that was extracted
+pub fn unpack_tarball(
+    data: Vec<u8>,
+    destination: &Path,
+    store: &Path,
+    linker: &Linker,
+) -> miette::Result<PackageIndex> {
     // Generate the tarball archive given the decompressed bytes
-    let mut node_archive = Archive::new(Cursor::new(data));
+    let mut archive = Archive::new(Cursor::new(data));
 
-    // extract to both the global store + node_modules (in the case of them using the pnpm linking algorithm)
-    let mut cas_file_map: HashMap<String, Integrity> = HashMap::new();
+    let mut index = PackageIndex::default();
+
+    // position of every regular file in the index, used to resolve hardlinks
+    let mut extracted_files: HashMap<String, usize> = HashMap::new();
 
     // Add package's directory to list of created directories
-    let mut created_directories: Vec<PathBuf> = vec![];
+    let mut created_directories: Vec<PathBuf> = vec![destination.to_path_buf()];
 
-    for entry in node_archive.entries().into_diagnostic()? {
-        let mut entry = entry.into_diagnostic()?;
+    fs::create_dir_all(destination).into_diagnostic()?;
 
-        // Read the contents of the entry
-        let mut buffer = Vec::with_capacity(entry.size() as usize);
-        entry.read_to_end(&mut buffer).into_diagnostic()?;
+    for entry in archive.entries().into_diagnostic()? {
+        let mut entry = entry.into_diagnostic()?;
 
-        let entry_path = entry.path().unwrap();
+        let raw_path = entry.path().into_diagnostic()?.into_owned();
 
         // Remove `package/` from `package/lib/index.js`
-        let cleaned_entry_path_string = entry_path.strip_prefix("package/").unwrap();
+        let relative_path = match sanitize_entry_path(&raw_path)? {
+            Some(path) => path,
+            // the top-level directory itself
+            None => continue,
+        };
+
+        let entry_path = destination.join(&relative_path);
+
+        // a symlink extracted earlier would be followed by the writes below
+        if is_under_symlink(destination, &relative_path) {
+            return Err(VoltError::TarballPathTraversal {
+                path: raw_path.display().to_string(),
+            }
+            .into());
+        }
 
-        // Create the path to the local .volt directory
-        let mut package_directory = config.node_modules()?.join(VoltConfig::VOLT_HOME);
+        let entry_type = entry.header().entry_type();
 
-        // Add package's directory to it
-        package_directory.push(package.directory_name());
+        if entry_type.is_dir() {
+            if !created_directories.iter().any(|p| p == &entry_path) {
+                fs::create_dir_all(&entry_path).into_diagnostic()?;
+                created_directories.push(entry_path);
+            }
 
-        // push node_modules/.volt/send@0.17.2 to the list (because we created it in the previous step)
-        created_directories.push(package_directory.clone());
+            continue;
+        }
 
-        // Add the cleaned path to the package's directory
-        let mut entry_path = package_directory.clone();
+        // Only regular files and links are extracted, everything else (character devices,
+        // fifos, ...) has no business being inside of a package.
+        if !(entry_type.is_file() || entry_type.is_symlink() || entry_type.is_hard_link()) {
+            continue;
+        }
 
-        entry_path.push("node_modules");
+        // If we haven't created the parent directory yet, create it
+        if let Some(parent) = entry_path.parent() {
+            if !created_directories.iter().any(|p| p == parent) {
+                fs::create_dir_all(parent).into_diagnostic()?;
+                created_directories.push(parent.to_path_buf());
+            }
+        }
 
-        entry_path.push(&package.name);
+        let cas_key = relative_path.to_string_lossy().replace('\\', "/");
+
+        match entry_type {
+            EntryType::Symlink => {
+                let target = entry
+                    .link_name()
+                    .into_diagnostic()?
+                    .map(|target| target.into_owned())
+                    .unwrap_or_default();
+
+                if !symlink_stays_inside(&relative_path, &target) {
+                    return Err(VoltError::TarballLinkEscape {
+                        path: raw_path.display().to_string(),
+                        target: target.display().to_string(),
+                    }
+                    .into());
+                }
+
+                // a previous entry might have already created this path
+                let _ = fs::remove_file(&entry_path);
+
+                let symlink = IndexedSymlink {
+                    path: cas_key,
+                    target: target.to_string_lossy().replace('\\', "/"),
+                };
+
+                symlink.create(destination)?;
+
+                index.symlinks.push(symlink);
+            }
+            EntryType::Link => {
+                let target = entry
+                    .link_name()
+                    .into_diagnostic()?
+                    .map(|target| target.into_owned())
+                    .unwrap_or_default();
+
+                let target_path = sanitize_entry_path(&target)?;
+
+                let target_key = target_path
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().replace('\\', "/"));
+
+                let target_file = match target_key.and_then(|key| extracted_files.get(&key)) {
+                    Some(position) => index.files[*position].clone(),
+                    None => {
+                        return Err(VoltError::TarballMissingLinkTarget {
+                            path: raw_path.display().to_string(),
+                            target: target.display().to_string(),
+                        }
+                        .into())
+                    }
+                };
+
+                let file = IndexedFile {
+                    path: cas_key.clone(),
+                    mode: normalize_mode(entry.header().mode().unwrap_or(0o644)),
+                    ..target_file
+                };
+
+                link_from_store(store, &file.integrity()?, &entry_path, file.mode, linker)?;
+
+                extracted_files.insert(cas_key, index.files.len());
+                index.files.push(file);
+            }
+            _ => {
+                // Read the contents of the entry
+                let mut buffer = Vec::with_capacity(entry.size() as usize);
+                entry.read_to_end(&mut buffer).into_diagnostic()?;
+
+                // Write the contents of the entry into the content-addressable store located at `app.volt_dir`
+                // We get a hash of the file
+                let sri = cacache::write_hash_sync(store, &buffer).into_diagnostic()?;
+
+                let mode = normalize_mode(entry.header().mode().unwrap_or(0o644));
+
+                // Link the contents to node_modules
+                link_from_store(store, &sri, &entry_path, mode, linker)?;
+
+                // Insert the name of the file and map it to the hash of the file
+                extracted_files.insert(cas_key.clone(), index.files.len());
+                index.files.push(IndexedFile {
+                    path: cas_key,
+                    size: buffer.len() as u64,
+                    mode,
+                    integrity: sri.to_string(),
+                });
+            }
+        }
+    }
 
-        entry_path.push(cleaned_entry_path_string);
+    // links extracted later can change where an earlier one resolves, so the targets are
+    // resolved once every link is in place
+    let escaping = index
+        .symlinks
+        .iter()
+        .filter(|symlink| {
+            !resolves_inside(
+                destination,
+                Path::new(&symlink.path),
+                Path::new(&symlink.target),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    for symlink in &escaping {
+        let _ = fs::remove_file(destination.join(&symlink.path));
+    }
 
-        // Get the entry's parent
-        let entry_path_parent = entry_path.parent().unwrap();
+    if let Some(symlink) = escaping.first() {
+        return Err(VoltError::TarballLinkEscape {
+            path: symlink.path.clone(),
+            target: symlink.target.clone(),
+        }
+        .into());
+    }
 
-        // If we haven't created this directory yet, create it
-        if !created_directories.iter().any(|p| p == entry_path_parent) {
-            created_directories.push(entry_path_parent.to_path_buf());
-            std::fs::create_dir_all(entry_path_parent).into_diagnostic()?;
+    Ok(index)
+}
+
+/// Strip the top-level directory from a tarball entry path, rejecting absolute paths and any
+/// path containing `..`.
+///
+/// Returns `None` for the top-level directory itself.
+fn sanitize_entry_path(path: &Path) -> miette::Result<Option<PathBuf>> {
+    let mut cleaned = PathBuf::new();
+    let mut stripped_root = false;
+
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => {
+                if stripped_root {
+                    cleaned.push(part);
+                } else {
+                    stripped_root = true;
+                }
+            }
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(VoltError::TarballPathTraversal {
+                    path: path.display().to_string(),
+                }
+                .into());
+            }
         }
+    }
+
+    if cleaned.as_os_str().is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(cleaned))
+    }
+}
+
+/// Whether one of the directories leading to `path` (relative to `destination`) is a symlink.
+///
+/// Each symlink of a package is checked on its own, but chained links can still lead outside of
+/// it: with `a/b -> ..` and `a/b/c -> ..`, `a/b/c` points above the package root. Nothing is
+/// extracted through a symlink, so such chains are never followed.
+fn is_under_symlink(destination: &Path, path: &Path) -> bool {
+    path.ancestors()
+        .skip(1)
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .any(|ancestor| {
+            fs::symlink_metadata(destination.join(ancestor))
+                .map_or(false, |metadata| metadata.file_type().is_symlink())
+        })
+}
 
-        let mut file_path = package_directory.join("node_modules");
+/// Check that a symlink at `path` (relative to the package root) pointing at `target` resolves
+/// to a location inside of the package.
+fn symlink_stays_inside(path: &Path, target: &Path) -> bool {
+    // where the link lives, relative to the package root
+    let mut depth = path.components().count().saturating_sub(1);
+
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if depth == 0 {
+                    return false;
+                }
+
+                depth -= 1;
+            }
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
 
-        file_path.push(package.name.clone());
+    true
+}
 
-        file_path.push(cleaned_entry_path_string);
+/// Most symlinks followed to resolve a target, as many as Linux follows.
+const MAX_FOLLOWED_LINKS: usize = 40;
+
+/// Check that the symlink at `path` pointing at `target` resolves inside of the package extracted
+/// to `destination`, following the symlinks already extracted that it passes through.
+///
+/// [`symlink_stays_inside`] checks a target on its own: with `a/b -> ..`, `x -> a/b/..` looks
+/// like it points at `a`, but resolves above the package root.
+fn resolves_inside(destination: &Path, path: &Path, target: &Path) -> bool {
+    // the location being resolved, relative to the package root
+    let mut resolved = path
+        .parent()
+        .map(|parent| parent.iter().map(OsStr::to_os_string).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut pending = target
+        .iter()
+        .map(OsStr::to_os_string)
+        .collect::<VecDeque<_>>();
+    let mut followed = 0;
+
+    if target.has_root() {
+        return false;
+    }
 
-        // Write the contents to node_modules
-        let mut file = std::fs::File::create(&file_path).unwrap();
+    while let Some(part) = pending.pop_front() {
+        if part == "." {
+            continue;
+        }
 
-        file.write_all(&buffer).into_diagnostic()?;
+        if part == ".." {
+            if resolved.pop().is_none() {
+                return false;
+            }
 
-        // Write the contents of the entry into the content-addressable store located at `app.volt_dir`
-        // We get a hash of the file
-        let sri = cacache::write_hash_sync(&config.volt_home()?, &buffer).into_diagnostic()?;
+            continue;
+        }
 
-        // Insert the name of the file and map it to the hash of the file
-        cas_file_map.insert(cleaned_entry_path_string.to_str().unwrap().to_string(), sri);
+        resolved.push(part);
+
+        if let Ok(link) = fs::read_link(destination.join(resolved.iter().collect::<PathBuf>())) {
+            // loops and absolute targets never resolve inside of the package
+            followed += 1;
+
+            if followed > MAX_FOLLOWED_LINKS || link.has_root() {
+                return false;
+            }
+
+            resolved.pop();
+
+            for part in link.iter().rev() {
+                pending.push_front(part.to_os_string());
+            }
+        }
     }
 
-    // Write the file, shasum map to the content-addressable store
-    cacache::write_sync(
-        &config.volt_home()?,
-        &package.cacache_key(),
-        serde_json::to_string(&cas_file_map).into_diagnostic()?,
-    )
-    .into_diagnostic()?;
+    true
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::unpack_tarball;
+    use crate::core::store::{LinkStrategy, Linker};
+
+    use tar::{EntryType, Header};
+    use tempfile::tempdir;
+
+    use std::{fs, path::Path};
+
+    /// A single entry of a fixture tarball. Paths are written verbatim into the header so that
+    /// malicious archives (which `tar::Builder` refuses to create) can be built.
+    struct Fixture<'a> {
+        path: &'a str,
+        kind: EntryType,
+        mode: u32,
+        link: Option<&'a str>,
+        contents: &'a [u8],
+    }
+
+    impl<'a> Fixture<'a> {
+        fn file(path: &'a str, contents: &'a [u8]) -> Self {
+            Self {
+                path,
+                kind: EntryType::Regular,
+                mode: 0o644,
+                link: None,
+                contents,
+            }
+        }
+
+        fn executable(path: &'a str, contents: &'a [u8]) -> Self {
+            Self {
+                mode: 0o755,
+                ..Self::file(path, contents)
+            }
+        }
+
+        fn link(path: &'a str, kind: EntryType, target: &'a str) -> Self {
+            Self {
+                path,
+                kind,
+                mode: 0o777,
+                link: Some(target),
+                contents: b"",
+            }
+        }
+    }
+
+    fn tarball(entries: &[Fixture]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        for entry in entries {
+            let mut header = Header::new_old();
+
+            header.as_old_mut().name[..entry.path.len()].copy_from_slice(entry.path.as_bytes());
+
+            if let Some(link) = entry.link {
+                header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+            }
+
+            header.set_entry_type(entry.kind);
+            header.set_mode(entry.mode);
+            header.set_size(entry.contents.len() as u64);
+            header.set_cksum();
+
+            builder.append(&header, entry.contents).unwrap();
+        }
+
+        builder.into_inner().unwrap()
+    }
+
+    fn unpack(entries: &[Fixture], root: &Path) -> miette::Result<Vec<String>> {
+        let destination = root.join("node_modules").join("pkg");
+        let store = root.join("store");
+
+        let linker = Linker::new(LinkStrategy::Hardlink);
+
+        let index = unpack_tarball(tarball(entries), &destination, &store, &linker)?;
+
+        let mut files = index
+            .files
+            .into_iter()
+            .map(|file| file.path)
+            .collect::<Vec<_>>();
+        files.sort();
+
+        Ok(files)
+    }
+
+    #[test]
+    fn extracts_files_under_the_package_root() {
+        let dir = tempdir().unwrap();
+
+        let files = unpack(
+            &[
+                Fixture::file("package/package.json", b"{}"),
+                Fixture::file("package/lib/index.js", b"module.exports = 1;"),
+            ],
+            dir.path(),
+        )
+        .unwrap();
+
+        assert_eq!(files, vec!["lib/index.js", "package.json"]);
+        assert_eq!(
+            fs::read(dir.path().join("node_modules/pkg/lib/index.js")).unwrap(),
+            b"module.exports = 1;"
+        );
+    }
+
+    #[test]
+    fn accepts_any_top_level_directory() {
+        let dir = tempdir().unwrap();
+
+        let files = unpack(
+            &[
+                Fixture::file("node/package.json", b"{}"),
+                Fixture::file("./node/index.js", b""),
+            ],
+            dir.path(),
+        )
+        .unwrap();
+
+        assert_eq!(files, vec!["index.js", "package.json"]);
+    }
+
+    #[test]
+    fn rejects_parent_directory_traversal() {
+        let dir = tempdir().unwrap();
+
+        let result = unpack(
+            &[Fixture::file("package/../../../.bashrc", b"rm -rf ~")],
+            dir.path(),
+        );
+
+        assert!(result.is_err());
+        assert!(!dir.path().join(".bashrc").exists());
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        let dir = tempdir().unwrap();
+
+        let evil = dir.path().join("evil");
+        let evil = evil.to_str().unwrap();
+
+        assert!(unpack(&[Fixture::file(evil, b"")], dir.path()).is_err());
+        assert!(!Path::new(evil).exists());
+    }
+
+    #[test]
+    fn rejects_symlinks_escaping_the_package() {
+        let dir = tempdir().unwrap();
+
+        let result = unpack(
+            &[Fixture::link(
+                "package/lib/escape",
+                EntryType::Symlink,
+                "../../../../outside",
+            )],
+            dir.path(),
+        );
+
+        assert!(result.is_err());
+
+        let result = unpack(
+            &[Fixture::link(
+                "package/passwd",
+                EntryType::Symlink,
+                "/etc/passwd",
+            )],
+            dir.path(),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_entries_under_chained_symlinks() {
+        let dir = tempdir().unwrap();
+
+        let result = unpack(
+            &[
+                Fixture::link("package/a/b", EntryType::Symlink, ".."),
+                Fixture::link("package/a/b/c", EntryType::Symlink, ".."),
+                Fixture::file("package/a/b/c/x", b"escaped"),
+            ],
+            dir.path(),
+        );
+
+        assert!(result.is_err());
+        assert!(!dir.path().join("node_modules/c").exists());
+        assert!(!dir.path().join("node_modules/x").exists());
+        assert!(!dir.path().join("node_modules/pkg/c").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_resolving_through_other_symlinks() {
+        for fixtures in [
+            [
+                Fixture::link("package/a/b", EntryType::Symlink, ".."),
+                Fixture::link("package/x", EntryType::Symlink, "a/b/.."),
+            ],
+            // the link passed through can come later in the archive
+            [
+                Fixture::link("package/x", EntryType::Symlink, "a/b/.."),
+                Fixture::link("package/a/b", EntryType::Symlink, ".."),
+            ],
+        ] {
+            let dir = tempdir().unwrap();
+
+            let result = unpack(&fixtures, dir.path());
+
+            assert!(result.is_err());
+            assert!(fs::symlink_metadata(dir.path().join("node_modules/pkg/x")).is_err());
+        }
+
+        let dir = tempdir().unwrap();
+
+        unpack(
+            &[
+                Fixture::link("package/a/b", EntryType::Symlink, "c"),
+                Fixture::file("package/a/c/index.js", b"hello"),
+                Fixture::link("package/x", EntryType::Symlink, "a/b/../index.js"),
+            ],
+            dir.path(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn rejects_hardlinks_outside_of_the_archive() {
+        let dir = tempdir().unwrap();
+
+        let result = unpack(
+            &[Fixture::link(
+                "package/passwd",
+                EntryType::Link,
+                "package/../../etc/passwd",
+            )],
+            dir.path(),
+        );
+
+        assert!(result.is_err());
+
+        let result = unpack(
+            &[Fixture::link(
+                "package/a.js",
+                EntryType::Link,
+                "package/b.js",
+            )],
+            dir.path(),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn recreates_links_inside_the_package() {
+        let dir = tempdir().unwrap();
+
+        let files = unpack(
+            &[
+                Fixture::file("package/index.js", b"hello"),
+                Fixture::link("package/copy.js", EntryType::Link, "package/index.js"),
+                Fixture::link("package/lib/alias.js", EntryType::Symlink, "../index.js"),
+            ],
+            dir.path(),
+        )
+        .unwrap();
+
+        assert_eq!(files, vec!["copy.js", "index.js"]);
+
+        let package = dir.path().join("node_modules/pkg");
+
+        assert_eq!(fs::read(package.join("copy.js")).unwrap(), b"hello");
+
+        #[cfg(unix)]
+        assert_eq!(fs::read(package.join("lib/alias.js")).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn skips_special_entries() {
+        let dir = tempdir().unwrap();
+
+        let files = unpack(
+            &[
+                Fixture::file("package/index.js", b""),
+                Fixture {
+                    kind: EntryType::Fifo,
+                    ..Fixture::file("package/fifo", b"")
+                },
+                Fixture {
+                    kind: EntryType::Char,
+                    ..Fixture::file("package/tty", b"")
+                },
+            ],
+            dir.path(),
+        )
+        .unwrap();
+
+        assert_eq!(files, vec!["index.js"]);
+        assert!(!dir.path().join("node_modules/pkg/fifo").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn preserves_the_executable_bit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+
+        unpack(
+            &[
+                Fixture::executable("package/bin/cli.js", b"#!/usr/bin/env node"),
+                Fixture::file("package/index.js", b""),
+            ],
+            dir.path(),
+        )
+        .unwrap();
+
+        let package = dir.path().join("node_modules/pkg");
+
+        let mode = |path: &str| {
+            fs::metadata(package.join(path))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o777
+        };
+
+        assert_eq!(mode("bin/cli.js"), 0o755);
+        assert_eq!(mode("index.js"), 0o644);
+    }
 }
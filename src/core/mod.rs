@@ -16,8 +16,14 @@
 
 #[macro_use]
 pub mod utils;
+pub mod cache;
 pub mod classes;
+pub mod http;
 pub mod io;
 pub mod model;
 pub mod net;
+pub mod progress;
 pub mod prompt;
+pub mod store;
+#[cfg(test)]
+pub mod test_server;
@@ -14,52 +14,29 @@
     limitations under the License.
 */
 
-use crate::core::utils::package::NpmPackage;
-
-use isahc::{http::StatusCode, AsyncReadResponseExt};
-use thiserror::Error;
-
-use std::io;
-
-#[derive(Error, Debug)]
-pub enum GetPackageError {
-    #[error("network request failed with registry")]
-    Request(isahc::Error),
-    #[error("unable to read network response")]
-    IO(io::Error),
-    #[error("unable to deserialize network response: {0:?}")]
-    Json(serde_json::Error),
-}
+use crate::{
+    cli::VoltConfig,
+    core::{http::HttpClient, net::fetch_packument, utils::package::NpmPackage},
+};
 
 #[allow(dead_code)]
-/// Request a package from `registry.yarnpkg.com`
+/// Request the full packument of a package from the configured registry
 ///
-/// Uses `chttp` async implementation to send a `get` request for the package
+/// Goes through the metadata cache, so the registry is only asked again once the cached
+/// packument is stale (and then only with a conditional request)
 /// ## Arguments
-/// * `name` - Name of the package to request from `registry.yarnpkg.com`
+/// * `name` - Name of the package to request from the registry
 /// ## Examples
 /// ```
 /// // Await an async response
-/// get_package("react").await;
+/// get_package(&client, &config, "react").await;
 /// ```
 /// ## Returns
-/// * `Result<Option<Package>, GetPackageError>`
-pub async fn get_package(name: &str) -> Result<Option<NpmPackage>, GetPackageError> {
-    let mut resp = isahc::get_async(format!("http://registry.yarnpkg.com/{}", name))
-        .await
-        .map_err(GetPackageError::Request)?;
-
-    if !resp.status().is_success() {
-        match resp.status() {
-            StatusCode::NOT_FOUND => {}
-            StatusCode::INTERNAL_SERVER_ERROR => {}
-            StatusCode::METHOD_NOT_ALLOWED => {}
-            _ => {}
-        }
-    }
-
-    let body_string = resp.text().await.map_err(GetPackageError::IO)?;
-    let package: NpmPackage = serde_json::from_str(&body_string).map_err(GetPackageError::Json)?;
-
-    Ok(Some(package))
+/// * `Result<Option<NpmPackage>>`
+pub async fn get_package(
+    client: &HttpClient,
+    config: &VoltConfig,
+    name: &str,
+) -> miette::Result<Option<NpmPackage>> {
+    fetch_packument(client, config, name, None, true).await
 }
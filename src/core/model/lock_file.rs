@@ -15,115 +15,706 @@
 */
 
 use miette::Result;
+use node_semver::{Range, Version};
+use oro_node_semver::{Version as OroVersion, VersionReq};
 use serde::{Deserialize, Serialize};
-use speedy::{Readable, Writable};
-use thiserror::Error;
 
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{self, BufReader, Write},
-    path::Path,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
 };
 
-use crate::core::utils::voltapi::VoltPackage;
-
-#[derive(Error, Debug)]
-pub enum LockFileError {
-    #[error("unable to read lock file")]
-    _IO(io::Error),
-    #[error("unable to deserialize lock file")]
-    #[allow(dead_code)]
-    Decode(serde_json::Error),
-    // #[error("unable to serialize lock file")]
-    // Encode(serde_json::Error),
+use crate::core::utils::{
+    errors::VoltError,
+    voltapi::{VoltPackage, VoltResponse},
+};
+
+/// The most dependency paths `LockFile::paths` returns for a single root.
+pub const MAX_PATHS: usize = 1000;
+
+/// A package locked at several versions where a single one satisfies every range it's required
+/// with.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Duplicate {
+    pub name: String,
+    pub ranges: Vec<String>,  // every range the package is required with
+    pub kept: String,         // version that satisfies all of them
+    pub removed: Vec<String>, // versions that aren't needed anymore
 }
 
 /// The lock file is responsible for locking/pinning dependency versions in a given project.
-/// It stores a list of dependencies along with their resolved version, registry url, and sha1 checksum.
+/// It stores the dependencies requested by the project along with every package of their
+/// resolved (flattened) dependency trees.
 ///
 /// ## Examples
 ///
 /// ```
-/// // Load the lock file for the current project or create new lock file
-/// let mut lock_file = LockFile::load(lock_file_path)
-///     .unwrap_or_else(|| LockFile::new(lock_file_path));
+/// // Load the lock file for the current project, or start a new one
+/// let mut lock_file = LockFile::load(config.lockfile()?)?;
 ///
-/// // Add dependency
-/// lock_file.add(
-///     ("react".to_string(), "^1.0.0".to_string()),
-///     DependencyLock {
-///         name: "react".to_string(),
-///         version: "1.2.6".to_string(),
-///         tarball: String::new(),
-///         sha1: String::new(),
-///     }
-/// );
+/// // Add a resolved dependency and its tree
+/// lock_file.add(&response);
 ///
 /// // Save changes to disk
-/// lock_file.save().expect("Unable to save lock file");
+/// lock_file.save()?;
 /// ```
-#[derive(Clone, Debug, Writable, Readable, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct LockFile {
-    pub path: String,
-    #[speedy(skip)]
-    pub global: bool,
-    #[speedy(skip)]
-    pub dependencies: HashMap<String, VoltPackage>,
+    #[serde(skip)]
+    pub path: PathBuf,
+    pub dependencies: BTreeMap<String, String>, // dependencies requested by the project <name, version>
+    pub packages: BTreeMap<String, VoltPackage>, // every package of the tree <name@version, data>
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, String>, // versions replacing others wherever they're required <name@version, version>
 }
 
 impl LockFile {
     /// Creates a new instance of a lock file with a path it should be saved at.
     /// It can be saved to the file by calling [`Self::save()`].
-    pub fn _new<P: AsRef<Path>>(path: P, global: bool) -> Self {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
         Self {
-            path: path.as_ref().to_str().unwrap().to_string(),
-            global,
-            dependencies: HashMap::with_capacity(1), // We will be installing at least 1 dependency
+            path: path.as_ref().to_path_buf(),
+            ..Default::default()
         }
     }
 
-    pub fn _add(_package: VoltPackage) {}
-
-    /// Loads a lock file from the given path.
-    pub fn _load<P: AsRef<Path>>(path: P, global: bool) -> Result<Self, LockFileError> {
+    /// Loads a lock file from the given path, or creates a new one if it doesn't exist yet.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
 
-        let dependencies = if path.exists() {
-            let f = File::open(path).map_err(LockFileError::_IO)?;
-            let reader = BufReader::new(f);
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::new(path)),
+            Err(e) => {
+                return Err(VoltError::ReadFileError {
+                    source: e,
+                    name: path.display().to_string(),
+                }
+                .into())
+            }
+        };
+
+        let mut lock_file: Self =
+            serde_json::from_slice(&data).map_err(|e| VoltError::LockFileParseError {
+                source: e,
+                path: path.display().to_string(),
+            })?;
+
+        lock_file.path = path.to_path_buf();
+
+        Ok(lock_file)
+    }
+
+    /// Saves the lock file to the same path it was opened from, as pretty printed json with
+    /// sorted keys so that it diffs well.
+    pub fn save(&self) -> Result<()> {
+        let write_error = |e| VoltError::WriteFileError {
+            source: e,
+            name: self.path.display().to_string(),
+        };
+
+        // going through a `Value` sorts the keys of the maps inside of packages
+        let value = serde_json::to_value(self).map_err(|e| write_error(e.into()))?;
+        let mut contents =
+            serde_json::to_string_pretty(&value).map_err(|e| write_error(e.into()))?;
+
+        contents.push('\n');
+
+        // leave the file (and its modification time) alone when nothing changed
+        if fs::read(&self.path).map_or(false, |existing| existing == contents.as_bytes()) {
+            return Ok(());
+        }
+
+        fs::write(&self.path, contents).map_err(write_error)?;
+
+        Ok(())
+    }
+
+    /// Record a dependency of the project along with its resolved tree.
+    pub fn add(&mut self, response: &VoltResponse) {
+        self.dependencies
+            .insert(response.name.clone(), response.version.clone());
+
+        // trees resolved from the lock file have their overrides applied, the locked packages
+        // keep the dependencies they declare
+        for (key, package) in &response.tree {
+            self.packages
+                .entry(key.clone())
+                .or_insert_with(|| package.clone());
+        }
+    }
+
+    /// Find the package that a dependency on `name@range` resolves to (the highest locked
+    /// version that satisfies it).
+    pub fn find(&self, name: &str, range: &str) -> Option<&VoltPackage> {
+        let range = Range::parse(range).ok();
+
+        self.packages
+            .values()
+            .filter(|package| package.name == name)
+            .filter_map(|package| Some((package.version.parse::<Version>().ok()?, package)))
+            .filter(|(version, _)| range.as_ref().map_or(true, |r| version.satisfies(r)))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, package)| package)
+    }
+
+    /// The package that `name@range`, a dependency of a locked package, is locked at. Trees
+    /// resolved by the registry record the exact version of every dependency, other ranges
+    /// resolve to the highest locked version that satisfies them. Overridden versions are
+    /// replaced by their override.
+    pub fn locked(&self, name: &str, range: &str) -> Option<&VoltPackage> {
+        let overridden = |version: &str| {
+            self.overrides
+                .get(&format!("{}@{}", name, version))
+                .and_then(|to| self.packages.get(&format!("{}@{}", name, to)))
+        };
+
+        // overridden versions are pruned from the lock file once nothing else requires them
+        if let Some(package) = overridden(range) {
+            return Some(package);
+        }
+
+        let package = self
+            .packages
+            .get(&format!("{}@{}", name, range))
+            .or_else(|| self.find(name, range))?;
+
+        Some(overridden(&package.version).unwrap_or(package))
+    }
+
+    /// `package` with its dependencies at the exact versions they're locked at, which is how
+    /// they get linked.
+    pub fn resolved(&self, package: &VoltPackage) -> VoltPackage {
+        let mut resolved = package.clone();
+
+        for dependencies in [
+            &mut resolved.dependencies,
+            &mut resolved.optional_dependencies,
+        ] {
+            for (name, range) in dependencies.iter_mut().flatten() {
+                if let Some(locked) = self.locked(name, range) {
+                    *range = locked.version.clone();
+                }
+            }
+        }
+
+        resolved
+    }
+
+    /// Every package that `name@version` depends on (transitively), including itself, with the
+    /// versions of their dependencies resolved.
+    pub fn tree(&self, name: &str, version: &str) -> HashMap<String, VoltPackage> {
+        let mut tree = HashMap::new();
+        let mut queue = vec![format!("{}@{}", name, version)];
+
+        while let Some(key) = queue.pop() {
+            let package = match self.packages.get(&key) {
+                Some(package) if !tree.contains_key(&key) => package,
+                _ => continue,
+            };
+
+            let dependencies = package
+                .dependencies
+                .iter()
+                .chain(package.optional_dependencies.iter())
+                .flatten();
+
+            for (name, range) in dependencies {
+                if let Some(dependency) = self.locked(name, range) {
+                    queue.push(format!("{}@{}", dependency.name, dependency.version));
+                }
+            }
+
+            tree.insert(key, self.resolved(package));
+        }
+
+        tree
+    }
 
-            if global {
-                LockFile::read_from_buffer(reader.buffer()).unwrap()
-            } else {
-                serde_json::from_reader(reader).unwrap()
+    /// The dependencies of `package` (optional ones included): their name, the range they're
+    /// required with and the locked package they resolve to.
+    pub fn dependencies_of<'a>(
+        &'a self,
+        package: &'a VoltPackage,
+    ) -> impl Iterator<Item = (&'a str, &'a str, Option<&'a VoltPackage>)> + 'a {
+        package
+            .dependencies
+            .iter()
+            .chain(package.optional_dependencies.iter())
+            .flatten()
+            .map(move |(name, range)| (name.as_str(), range.as_str(), self.locked(name, range)))
+    }
+
+    /// Every dependency path from `name@range` to the packages matched by `target`. Each step of
+    /// a path is a package along with the range it was required with, packages already on a path
+    /// are not walked again so cycles end there.
+    ///
+    /// The number of paths grows exponentially with the diamonds of the tree, so at most
+    /// `MAX_PATHS` of them are returned.
+    pub fn paths<'a>(
+        &'a self,
+        name: &str,
+        range: &'a str,
+        target: impl Fn(&VoltPackage) -> bool,
+    ) -> Vec<Vec<(&'a str, &'a VoltPackage)>> {
+        let key = |package: &VoltPackage| format!("{}@{}", package.name, package.version);
+
+        // walk the dependents of the targets first, so that the paths only go through packages
+        // that lead to them
+        let mut dependents: HashMap<String, Vec<&VoltPackage>> = HashMap::new();
+
+        for package in self.packages.values() {
+            for (_, _, dependency) in self.dependencies_of(package) {
+                if let Some(dependency) = dependency {
+                    dependents.entry(key(dependency)).or_default().push(package);
+                }
             }
-        } else {
-            LockFile {
-                path: path.to_str().unwrap().to_string(),
-                global,
-                dependencies: HashMap::new(),
+        }
+
+        let mut leads = HashSet::new();
+        let mut queue = self
+            .packages
+            .values()
+            .filter(|package| target(package))
+            .collect::<Vec<_>>();
+
+        while let Some(package) = queue.pop() {
+            if leads.insert(key(package)) {
+                queue.extend(dependents.get(&key(package)).into_iter().flatten());
             }
+        }
+
+        let mut paths = vec![];
+
+        // a dependency of the project is locked at the version recorded for it
+        let root = self
+            .dependencies
+            .get(name)
+            .and_then(|version| self.packages.get(&format!("{}@{}", name, version)))
+            .or_else(|| self.locked(name, range));
+
+        let root = match root {
+            Some(root) if leads.contains(&key(root)) => root,
+            _ => return paths,
         };
 
-        Ok(dependencies)
+        // depth first, each entry is a path that still has to be extended
+        let mut stack = vec![vec![(range, root)]];
+        // cycles can still lead the walk into dead ends, so bound it as well
+        let mut budget = MAX_PATHS * 64;
+
+        while let Some(path) = stack.pop() {
+            if paths.len() == MAX_PATHS || budget == 0 {
+                break;
+            }
+
+            budget -= 1;
+
+            let (_, last) = path[path.len() - 1];
+
+            if target(last) {
+                paths.push(path.clone());
+            }
+
+            let mut next = self
+                .dependencies_of(last)
+                .filter_map(|(_, range, dependency)| Some((range, dependency?)))
+                .filter(|(_, dependency)| leads.contains(&key(dependency)))
+                .filter(|(_, dependency)| {
+                    !path
+                        .iter()
+                        .any(|(_, package)| key(package) == key(dependency))
+                })
+                .collect::<Vec<_>>();
+
+            // walk the dependencies in order, so the paths kept past the limit are stable
+            next.sort_by_key(|(_, dependency)| std::cmp::Reverse(key(dependency)));
+
+            for step in next {
+                let mut next = path.clone();
+                next.push(step);
+                stack.push(next);
+            }
+        }
+
+        paths.sort_by_key(|path| {
+            path.iter()
+                .map(|(_, package)| key(package))
+                .collect::<Vec<_>>()
+        });
+
+        paths
     }
 
-    // Saves a lock file dumping pretty, formatted json
-    // pub fn save_pretty(&self) -> Result<(), LockFileError> {
-    //     let lock_file = File::create(&self.path).map_err(LockFileError::IO)?;
-    //     let writer = BufWriter::new(lock_file);
-    //     serde_json::to_writer_pretty(writer, &self.dependencies).map_err(LockFileError::Encode)
-    // }
+    /// The packages locked at several versions that could be locked at a single one, the highest
+    /// locked version that satisfies the intersection of their ranges. `roots` are the
+    /// dependencies of the project along with their range.
+    pub fn duplicates(&self, roots: &[(String, String)]) -> Vec<Duplicate> {
+        // <name, ranges>
+        let mut ranges: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
 
-    // Saves a lock file to the same path it was opened from.
-    pub fn _save(&self) -> Result<()> {
-        let mut lock_file = File::create(&self.path).unwrap();
+        for (name, range) in roots {
+            ranges.entry(name).or_default().push(range);
+        }
 
-        lock_file
-            .write_all(&self.dependencies.write_to_vec().unwrap())
-            .unwrap();
+        for package in self.packages.values() {
+            for (name, range, _) in self.dependencies_of(package) {
+                ranges.entry(name).or_default().push(range);
+            }
+        }
 
-        Ok(())
+        // <name, versions>
+        let mut versions: BTreeMap<&str, Vec<OroVersion>> = BTreeMap::new();
+
+        for package in self.packages.values() {
+            if let Ok(version) = OroVersion::parse(&package.version) {
+                versions.entry(&package.name).or_default().push(version);
+            }
+        }
+
+        let mut duplicates = vec![];
+
+        for (name, mut versions) in versions {
+            if versions.len() < 2 {
+                continue;
+            }
+
+            let mut required = ranges.remove(name).unwrap_or_default();
+
+            required.sort_unstable();
+            required.dedup();
+
+            // ranges that aren't semver (tags, urls) can't be intersected
+            let intersection = required
+                .iter()
+                .map(VersionReq::parse)
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .ok()
+                .and_then(|required| {
+                    required
+                        .into_iter()
+                        .try_fold(VersionReq::any(), |acc, range| acc.intersect(&range))
+                });
+
+            let intersection = match intersection {
+                Some(intersection) => intersection,
+                None => continue,
+            };
+
+            versions.sort();
+
+            let kept = match versions
+                .iter()
+                .rev()
+                .find(|version| intersection.satisfies(version))
+            {
+                Some(kept) => kept.clone(),
+                None => continue,
+            };
+
+            duplicates.push(Duplicate {
+                name: name.to_string(),
+                ranges: required.iter().map(ToString::to_string).collect(),
+                kept: kept.to_string(),
+                removed: versions
+                    .iter()
+                    .filter(|version| **version != kept)
+                    .map(ToString::to_string)
+                    .collect(),
+            });
+        }
+
+        duplicates
+    }
+
+    /// Lock each of the `duplicates` at the version it's kept at, then remove the packages that
+    /// nothing depends on anymore. Returns the removed packages.
+    pub fn dedupe(&mut self, duplicates: &[Duplicate]) -> Vec<VoltPackage> {
+        let mut removed = vec![];
+
+        for duplicate in duplicates {
+            for version in &duplicate.removed {
+                let key = format!("{}@{}", duplicate.name, version);
+
+                if let Some(package) = self.packages.remove(&key) {
+                    removed.push(package);
+                }
+            }
+
+            if let Some(version) = self.dependencies.get_mut(&duplicate.name) {
+                *version = duplicate.kept.clone();
+            }
+        }
+
+        // the dependencies of the removed versions can be left without dependents
+        removed.extend(self.prune());
+
+        removed
+    }
+
+    /// Remove the packages that none of the dependencies of the project lead to, returning them.
+    pub fn prune(&mut self) -> Vec<VoltPackage> {
+        let reachable = self
+            .dependencies
+            .iter()
+            .flat_map(|(name, version)| self.tree(name, version).into_keys())
+            .collect::<HashSet<_>>();
+
+        let unreachable = self
+            .packages
+            .keys()
+            .filter(|key| !reachable.contains(*key))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let removed = unreachable
+            .into_iter()
+            .filter_map(|key| self.packages.remove(&key))
+            .collect();
+
+        // overrides to versions that aren't locked anymore don't replace anything
+        let packages = &self.packages;
+
+        self.overrides.retain(|key, version| {
+            let name = key.rsplit_once('@').map_or(key.as_str(), |(name, _)| name);
+
+            packages.contains_key(&format!("{}@{}", name, version))
+        });
+
+        removed
+    }
+
+    /// Resolve a dependency of the project from the lock file, if the locked version is
+    /// accepted by `accepts`.
+    pub fn resolve(&self, name: &str, accepts: impl Fn(&str) -> bool) -> Option<VoltResponse> {
+        let version = self.dependencies.get(name)?;
+
+        if !accepts(version) {
+            return None;
+        }
+
+        Some(VoltResponse {
+            name: name.to_string(),
+            version: version.clone(),
+            versions: vec![version.clone()],
+            tree: self.tree(name, version),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LockFile, MAX_PATHS};
+    use crate::core::utils::voltapi::{VoltPackage, VoltResponse};
+
+    use std::collections::HashMap;
+
+    fn package(name: &str, version: &str, dependencies: &[(&str, &str)]) -> VoltPackage {
+        VoltPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            optional: false,
+            integrity: String::new(),
+            tarball: String::new(),
+            bin: None,
+            scripts: None,
+            dependencies: Some(
+                dependencies
+                    .iter()
+                    .map(|(name, range)| (name.to_string(), range.to_string()))
+                    .collect(),
+            ),
+            peer_dependencies: None,
+            peer_dependencies_meta: None,
+            optional_dependencies: None,
+            overrides: None,
+            engines: None,
+            os: None,
+            cpu: None,
+        }
+    }
+
+    #[test]
+    fn resolves_dependencies_from_the_lock_file() {
+        let mut lock_file = LockFile::default();
+
+        let tree: HashMap<String, VoltPackage> = [
+            package("a", "1.0.0", &[("b", "^1.0.0")]),
+            package("b", "1.2.0", &[]),
+            package("b", "2.0.0", &[]),
+            package("c", "1.0.0", &[("b", "^2.0.0")]),
+        ]
+        .into_iter()
+        .map(|package| (format!("{}@{}", package.name, package.version), package))
+        .collect();
+
+        lock_file.add(&VoltResponse {
+            name: "a".to_string(),
+            version: "1.0.0".to_string(),
+            versions: vec![],
+            tree,
+        });
+
+        let response = lock_file.resolve("a", |_| true).unwrap();
+
+        let mut keys = response.tree.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+
+        assert_eq!(keys, vec!["a@1.0.0", "b@1.2.0"]);
+        assert!(lock_file
+            .resolve("a", |version| version == "2.0.0")
+            .is_none());
+        assert!(lock_file.resolve("c", |_| true).is_none());
+    }
+
+    #[test]
+    fn finds_every_path_to_a_package() {
+        let packages = [
+            package("a", "1.0.0", &[("b", "^1.0.0"), ("c", "^1.0.0")]),
+            package("b", "1.0.0", &[("d", "^1.0.0"), ("a", "^1.0.0")]),
+            package("c", "1.0.0", &[("d", "^2.0.0")]),
+            package("d", "1.0.0", &[]),
+            package("d", "2.0.0", &[]),
+            package("e", "1.0.0", &[]),
+        ];
+
+        let lock_file = LockFile {
+            packages: packages
+                .into_iter()
+                .map(|package| (format!("{}@{}", package.name, package.version), package))
+                .collect(),
+            ..Default::default()
+        };
+
+        let paths = lock_file
+            .paths("a", "^1.0.0", |package| package.name == "d")
+            .into_iter()
+            .map(|path| {
+                path.into_iter()
+                    .map(|(range, package)| {
+                        format!("{}@{} ({})", package.name, package.version, range)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            paths,
+            vec![
+                vec!["a@1.0.0 (^1.0.0)", "b@1.0.0 (^1.0.0)", "d@1.0.0 (^1.0.0)"],
+                vec!["a@1.0.0 (^1.0.0)", "c@1.0.0 (^1.0.0)", "d@2.0.0 (^2.0.0)"],
+            ]
+        );
+        assert!(lock_file
+            .paths("a", "^1.0.0", |package| package.name == "e")
+            .is_empty());
+    }
+
+    #[test]
+    fn bounds_the_paths_of_a_chain_of_diamonds() {
+        // every level doubles the number of paths to the bottom of the chain
+        let mut packages = vec![];
+
+        for level in 0..32 {
+            let next = format!("top{}", level + 1);
+            let dependencies = [
+                (format!("left{}", level), "1.0.0".to_string()),
+                (format!("right{}", level), "1.0.0".to_string()),
+            ];
+
+            packages.push(package(
+                &format!("top{}", level),
+                "1.0.0",
+                &dependencies
+                    .iter()
+                    .map(|(name, range)| (name.as_str(), range.as_str()))
+                    .collect::<Vec<_>>(),
+            ));
+
+            for side in ["left", "right"] {
+                packages.push(package(
+                    &format!("{}{}", side, level),
+                    "1.0.0",
+                    &[(next.as_str(), "1.0.0")],
+                ));
+            }
+        }
+
+        packages.push(package("top32", "1.0.0", &[]));
+
+        let lock_file = LockFile {
+            packages: packages
+                .into_iter()
+                .map(|package| (format!("{}@{}", package.name, package.version), package))
+                .collect(),
+            ..Default::default()
+        };
+
+        let paths = lock_file.paths("top0", "1.0.0", |package| package.name == "top32");
+
+        assert_eq!(paths.len(), MAX_PATHS);
+        assert!(paths.iter().all(|path| path.len() == 65));
+    }
+
+    #[test]
+    fn dedupes_packages_satisfying_every_range() {
+        let packages = [
+            package("a", "1.0.0", &[("d", "^1.0.0")]),
+            package("b", "1.0.0", &[("d", "~1.1.0")]),
+            package("c", "1.0.0", &[("e", "^1.0.0")]),
+            package("d", "1.1.2", &[]),
+            package("d", "1.2.0", &[("f", "^1.0.0")]),
+            package("e", "1.0.0", &[]),
+            package("e", "2.0.0", &[]),
+            package("f", "1.0.0", &[]),
+        ];
+
+        let mut lock_file = LockFile {
+            dependencies: [
+                ("a", "1.0.0"),
+                ("b", "1.0.0"),
+                ("c", "1.0.0"),
+                ("e", "2.0.0"),
+            ]
+            .into_iter()
+            .map(|(name, version)| (name.to_string(), version.to_string()))
+            .collect(),
+            packages: packages
+                .into_iter()
+                .map(|package| (format!("{}@{}", package.name, package.version), package))
+                .collect(),
+            ..Default::default()
+        };
+
+        let roots = [
+            ("a", "^1.0.0"),
+            ("b", "^1.0.0"),
+            ("c", "^1.0.0"),
+            ("e", "^2.0.0"),
+        ]
+        .into_iter()
+        .map(|(name, range)| (name.to_string(), range.to_string()))
+        .collect::<Vec<_>>();
+
+        let duplicates = lock_file.duplicates(&roots);
+
+        // `e` is required with ranges that don't intersect
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].name, "d");
+        assert_eq!(duplicates[0].kept, "1.1.2");
+        assert_eq!(duplicates[0].removed, vec!["1.2.0"]);
+
+        let mut removed = lock_file
+            .dedupe(&duplicates)
+            .into_iter()
+            .map(|package| format!("{}@{}", package.name, package.version))
+            .collect::<Vec<_>>();
+
+        removed.sort();
+
+        assert_eq!(removed, vec!["d@1.2.0", "f@1.0.0"]);
+        assert!(lock_file.duplicates(&roots).is_empty());
     }
 }
@@ -1,24 +1,40 @@
-use std::time::Instant;
+use std::{collections::HashMap, path::Path, time::Instant};
 
-use crate::core::{
-    utils::constants::MAX_RETRIES,
-    utils::errors::VoltError,
-    utils::voltapi::{VoltPackage, VoltResponse},
-    utils::State,
+use crate::{
+    cli::VoltConfig,
+    core::{
+        cache::{metadata_key, packument_key, CachePolicy, CachedMetadata},
+        http::{HttpClient, HttpResponse},
+        model::lock_file::LockFile,
+        utils::constants::VOLT_REGISTRY,
+        utils::errors::VoltError,
+        utils::voltapi::{VoltPackage, VoltResponse},
+        utils::State,
+    },
 };
 
 use colored::Colorize;
 use futures_util::{stream::FuturesUnordered, StreamExt};
 use indicatif::ProgressBar;
-use isahc::AsyncReadResponseExt;
 use miette::{IntoDiagnostic, Result};
-use package_spec::PackageSpec;
-use reqwest::StatusCode;
+use node_semver::{Range, Version};
+use package_spec::{PackageSpec, VersionSpec};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, WWW_AUTHENTICATE},
+    StatusCode,
+};
+use serde::{
+    de::{DeserializeOwned, IgnoredAny},
+    Deserialize,
+};
 use speedy::Readable;
 
 pub async fn get_volt_response_multi(
     packages: &[PackageSpec],
     progress_bar: &ProgressBar,
+    config: &VoltConfig,
+    lock_file: &LockFile,
+    client: &HttpClient,
 ) -> Vec<Result<VoltResponse>> {
     packages
         .iter()
@@ -36,107 +52,402 @@ pub async fn get_volt_response_multi(
                 progress_bar.set_message(format!("{}@{}", name, version.truecolor(125, 125, 125)));
             }
 
-            get_volt_response(spec)
+            get_volt_response(spec, config, lock_file, client)
         })
         .collect::<FuturesUnordered<_>>()
         .collect::<Vec<Result<VoltResponse>>>()
         .await
 }
 
-// Get response from volt CDN
-pub async fn get_volt_response(package_spec: &PackageSpec) -> Result<VoltResponse> {
-    // number of retries
-    let mut retries = 0;
+/// Whether `version` can be used for the `requested` version of a package without asking the
+/// registry (tags other than `latest` can only be resolved by the registry).
+pub fn satisfies_spec(requested: Option<&VersionSpec>, version: &str) -> bool {
+    match requested {
+        None => true,
+        Some(VersionSpec::Tag(tag)) => tag == "latest",
+        Some(VersionSpec::Version(requested)) => requested.to_string() == version,
+        Some(VersionSpec::Range(range)) => {
+            match (Range::parse(range.to_string()), Version::parse(version)) {
+                (Ok(range), Ok(version)) => version.satisfies(&range),
+                _ => false,
+            }
+        }
+    }
+}
 
+/// Metadata of `package_spec` cached from the volt CDN. Specs used to be displayed with the scope
+/// of scoped packages repeated and no `@` before the requested version, e.g. `react18.0.0`,
+/// the metadata cached under those keys is still used.
+fn read_cached_response(store: &Path, package_spec: &PackageSpec) -> Option<CachedMetadata> {
+    CachedMetadata::read(
+        store,
+        &metadata_key(VOLT_REGISTRY, &package_spec.to_string()),
+    )
+    .or_else(|| match package_spec {
+        PackageSpec::Npm {
+            scope,
+            name,
+            requested,
+        } => {
+            let legacy = format!(
+                "{}{}{}",
+                scope
+                    .as_ref()
+                    .map(|scope| format!("@{}/", scope))
+                    .unwrap_or_default(),
+                name,
+                requested
+                    .as_ref()
+                    .map(|requested| requested.to_string())
+                    .unwrap_or_default()
+            );
+
+            CachedMetadata::read(store, &metadata_key(VOLT_REGISTRY, &legacy))
+        }
+        _ => None,
+    })
+}
+
+// Get response from volt CDN
+pub async fn get_volt_response(
+    package_spec: &PackageSpec,
+    config: &VoltConfig,
+    lock_file: &LockFile,
+    client: &HttpClient,
+) -> Result<VoltResponse> {
     // we know that PackageSpec is of type npm (we filtered the non-npm ones out)
 
-    if let PackageSpec::Npm { name, .. } = package_spec {
-        // loop until MAX_RETRIES reached.
-        loop {
-            // get a response
+    if let PackageSpec::Npm {
+        name, requested, ..
+    } = package_spec
+    {
+        let url = format!("{}/{}.sp", VOLT_REGISTRY, &package_spec);
+
+        let store = config.volt_home()?;
+        let key = metadata_key(VOLT_REGISTRY, &package_spec.to_string());
+        let cached = read_cached_response(&store, package_spec);
+
+        let decode = |data: &[u8]| -> Result<VoltResponse> {
             let mut response =
-                isahc::get_async(format!("http://registry.voltpkg.com/{}.sp", &package_spec))
-                    .await
-                    .map_err(VoltError::NetworkError)?;
+                VoltResponse::read_from_buffer(data).map_err(|_| VoltError::_DeserializeError)?;
 
-            // check the status of the response
-            match response.status() {
-                // 200 (OK)
-                StatusCode::OK => {
-                    let mut response: VoltResponse =
-                        VoltResponse::read_from_buffer(&response.bytes().await.unwrap()).unwrap();
+            response.name = name.to_string();
 
-                    response.name = name.to_string();
+            Ok(response)
+        };
 
-                    return Ok(response);
-                }
-                // 429 (TOO_MANY_REQUESTS)
-                StatusCode::TOO_MANY_REQUESTS => {
-                    return Err(VoltError::TooManyRequests {
-                        url: format!("http://registry.voltpkg.com/{}.sp", &package_spec),
-                    }
-                    .into());
-                }
-                // 400 (BAD_REQUEST)
-                StatusCode::BAD_REQUEST => {
-                    return Err(VoltError::BadRequest {
-                        url: format!("http://registry.voltpkg.com/{}.sp", &package_spec),
-                    }
-                    .into());
-                }
-                // 404 (NOT_FOUND)
-                StatusCode::NOT_FOUND if retries == MAX_RETRIES => {
-                    return Err(VoltError::PackageNotFound {
-                        url: format!("http://registry.voltpkg.com/{}.sp", &package_spec),
-                        package_name: package_spec.to_string(),
-                    }
-                    .into());
-                }
-                // Other Errors
-                _ => {
-                    if retries == MAX_RETRIES {
-                        return Err(VoltError::NetworkUnknownError {
-                            url: format!("http://registry.voltpkg.com/{}.sp", name),
-                            package_name: package_spec.to_string(),
-                            code: response.status().as_str().to_string(),
-                        }
-                        .into());
-                    }
+        if config.prefer_offline() {
+            if let Some(response) =
+                lock_file.resolve(name, |version| satisfies_spec(requested.as_ref(), version))
+            {
+                return Ok(response);
+            }
+
+            if let Some(cached) = &cached {
+                return decode(&cached.data);
+            }
+
+            if config.offline() {
+                return Err(VoltError::OfflineMetadataMissing {
+                    spec: package_spec.to_string(),
                 }
+                .into());
+            }
+        }
+
+        if let Some(cached) = &cached {
+            if cached.policy.is_fresh(&url) {
+                return decode(&cached.data);
+            }
+        }
+
+        let response = client
+            .get_with_headers(
+                &url,
+                cached
+                    .as_ref()
+                    .map(|cached| cached.policy.conditional_headers(&url))
+                    .unwrap_or_default(),
+            )
+            .await?;
+
+        // check the status of the response
+        match response.status {
+            // 200 (OK)
+            StatusCode::OK => {
+                let policy = CachePolicy::from_headers(&url, &response.headers);
+
+                let volt_response = decode(&response.body)?;
+
+                CachedMetadata::write(&store, &key, &policy, &response.body)?;
+
+                Ok(volt_response)
             }
+            // 304 (NOT_MODIFIED), the cached response is still up to date
+            StatusCode::NOT_MODIFIED if cached.is_some() => {
+                let cached = cached.unwrap();
+                let policy = cached.policy.revalidated(&response.headers);
+
+                CachedMetadata::write(&store, &key, &policy, &cached.data)?;
 
-            retries += 1;
+                decode(&cached.data)
+            }
+            // 429 (TOO_MANY_REQUESTS)
+            StatusCode::TOO_MANY_REQUESTS => Err(VoltError::TooManyRequests { url }.into()),
+            // 400 (BAD_REQUEST)
+            StatusCode::BAD_REQUEST => Err(VoltError::BadRequest { url }.into()),
+            // 404 (NOT_FOUND)
+            StatusCode::NOT_FOUND => Err(VoltError::PackageNotFound {
+                url,
+                package_name: package_spec.to_string(),
+            }
+            .into()),
+            // Other Errors
+            status => Err(VoltError::NetworkUnknownError {
+                url,
+                package_name: package_spec.to_string(),
+                code: status.as_str().to_string(),
+            }
+            .into()),
         }
     } else {
         panic!("Volt does not support non-npm package specifications yet.");
     }
 }
 
+/// Headers authenticating a request to `url` with the configured token, only sent to the
+/// registry itself.
+pub fn auth_headers(config: &VoltConfig, url: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    if let Some(token) = config.token() {
+        if url.starts_with(&format!("{}/", config.registry())) {
+            if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", token)) {
+                headers.insert(AUTHORIZATION, value);
+            }
+        }
+    }
+
+    headers
+}
+
+/// Headers of a request changing a package on the registry: its authentication and the
+/// one-time password of accounts with two-factor authentication.
+pub fn write_headers(config: &VoltConfig, url: &str, otp: Option<&str>) -> Result<HeaderMap> {
+    let mut headers = auth_headers(config, url);
+
+    if let Some(otp) = otp {
+        let value = HeaderValue::from_str(otp).map_err(|_| VoltError::InvalidOtp {
+            otp: otp.to_string(),
+        })?;
+
+        headers.insert("npm-otp", value);
+    }
+
+    Ok(headers)
+}
+
+/// Turn the answer of the registry to a request changing a package into an error if it refused
+/// the change.
+pub fn check_write(method: &str, url: &str, response: &HttpResponse) -> Result<()> {
+    if response.status.is_success() {
+        return Ok(());
+    }
+
+    let reason = serde_json::from_slice::<serde_json::Value>(&response.body)
+        .ok()
+        .and_then(|body| {
+            ["error", "reason", "message"]
+                .iter()
+                .find_map(|field| body[*field].as_str().map(|reason| reason.to_string()))
+        })
+        .unwrap_or_else(|| {
+            response
+                .status
+                .canonical_reason()
+                .unwrap_or("unknown error")
+                .to_string()
+        });
+
+    let asks_otp = response
+        .headers
+        .get(WWW_AUTHENTICATE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| value.to_ascii_lowercase().contains("otp"))
+        || reason.to_ascii_lowercase().contains("one-time pass");
+
+    if response.status == StatusCode::UNAUTHORIZED && asks_otp {
+        return Err(VoltError::OtpRequired {
+            url: url.to_string(),
+        }
+        .into());
+    }
+
+    Err(VoltError::RegistryRejected {
+        method: method.to_string(),
+        url: url.to_string(),
+        code: response.status.as_str().to_string(),
+        reason,
+    }
+    .into())
+}
+
+/// `Accept` header asking for an abbreviated packument, which only contains what's needed to
+/// install a package.
+pub const ABBREVIATED_PACKUMENT: &str =
+    "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*";
+
+/// Whether the cached packument `data` has a version for `requested`, so that it can be used
+/// without asking the registry.
+fn packument_satisfies(data: &[u8], requested: Option<&VersionSpec>) -> bool {
+    #[derive(Deserialize)]
+    struct Versions {
+        #[serde(rename = "dist-tags", default)]
+        dist_tags: HashMap<String, String>,
+        #[serde(default)]
+        versions: HashMap<String, IgnoredAny>,
+    }
+
+    let packument = match serde_json::from_slice::<Versions>(data) {
+        Ok(packument) => packument,
+        Err(_) => return false,
+    };
+
+    match requested {
+        Some(VersionSpec::Tag(tag)) => packument.dist_tags.contains_key(tag),
+        // like npm, ranges only pick prereleases that were asked for by version
+        Some(VersionSpec::Range(_)) => packument
+            .versions
+            .keys()
+            .filter(|version| !version.contains('-'))
+            .any(|version| satisfies_spec(requested, version)),
+        _ => packument
+            .versions
+            .keys()
+            .any(|version| satisfies_spec(requested, version)),
+    }
+}
+
+/// Fetch the packument of `name` from the configured registry, going through the metadata cache.
+///
+/// Abbreviated packuments are requested unless `full` is set. With `--prefer-offline`, a cached
+/// packument is only revalidated if none of its versions is `requested`. Returns `None` if the
+/// package doesn't exist.
+pub async fn fetch_packument<T: DeserializeOwned>(
+    client: &HttpClient,
+    config: &VoltConfig,
+    name: &str,
+    requested: Option<&VersionSpec>,
+    full: bool,
+) -> Result<Option<T>> {
+    let registry = config.registry();
+    let url = format!("{}/{}", registry, name.replace('/', "%2f"));
+
+    let store = config.volt_home()?;
+    let key = packument_key(registry, name, full);
+    let cached = CachedMetadata::read(&store, &key);
+
+    let parse = |data: &[u8]| -> Result<Option<T>> {
+        Ok(Some(serde_json::from_slice(data).into_diagnostic()?))
+    };
+
+    if let Some(cached) = &cached {
+        // offline, a packument without the requested version is reported as such by the caller
+        let usable = config.offline()
+            || (config.prefer_offline() && packument_satisfies(&cached.data, requested));
+
+        if usable || cached.policy.is_fresh(&url) {
+            return parse(&cached.data);
+        }
+    }
+
+    if config.offline() {
+        return Err(VoltError::OfflineMetadataMissing {
+            spec: name.to_string(),
+        }
+        .into());
+    }
+
+    let mut headers = cached
+        .as_ref()
+        .map(|cached| cached.policy.conditional_headers(&url))
+        .unwrap_or_default();
+
+    headers.extend(auth_headers(config, &url));
+
+    headers.insert(
+        ACCEPT,
+        HeaderValue::from_static(if full {
+            "application/json"
+        } else {
+            ABBREVIATED_PACKUMENT
+        }),
+    );
+
+    let response = client.get_with_headers(&url, headers).await?;
+
+    match response.status {
+        // 304 (NOT_MODIFIED), the cached packument is still up to date
+        StatusCode::NOT_MODIFIED if cached.is_some() => {
+            let cached = cached.unwrap();
+            let policy = cached.policy.revalidated(&response.headers);
+
+            CachedMetadata::write(&store, &key, &policy, &cached.data)?;
+
+            parse(&cached.data)
+        }
+        // 404 (NOT_FOUND)
+        StatusCode::NOT_FOUND => Ok(None),
+        status if status.is_success() => {
+            let policy = CachePolicy::from_headers(&url, &response.headers);
+
+            CachedMetadata::write(&store, &key, &policy, &response.body)?;
+
+            parse(&response.body)
+        }
+        status => Err(VoltError::NetworkUnknownError {
+            url,
+            package_name: name.to_string(),
+            code: status.as_str().to_string(),
+        }
+        .into()),
+    }
+}
+
 /// downloads and extracts tarball file from package
-pub async fn fetch_tarball(package: &VoltPackage, state: State) -> Result<bytes::Bytes> {
+pub async fn fetch_tarball(package: &VoltPackage, state: &State) -> Result<bytes::Bytes> {
     // Recieve the tarball from the npm registry
     let response = state
         .http_client
-        .get(&package.tarball)
-        .send()
-        .await
-        .into_diagnostic()?
-        .bytes()
-        .await
-        .into_diagnostic()?;
+        .get_with_progress(&package.tarball, state.progress.fetch())
+        .await?;
 
-    Ok(response)
+    if !response.status.is_success() {
+        return Err(VoltError::NetworkUnknownError {
+            url: package.tarball.clone(),
+            package_name: format!("{}@{}", package.name, package.version),
+            code: response.status.as_str().to_string(),
+        }
+        .into());
+    }
+
+    Ok(response.body)
 }
 
 pub async fn fetch_dep_tree(
     data: &[PackageSpec],
     progress_bar: &ProgressBar,
+    config: &VoltConfig,
+    lock_file: &LockFile,
+    client: &HttpClient,
 ) -> Result<Vec<VoltResponse>> {
     if data.len() > 1 {
-        Ok(get_volt_response_multi(data, progress_bar)
-            .await
-            .into_iter()
-            .collect::<Result<Vec<_>>>()?)
+        Ok(
+            get_volt_response_multi(data, progress_bar, config, lock_file, client)
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>>>()?,
+        )
     } else {
         if let PackageSpec::Npm {
             name, requested, ..
@@ -151,20 +462,20 @@ pub async fn fetch_dep_tree(
             progress_bar.set_message(format!("{}@{}", name, version.truecolor(125, 125, 125)));
         }
 
-        Ok(vec![get_volt_response(&data[0]).await?])
+        Ok(vec![
+            get_volt_response(&data[0], config, lock_file, client).await?,
+        ])
     }
 }
 
-pub async fn _ping() {
+pub async fn _ping(client: &HttpClient) -> Result<()> {
     let _ping = Instant::now();
 
     println!("PING! http://registry.voltpkg.com/");
 
-    let response = isahc::get_async("http://registry.voltpkg.com/ping")
-        .await
-        .unwrap();
+    let response = client.get("http://registry.voltpkg.com/ping").await?;
 
-    match response.status() {
+    match response.status {
         StatusCode::OK => {
             let pong = Instant::now();
 
@@ -182,11 +493,9 @@ pub async fn _ping() {
 
     println!("PING! https://registry.npmjs.org/");
 
-    let response = isahc::get_async("https://registry.npmjs.org/")
-        .await
-        .unwrap();
+    let response = client.get("https://registry.npmjs.org/").await?;
 
-    match response.status() {
+    match response.status {
         StatusCode::OK => {
             let pong = Instant::now();
 
@@ -199,4 +508,58 @@ pub async fn _ping() {
             println!("Ping failed");
         }
     }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{packument_satisfies, read_cached_response};
+    use crate::core::cache::{metadata_key, CachePolicy, CachedMetadata};
+    use crate::core::utils::constants::VOLT_REGISTRY;
+    use package_spec::PackageSpec;
+
+    #[test]
+    fn reads_responses_cached_under_the_previous_spec_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let spec = "@me/thing@1.0.0".parse::<PackageSpec>().unwrap();
+
+        assert!(read_cached_response(dir.path(), &spec).is_none());
+
+        CachedMetadata::write(
+            dir.path(),
+            &metadata_key(VOLT_REGISTRY, "@me/@me/thing1.0.0"),
+            &CachePolicy::default(),
+            b"response",
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_cached_response(dir.path(), &spec).unwrap().data,
+            b"response"
+        );
+    }
+
+    #[test]
+    fn uses_cached_packuments_with_a_requested_version() {
+        let packument = br#"{
+            "dist-tags": { "latest": "1.1.0", "next": "2.0.0-beta.1" },
+            "versions": { "1.0.0": {}, "1.1.0": {}, "2.0.0-beta.1": {} }
+        }"#;
+
+        let spec = |spec: &str| match spec.parse::<PackageSpec>().unwrap() {
+            PackageSpec::Npm { requested, .. } => requested,
+            _ => unreachable!(),
+        };
+
+        assert!(packument_satisfies(packument, None));
+        assert!(packument_satisfies(packument, spec("a@^1.0.0").as_ref()));
+        assert!(packument_satisfies(packument, spec("a@1.0.0").as_ref()));
+        assert!(packument_satisfies(packument, spec("a@next").as_ref()));
+
+        // published after the packument was cached
+        assert!(!packument_satisfies(packument, spec("a@^1.2.0").as_ref()));
+        assert!(!packument_satisfies(packument, spec("a@1.0.1").as_ref()));
+        assert!(!packument_satisfies(packument, spec("a@beta").as_ref()));
+    }
 }
@@ -0,0 +1,166 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Progress reporting for installs: a bar per phase (drawn on stderr) when stdout and stderr are
+//! terminals, nothing but the final summary lines otherwise.
+
+use crate::core::classes::meta::Meta;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Spinner shown while resolving dependencies, hidden unless `meta` allows drawing progress.
+pub fn resolve_spinner(meta: &Meta) -> ProgressBar {
+    if !meta.progress() {
+        return ProgressBar::hidden();
+    }
+
+    let spinner = ProgressBar::new_spinner()
+        .with_style(ProgressStyle::default_spinner().template("{spinner:.cyan} {msg}"));
+
+    spinner.enable_steady_tick(10);
+
+    spinner
+}
+
+/// What happened during an install, reported once every package is installed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InstallSummary {
+    pub cache_hits: usize,     // packages linked from the global store
+    pub downloads: usize,      // packages downloaded from the registry
+    pub downloaded_bytes: u64, // size of the downloaded tarballs
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    cache_hits: AtomicUsize,
+    downloads: AtomicUsize,
+    downloaded_bytes: AtomicU64,
+}
+
+/// Progress of the fetch, extract, link and scripts phases of an install. Cloning it is cheap,
+/// clones update the same bars.
+#[derive(Clone, Debug)]
+pub struct InstallProgress {
+    multi: Arc<MultiProgress>,
+    fetch: ProgressBar,
+    extract: ProgressBar,
+    link: ProgressBar,
+    scripts: ProgressBar,
+    counters: Arc<Counters>,
+}
+
+impl InstallProgress {
+    /// Progress of installing `total` packages, only drawn if `meta` allows it.
+    pub fn new(total: usize, meta: &Meta) -> Self {
+        let multi = MultiProgress::with_draw_target(if meta.progress() {
+            ProgressDrawTarget::stderr()
+        } else {
+            ProgressDrawTarget::hidden()
+        });
+
+        let packages = |phase: &'static str| {
+            multi.add(
+                ProgressBar::new(total as u64)
+                    .with_style(
+                        ProgressStyle::default_bar()
+                            .template(
+                                "{prefix:>8.cyan.bold} [{bar:40.cyan/blue}] {pos:>7}/{len:7} {msg}",
+                            )
+                            .progress_chars("=>-"),
+                    )
+                    .with_prefix(phase),
+            )
+        };
+
+        // the number of bytes to download and extract is only known once the responses come in
+        let bytes = |phase: &'static str| {
+            multi.add(
+                ProgressBar::new(0)
+                    .with_style(
+                        ProgressStyle::default_bar()
+                            .template("{prefix:>8.cyan.bold} [{bar:40.cyan/blue}] {bytes:>7}/{total_bytes:7} {bytes_per_sec}")
+                            .progress_chars("=>-"),
+                    )
+                    .with_prefix(phase),
+            )
+        };
+
+        Self {
+            fetch: bytes("fetch"),
+            extract: bytes("extract"),
+            link: packages("link"),
+            scripts: packages("scripts"),
+            multi: Arc::new(multi),
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    /// Bar counting the bytes of the tarballs being downloaded.
+    pub fn fetch(&self) -> &ProgressBar {
+        &self.fetch
+    }
+
+    /// A package was found in the global store.
+    pub fn cache_hit(&self) {
+        self.counters.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A tarball of `bytes` bytes, `unpacked` bytes once decompressed, was downloaded and has to
+    /// be extracted.
+    pub fn downloaded(&self, bytes: u64, unpacked: u64) {
+        self.extract.inc_length(unpacked);
+
+        self.counters.downloads.fetch_add(1, Ordering::Relaxed);
+        self.counters
+            .downloaded_bytes
+            .fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// A tarball of `unpacked` bytes (decompressed) was extracted into the store.
+    pub fn extracted(&self, unpacked: u64) {
+        self.extract.inc(unpacked);
+    }
+
+    /// A package was linked into `node_modules`, along with its dependencies.
+    pub fn linked(&self) {
+        self.link.inc(1);
+    }
+
+    /// The binary scripts of a package were generated.
+    pub fn scripts_generated(&self) {
+        self.scripts.inc(1);
+    }
+
+    /// Clear the bars and return what happened.
+    pub fn finish(&self) -> InstallSummary {
+        for bar in [&self.fetch, &self.extract, &self.link, &self.scripts] {
+            bar.finish_and_clear();
+        }
+
+        let _ = self.multi.clear();
+
+        InstallSummary {
+            cache_hits: self.counters.cache_hits.load(Ordering::Relaxed),
+            downloads: self.counters.downloads.load(Ordering::Relaxed),
+            downloaded_bytes: self.counters.downloaded_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
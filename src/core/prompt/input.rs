@@ -545,3 +545,114 @@ where
         }
     }
 }
+
+pub struct MultiSelect<'a> {
+    prompt: Cow<'a, str>,
+    items: Vec<String>,
+    defaults: Vec<bool>,
+    theme: &'a dyn Theme,
+}
+
+impl<'a> MultiSelect<'a> {
+    /// Creates a multi select prompt with a specific theme.
+    pub fn with_theme(theme: &'a dyn Theme) -> MultiSelect<'a> {
+        MultiSelect {
+            prompt: "".into(),
+            items: vec![],
+            defaults: vec![],
+            theme,
+        }
+    }
+
+    /// Sets the multi select prompt.
+    pub fn with_prompt<S: Into<Cow<'a, str>>>(&mut self, prompt: S) -> &mut MultiSelect<'a> {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Adds items to the list.
+    pub fn items<T: ToString>(&mut self, items: &[T]) -> &mut MultiSelect<'a> {
+        self.items.extend(items.iter().map(ToString::to_string));
+        self
+    }
+
+    /// Sets which items are checked at first, items without an entry aren't.
+    pub fn defaults(&mut self, defaults: &[bool]) -> &mut MultiSelect<'a> {
+        self.defaults = defaults.to_vec();
+        self
+    }
+
+    /// Enables user interaction and returns the indices of the checked items.
+    ///
+    /// The user moves with the arrow keys (or `j` and `k`), checks items with space, checks or
+    /// unchecks all of them with `a` and confirms with enter. The dialog is rendered on stderr.
+    pub fn interact(&self) -> io::Result<Vec<usize>> {
+        self.interact_on(&Term::stderr())
+    }
+
+    /// Like [`interact`](#method.interact) but allows a specific terminal to be set.
+    pub fn interact_on(&self, term: &Term) -> io::Result<Vec<usize>> {
+        if self.items.is_empty() {
+            return Ok(vec![]);
+        }
+
+        if !term.features().is_attended() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "not a terminal",
+            ));
+        }
+
+        let mut render = TermThemeRenderer::new(term, self.theme);
+        let mut checked = (0..self.items.len())
+            .map(|index| self.defaults.get(index).copied().unwrap_or(false))
+            .collect::<Vec<_>>();
+        let mut active = 0;
+
+        term.hide_cursor()?;
+
+        loop {
+            render.multi_select_prompt(&self.prompt)?;
+
+            for (index, item) in self.items.iter().enumerate() {
+                render.multi_select_prompt_item(item, checked[index], index == active)?;
+            }
+
+            term.flush()?;
+
+            match term.read_key()? {
+                Key::ArrowDown | Key::Tab | Key::Char('j') => {
+                    active = (active + 1) % self.items.len();
+                }
+                Key::ArrowUp | Key::BackTab | Key::Char('k') => {
+                    active = (active + self.items.len() - 1) % self.items.len();
+                }
+                Key::Char(' ') => checked[active] = !checked[active],
+                Key::Char('a') => {
+                    let all = checked.iter().all(|checked| *checked);
+                    checked.iter_mut().for_each(|checked| *checked = !all);
+                }
+                Key::Enter => {
+                    render.clear()?;
+
+                    let selection = self
+                        .items
+                        .iter()
+                        .zip(&checked)
+                        .filter(|(_, checked)| **checked)
+                        .map(|(item, _)| item.as_str())
+                        .collect::<Vec<_>>();
+
+                    render.multi_select_prompt_selection(&self.prompt, &selection)?;
+                    term.show_cursor()?;
+                    term.flush()?;
+
+                    return Ok((0..checked.len()).filter(|index| checked[*index]).collect());
+                }
+                _ => {}
+            }
+
+            render.clear()?;
+        }
+    }
+}
@@ -19,6 +19,40 @@ use crate::core::prompt::input;
 use dialoguer::{console, theme::ColorfulTheme};
 use std::{borrow::Cow, io::Result};
 
+/// Theme shared by the prompts.
+fn theme() -> ColorfulTheme {
+    ColorfulTheme {
+        defaults_style: console::Style::new(),
+        prompt_style: console::Style::new(),
+        prompt_prefix: console::style(String::from("?")).yellow().bright(),
+        prompt_suffix: console::style(String::from(">")).blue().dim(),
+        success_prefix: console::style(String::from("✔")).green().bright(),
+        success_suffix: console::style(String::from("·")).blue().dim(),
+        error_prefix: console::style(String::from("❌")).bright().red(),
+        error_style: console::Style::new(),
+        hint_style: console::Style::new(),
+        values_style: console::Style::new(),
+        active_item_style: console::Style::new(),
+        inactive_item_style: console::Style::new(),
+        active_item_prefix: console::style(String::from("✔")).bright().green(),
+        inactive_item_prefix: console::style(String::from(" ")),
+        checked_item_prefix: console::style(String::from("")),
+        unchecked_item_prefix: console::style(String::from("")),
+        picked_item_prefix: console::style(String::from("")),
+        unpicked_item_prefix: console::style(String::from("")),
+        inline_selections: false,
+    }
+}
+
+/// Theme of the prompts listing items, with a bold prompt and hints.
+fn list_theme() -> ColorfulTheme {
+    ColorfulTheme {
+        prompt_style: console::Style::new().bold(),
+        hint_style: console::Style::new().bold(),
+        ..theme()
+    }
+}
+
 /// Prompt that returns `true` or `false` (as strings)
 #[derive(Debug)]
 pub struct Confirm<'i> {
@@ -35,27 +69,7 @@ pub struct Confirm<'i> {
 
 impl<'i> Confirm<'i> {
     pub fn run(&self) -> Result<bool> {
-        let theme = ColorfulTheme {
-            defaults_style: console::Style::new(),
-            prompt_style: console::Style::new(),
-            prompt_prefix: console::style(String::from("?")).yellow().bright(),
-            prompt_suffix: console::style(String::from(">")).blue().dim(),
-            success_prefix: console::style(String::from("✔")).green().bright(),
-            success_suffix: console::style(String::from("·")).blue().dim(),
-            error_prefix: console::style(String::from("❌")).bright().red(),
-            error_style: console::Style::new(),
-            hint_style: console::Style::new(),
-            values_style: console::Style::new(),
-            active_item_style: console::Style::new(),
-            inactive_item_style: console::Style::new(),
-            active_item_prefix: console::style(String::from("✔")).bright().green(),
-            inactive_item_prefix: console::style(String::from(" ")),
-            checked_item_prefix: console::style(String::from("")),
-            unchecked_item_prefix: console::style(String::from("")),
-            picked_item_prefix: console::style(String::from("")),
-            unpicked_item_prefix: console::style(String::from("")),
-            inline_selections: false,
-        };
+        let theme = theme();
 
         let value = dialoguer::Confirm::with_theme(&theme)
             .with_prompt(self.message.clone().into_owned())
@@ -81,27 +95,7 @@ pub struct Input<'i> {
 
 impl Input<'_> {
     pub fn run(&self) -> Result<String> {
-        let theme = ColorfulTheme {
-            defaults_style: console::Style::new(),
-            prompt_style: console::Style::new(),
-            prompt_prefix: console::style(String::from("?")).yellow().bright(),
-            prompt_suffix: console::style(String::from(">")).blue().dim(),
-            success_prefix: console::style(String::from("✔")).green().bright(),
-            success_suffix: console::style(String::from("·")).blue().dim(),
-            error_prefix: console::style(String::from("❌")).bright().red(),
-            error_style: console::Style::new(),
-            hint_style: console::Style::new(),
-            values_style: console::Style::new(),
-            active_item_style: console::Style::new(),
-            inactive_item_style: console::Style::new(),
-            active_item_prefix: console::style(String::from("✔")).bright().green(),
-            inactive_item_prefix: console::style(String::from(" ")),
-            checked_item_prefix: console::style(String::from("")),
-            unchecked_item_prefix: console::style(String::from("")),
-            picked_item_prefix: console::style(String::from("")),
-            unpicked_item_prefix: console::style(String::from("")),
-            inline_selections: false,
-        };
+        let theme = theme();
 
         let mut input = input::Input::<String>::with_theme(&theme);
 
@@ -178,27 +172,7 @@ impl<'i> Select<'i> {
             return Ok(0);
         }
 
-        let theme = ColorfulTheme {
-            defaults_style: console::Style::new(),
-            prompt_style: console::Style::new().bold(),
-            prompt_prefix: console::style(String::from("?")).yellow().bright(),
-            prompt_suffix: console::style(String::from(">")).blue().dim(),
-            success_prefix: console::style(String::from("✔")).green().bright(),
-            success_suffix: console::style(String::from("·")).blue().dim(),
-            error_prefix: console::style(String::from("❌")).bright().red(),
-            error_style: console::Style::new(),
-            hint_style: console::Style::new().bold(),
-            values_style: console::Style::new(),
-            active_item_style: console::Style::new(),
-            inactive_item_style: console::Style::new(),
-            active_item_prefix: console::style(String::from("✔")).bright().green(),
-            inactive_item_prefix: console::style(String::from(" ")),
-            checked_item_prefix: console::style(String::from("")),
-            unchecked_item_prefix: console::style(String::from("")),
-            picked_item_prefix: console::style(String::from("")),
-            unpicked_item_prefix: console::style(String::from("")),
-            inline_selections: false,
-        };
+        let theme = list_theme();
 
         let mut input = dialoguer::Select::with_theme(&theme);
 
@@ -213,3 +187,39 @@ impl<'i> Select<'i> {
         input.interact()
     }
 }
+
+/// Prompt that allows the user to check any number of options from a list
+#[derive(Debug)]
+pub struct MultiSelect<'i> {
+    /// Message for the prompt
+    pub message: Cow<'i, str>,
+
+    /// Items that can be checked
+    pub items: Vec<Cow<'i, str>>,
+
+    /// Whether each item is checked at first, items without an entry aren't
+    pub checked: Vec<bool>,
+}
+
+impl<'i> MultiSelect<'i> {
+    /// Returns the indices of the checked items.
+    pub fn run(&self) -> Result<Vec<usize>> {
+        if self.items.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let theme = ColorfulTheme {
+            active_item_style: console::Style::new().cyan(),
+            active_item_prefix: console::style(String::from("›")).bright().cyan(),
+            checked_item_prefix: console::style(String::from("◉")).green(),
+            unchecked_item_prefix: console::style(String::from("◯")).dim(),
+            ..list_theme()
+        };
+
+        input::MultiSelect::with_theme(&theme)
+            .with_prompt(self.message.clone())
+            .items(&self.items)
+            .defaults(&self.checked)
+            .interact()
+    }
+}
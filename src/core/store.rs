@@ -0,0 +1,1389 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! Helpers for the global content-addressable store located at `~/.volt`.
+
+use crate::core::utils::{errors::VoltError, voltapi::VoltPackage};
+
+use clap::ArgEnum;
+use miette::IntoDiagnostic;
+use sha1::{Digest, Sha1};
+use speedy::{Readable, Writable};
+use ssri::Integrity;
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How files are materialized into `node_modules` from the global store.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkStrategy {
+    /// Hardlink files, every project shares the same copy on disk
+    Hardlink,
+    /// Clone files using copy-on-write (btrfs, xfs)
+    Reflink,
+    /// Copy files
+    Copy,
+}
+
+/// Path of a content entry inside of the store.
+///
+/// Mirrors the layout used by `cacache`: `sha512-BaSE64Hex=` is stored at
+/// `~/.volt/content-v2/sha512/ba/da/55deadbeefc0ffee`.
+pub fn content_path(store: &Path, integrity: &Integrity) -> PathBuf {
+    let (algorithm, hex) = integrity.to_hex();
+
+    let mut path = store.join("content-v2");
+
+    path.push(algorithm.to_string());
+    path.push(&hex[0..2]);
+    path.push(&hex[2..4]);
+    path.push(&hex[4..]);
+
+    path
+}
+
+/// Everything needed to materialize a package from the store, written once per package so that
+/// installing it again doesn't need a lookup per file.
+#[derive(Debug, Clone, Default, PartialEq, Readable, Writable)]
+pub struct PackageIndex {
+    pub files: Vec<IndexedFile>,
+    pub symlinks: Vec<IndexedSymlink>,
+}
+
+#[derive(Debug, Clone, PartialEq, Readable, Writable)]
+pub struct IndexedFile {
+    /// Path of the file relative to the package, using `/` as a separator
+    pub path: String,
+    /// Size of the file in bytes
+    pub size: u64,
+    /// Normalized permissions of the file (0o644 or 0o755)
+    pub mode: u32,
+    /// Integrity of the file's contents in the store
+    pub integrity: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Readable, Writable)]
+pub struct IndexedSymlink {
+    /// Path of the symlink relative to the package
+    pub path: String,
+    /// Where the symlink points to, relative to the symlink
+    pub target: String,
+}
+
+impl PackageIndex {
+    /// Read the index stored under `key`.
+    pub fn read(store: &Path, key: &str) -> miette::Result<Self> {
+        let data = cacache::read_sync(store, key).into_diagnostic()?;
+
+        Self::read_from_buffer(&data).into_diagnostic()
+    }
+
+    /// Write the index under `key`.
+    pub fn write(&self, store: &Path, key: &str) -> miette::Result<()> {
+        cacache::write_sync(store, key, self.write_to_vec().into_diagnostic()?)
+            .into_diagnostic()?;
+
+        Ok(())
+    }
+
+    /// Link every file of the package from the store into `destination`.
+    pub fn materialize(
+        &self,
+        store: &Path,
+        destination: &Path,
+        linker: &Linker,
+    ) -> miette::Result<()> {
+        let mut created_directories: HashSet<PathBuf> = HashSet::new();
+
+        fs::create_dir_all(destination).into_diagnostic()?;
+
+        for file in &self.files {
+            let path = destination.join(&file.path);
+
+            // If we haven't created this directory yet, create it
+            if let Some(parent) = path.parent() {
+                if !created_directories.contains(parent) {
+                    fs::create_dir_all(parent).into_diagnostic()?;
+                    created_directories.insert(parent.to_path_buf());
+                }
+            }
+
+            link_from_store(store, &file.integrity()?, &path, file.mode, linker)?;
+        }
+
+        for symlink in &self.symlinks {
+            symlink.create(destination)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl IndexedFile {
+    pub fn integrity(&self) -> miette::Result<Integrity> {
+        self.integrity.parse().into_diagnostic()
+    }
+}
+
+impl IndexedSymlink {
+    /// Create the symlink inside of the package located at `package_root`.
+    pub fn create(&self, package_root: &Path) -> miette::Result<()> {
+        let path = package_root.join(&self.path);
+
+        // a previous install might have already created this path
+        let _ = fs::remove_file(&path);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).into_diagnostic()?;
+        }
+
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&self.target, &path).map_err(|e| VoltError::LinkFileError {
+            source: e,
+            name: path.display().to_string(),
+        })?;
+
+        // symlinks need elevated privileges on windows, so they're skipped.
+        #[cfg(windows)]
+        tracing::debug!("skipping symlink {}", path.display());
+
+        Ok(())
+    }
+}
+
+/// Name of the unpacked copy of every package of `tree` (keyed by `name@version`).
+///
+/// Unpacked copies link to the copies of their dependencies, so the name includes a hash of the
+/// package and everything reachable from it: projects only share a copy when they resolved the
+/// same dependencies for it.
+pub fn unpacked_names(tree: &HashMap<String, VoltPackage>) -> HashMap<String, String> {
+    tree.keys()
+        .map(|id| {
+            let mut reachable = BTreeSet::new();
+            let mut pending = vec![id.as_str()];
+
+            while let Some(id) = pending.pop() {
+                if !reachable.insert(id) {
+                    continue;
+                }
+
+                for (name, version) in tree[id].dependencies.iter().flatten() {
+                    if let Some((dependency, _)) =
+                        tree.get_key_value(&format!("{}@{}", name, version))
+                    {
+                        pending.push(dependency);
+                    }
+                }
+            }
+
+            let mut hasher = Sha1::new();
+
+            for id in reachable {
+                let package = &tree[id];
+
+                let mut dependencies = package.dependencies.iter().flatten().collect::<Vec<_>>();
+                dependencies.sort();
+
+                hasher.update(format!("{}\0{}\0", id, package.integrity));
+
+                for (name, version) in dependencies {
+                    hasher.update(format!("{}@{}\0", name, version));
+                }
+
+                hasher.update("\n");
+            }
+
+            let hash = hex::encode(hasher.finalize());
+
+            (
+                id.clone(),
+                format!("{}-{}", tree[id].directory_name(), &hash[..16]),
+            )
+        })
+        .collect()
+}
+
+/// Path of the unpacked copy named `name` (see `unpacked_names`) kept in the store with
+/// `--store-unpacked`.
+///
+/// The copy is laid out like the package's directory in `node_modules/.volt`: the package is in
+/// `node_modules/<name>`, next to links to the copies of its dependencies.
+pub fn unpacked_path(store: &Path, name: &str) -> PathBuf {
+    store.join("unpacked").join(name)
+}
+
+/// Keep an unpacked copy of the package installed at `package_root` in the store, linking each
+/// of its `dependencies` (name and unpacked copy) next to it, so that later installs can link
+/// the whole directory at once.
+pub fn save_unpacked(
+    package_root: &Path,
+    package_name: &str,
+    dependencies: &[(String, PathBuf)],
+    unpacked: &Path,
+    linker: &Linker,
+) -> miette::Result<()> {
+    if unpacked.exists() {
+        return Ok(());
+    }
+
+    // link into a temporary directory first so a partially written copy is never used
+    let temporary = unpacked.with_file_name(format!(
+        ".{}-{}",
+        unpacked.file_name().unwrap_or_default().to_string_lossy(),
+        rand::random::<u32>()
+    ));
+
+    let node_modules = temporary.join("node_modules");
+
+    link_tree(package_root, &node_modules.join(package_name), linker)?;
+
+    for (name, dependency) in dependencies {
+        let path = node_modules.join(name);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).into_diagnostic()?;
+        }
+
+        link_directory(dependency, name, &path, name.matches('/').count() + 2)?;
+    }
+
+    if fs::rename(&temporary, unpacked).is_err() {
+        // another install stored the same package in the meantime
+        fs::remove_dir_all(&temporary).into_diagnostic()?;
+    }
+
+    Ok(())
+}
+
+/// Link the package `name` of the unpacked copy located at `unpacked` at `path`, which is
+/// `depth` directories below the directory holding the unpacked copies.
+fn link_directory(unpacked: &Path, name: &str, path: &Path, depth: usize) -> miette::Result<()> {
+    let link_error = |e| VoltError::LinkFileError {
+        source: e,
+        name: path.display().to_string(),
+    };
+
+    // relative, so that it keeps working once the temporary copy is renamed
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(
+        PathBuf::from("../".repeat(depth))
+            .join(unpacked.file_name().unwrap_or_default())
+            .join("node_modules")
+            .join(name),
+        path,
+    )
+    .map_err(link_error)?;
+
+    #[cfg(windows)]
+    junction::create(unpacked.join("node_modules").join(name), path).map_err(link_error)?;
+
+    Ok(())
+}
+
+/// Make `directory` (a package's directory in `node_modules/.volt`) a link to its unpacked copy,
+/// replacing whatever a previous install left there.
+pub fn link_unpacked(unpacked: &Path, directory: &Path) -> miette::Result<()> {
+    let link_error = |e| VoltError::LinkFileError {
+        source: e,
+        name: directory.display().to_string(),
+    };
+
+    if fs::read_link(directory).map_or(false, |target| target == unpacked) {
+        return Ok(());
+    }
+
+    detach_unpacked(directory)?;
+
+    if directory.exists() {
+        fs::remove_dir_all(directory).map_err(link_error)?;
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(unpacked, directory).map_err(link_error)?;
+
+    #[cfg(windows)]
+    junction::create(unpacked, directory).map_err(link_error)?;
+
+    Ok(())
+}
+
+/// Turn `directory` back into a directory of the project if it links to an unpacked copy, so
+/// that installing into it never writes into the store.
+pub fn detach_unpacked(directory: &Path) -> miette::Result<()> {
+    let link_error = |e| VoltError::LinkFileError {
+        source: e,
+        name: directory.display().to_string(),
+    };
+
+    // junctions are reported as symlinks too
+    let linked =
+        fs::symlink_metadata(directory).map_or(false, |metadata| metadata.file_type().is_symlink());
+
+    if linked {
+        #[cfg(unix)]
+        fs::remove_file(directory).map_err(link_error)?;
+
+        #[cfg(windows)]
+        fs::remove_dir(directory).map_err(link_error)?;
+    }
+
+    Ok(())
+}
+
+/// Recursively link every file of `source` into `destination`, recreating directories and
+/// symlinks.
+pub fn link_tree(source: &Path, destination: &Path, linker: &Linker) -> miette::Result<()> {
+    let link_error = |e, path: &Path| VoltError::LinkFileError {
+        source: e,
+        name: path.display().to_string(),
+    };
+
+    fs::create_dir_all(destination).map_err(|e| link_error(e, destination))?;
+
+    for entry in fs::read_dir(source).map_err(|e| link_error(e, source))? {
+        let entry = entry.map_err(|e| link_error(e, source))?;
+        let file_type = entry
+            .file_type()
+            .map_err(|e| link_error(e, &entry.path()))?;
+
+        let target = destination.join(entry.file_name());
+
+        if file_type.is_dir() {
+            link_tree(&entry.path(), &target, linker)?;
+        } else if file_type.is_symlink() {
+            let symlink = IndexedSymlink {
+                path: entry.file_name().to_string_lossy().into_owned(),
+                target: fs::read_link(entry.path())
+                    .map_err(|e| link_error(e, &entry.path()))?
+                    .to_string_lossy()
+                    .into_owned(),
+            };
+
+            symlink.create(destination)?;
+        } else {
+            linker
+                .link(&entry.path(), &target)
+                .map_err(|e| link_error(e, &target))?;
+
+            // copies and clones don't share permissions with the source
+            if linker.strategy() != LinkStrategy::Hardlink {
+                let permissions = entry
+                    .metadata()
+                    .map_err(|e| link_error(e, &entry.path()))?
+                    .permissions();
+
+                fs::set_permissions(&target, permissions).map_err(|e| link_error(e, &target))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Materialize a file of the store at `destination` with the normalized `mode`.
+///
+/// Hardlinks share their permissions with the store, and so with every project linking the
+/// same contents. A file is only hardlinked when the store already has its mode, or when nothing
+/// links the contents yet so that their mode can be set once for all. Otherwise it's copied, and
+/// the copy gets the mode.
+pub fn link_from_store(
+    store: &Path,
+    integrity: &Integrity,
+    destination: &Path,
+    mode: u32,
+    linker: &Linker,
+) -> miette::Result<()> {
+    let link_error = |e| VoltError::LinkFileError {
+        source: e,
+        name: destination.display().to_string(),
+    };
+
+    let source = content_path(store, integrity);
+
+    let shared = linker.strategy() == LinkStrategy::Hardlink;
+
+    if shared && !share_mode(&source, mode).map_err(link_error)? {
+        Linker::new(LinkStrategy::Copy)
+            .link(&source, destination)
+            .map_err(link_error)?;
+    } else {
+        linker.link(&source, destination).map_err(link_error)?;
+
+        if shared {
+            return Ok(());
+        }
+    }
+
+    set_file_mode(destination, mode).map_err(link_error)?;
+
+    Ok(())
+}
+
+/// Whether the contents at `source` can be hardlinked with `mode`: they already have it, or
+/// nothing links them yet and they are given it.
+#[cfg(unix)]
+fn share_mode(source: &Path, mode: u32) -> io::Result<bool> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let metadata = fs::metadata(source)?;
+
+    if metadata.mode() & 0o777 == mode {
+        return Ok(true);
+    }
+
+    if metadata.nlink() == 1 {
+        fs::set_permissions(source, fs::Permissions::from_mode(mode))?;
+
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+#[cfg(windows)]
+fn share_mode(_source: &Path, _mode: u32) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// Keep only whether a file is executable from its mode.
+pub fn normalize_mode(mode: u32) -> u32 {
+    if mode & 0o111 != 0 {
+        0o755
+    } else {
+        0o644
+    }
+}
+
+/// Apply a normalized mode to a file that isn't shared with the store.
+#[cfg(unix)]
+fn set_file_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if fs::metadata(path)?.permissions().mode() & 0o777 != mode {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_file_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// Links files out of the store using the requested [`LinkStrategy`].
+///
+/// When the strategy isn't supported (the store lives on a different filesystem, or the
+/// filesystem can't clone files), the linker falls back to copying for the rest of the run.
+/// Clones of a linker share that decision.
+#[derive(Clone, Debug)]
+pub struct Linker {
+    strategy: LinkStrategy,
+    fallback: Arc<AtomicBool>,
+}
+
+impl Linker {
+    pub fn new(strategy: LinkStrategy) -> Self {
+        Self {
+            strategy,
+            fallback: Arc::new(AtomicBool::new(strategy == LinkStrategy::Copy)),
+        }
+    }
+
+    /// The strategy that is currently being used to link files.
+    pub fn strategy(&self) -> LinkStrategy {
+        if self.fallback.load(Ordering::Relaxed) {
+            LinkStrategy::Copy
+        } else {
+            self.strategy
+        }
+    }
+
+    /// Materialize `source` (a file inside of the store) at `destination`, replacing whatever
+    /// is already there.
+    pub fn link(&self, source: &Path, destination: &Path) -> io::Result<()> {
+        match self.link_once(source, destination) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                fs::remove_file(destination)?;
+                self.link_once(source, destination)
+            }
+            result => result,
+        }
+    }
+
+    fn link_once(&self, source: &Path, destination: &Path) -> io::Result<()> {
+        let result = match self.strategy() {
+            LinkStrategy::Copy => return copy(source, destination),
+            LinkStrategy::Hardlink => fs::hard_link(source, destination),
+            LinkStrategy::Reflink => reflink(source, destination),
+        };
+
+        match result {
+            Err(e) if is_unsupported(&e) => {
+                tracing::debug!(
+                    "{:?} is not supported for {} ({}), falling back to copying files",
+                    self.strategy,
+                    destination.display(),
+                    e
+                );
+
+                self.fallback.store(true, Ordering::Relaxed);
+
+                copy(source, destination)
+            }
+            result => result,
+        }
+    }
+}
+
+/// Copy `source` to `destination`, failing if `destination` already exists so that an existing
+/// symlink is never followed.
+fn copy(source: &Path, destination: &Path) -> io::Result<()> {
+    let mut source = fs::File::open(source)?;
+
+    let mut destination = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(destination)?;
+
+    io::copy(&mut source, &mut destination).map(|_| ())
+}
+
+/// Whether a linking error means the strategy can't be used between the store and
+/// `node_modules` at all, rather than a problem with a single file.
+fn is_unsupported(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    if let Some(code) = error.raw_os_error() {
+        return [
+            libc::EXDEV,
+            libc::EPERM,
+            libc::EOPNOTSUPP,
+            libc::ENOTSUP,
+            libc::EINVAL,
+            libc::ENOTTY,
+            libc::EMLINK,
+        ]
+        .contains(&code);
+    }
+
+    #[cfg(windows)]
+    if let Some(code) = error.raw_os_error() {
+        // ERROR_NOT_SAME_DEVICE, ERROR_INVALID_FUNCTION, ERROR_NOT_SUPPORTED, ERROR_TOO_MANY_LINKS
+        return [17, 1, 50, 1142].contains(&code);
+    }
+
+    error.kind() == io::ErrorKind::Unsupported
+}
+
+/// Clone `source` into `destination` using the `FICLONE` ioctl.
+#[cfg(target_os = "linux")]
+fn reflink(source: &Path, destination: &Path) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // _IOW(0x94, 9, int) from linux/fs.h
+    const FICLONE: libc::c_ulong = 0x4004_9409;
+
+    let source_file = fs::File::open(source)?;
+
+    let destination_file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(destination)?;
+
+    // SAFETY: both file descriptors are valid for the duration of the call.
+    let result = unsafe {
+        libc::ioctl(
+            destination_file.as_raw_fd(),
+            FICLONE as _,
+            source_file.as_raw_fd(),
+        )
+    };
+
+    if result == -1 {
+        let error = io::Error::last_os_error();
+
+        drop(destination_file);
+        fs::remove_file(destination)?;
+
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_source: &Path, _destination: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reflinks are only supported on linux",
+    ))
+}
+
+/// Prefix of the keys under which package indexes are stored.
+pub const PACKAGE_INDEX_PREFIX: &str = "pkg-index::";
+
+/// Prefix of the keys written by older versions of volt, which stored a JSON map per package.
+const LEGACY_PACKAGE_PREFIX: &str = "pkg::";
+
+/// Directory (inside of the store) holding one marker file per project installed from the store.
+const PROJECTS_DIRECTORY: &str = "projects";
+
+/// A project that has been installed from the store.
+#[derive(Debug, Clone)]
+pub struct RegisteredProject {
+    pub path: PathBuf,
+    pub last_used: SystemTime,
+    marker: PathBuf,
+}
+
+impl RegisteredProject {
+    /// Forget about the project.
+    pub fn unregister(&self) -> miette::Result<()> {
+        match fs::remove_file(&self.marker) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(VoltError::WriteFileError {
+                source: e,
+                name: self.marker.display().to_string(),
+            }
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Names of the package directories inside of the project's `node_modules/.volt`.
+    pub fn installed_packages(&self) -> HashSet<String> {
+        fs::read_dir(self.path.join("node_modules").join(".volt"))
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Record that `project` has just been installed from the store.
+///
+/// Every project gets its own marker file (named after a hash of its path) so that concurrent
+/// installs never overwrite each other, the marker's modification time is when it was last used.
+pub fn register_project(store: &Path, project: &Path) -> miette::Result<()> {
+    let project = project
+        .canonicalize()
+        .unwrap_or_else(|_| project.to_path_buf());
+
+    let directory = store.join(PROJECTS_DIRECTORY);
+
+    fs::create_dir_all(&directory).map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: directory.display().to_string(),
+    })?;
+
+    let marker = directory.join(hex::encode(Sha1::digest(
+        project.to_string_lossy().as_bytes(),
+    )));
+
+    fs::write(&marker, project.to_string_lossy().as_bytes()).map_err(|e| {
+        VoltError::WriteFileError {
+            source: e,
+            name: marker.display().to_string(),
+        }
+    })?;
+
+    Ok(())
+}
+
+/// Every project that has been installed from the store.
+pub fn registered_projects(store: &Path) -> miette::Result<Vec<RegisteredProject>> {
+    let directory = store.join(PROJECTS_DIRECTORY);
+
+    let entries = match fs::read_dir(&directory) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => {
+            return Err(VoltError::ReadFileError {
+                source: e,
+                name: directory.display().to_string(),
+            }
+            .into())
+        }
+    };
+
+    let mut projects = vec![];
+
+    for entry in entries.filter_map(Result::ok) {
+        let marker = entry.path();
+
+        let (path, metadata) = match (fs::read_to_string(&marker), entry.metadata()) {
+            (Ok(path), Ok(metadata)) => (path, metadata),
+            _ => continue,
+        };
+
+        projects.push(RegisteredProject {
+            path: PathBuf::from(path),
+            last_used: metadata.modified().unwrap_or(UNIX_EPOCH),
+            marker,
+        });
+    }
+
+    Ok(projects)
+}
+
+/// An entry of the store's index.
+#[derive(Debug, Clone)]
+pub struct StoreEntry {
+    pub key: String,
+    pub integrity: Integrity,
+    pub time: SystemTime,
+}
+
+impl StoreEntry {
+    /// Name of the package's directory in `node_modules/.volt` (`name@version`), if this entry is
+    /// a package index.
+    pub fn directory_name(&self) -> Option<String> {
+        let mut parts = self.key.strip_prefix(PACKAGE_INDEX_PREFIX)?.split("::");
+
+        let name = parts.next()?;
+        let version = parts.next()?;
+
+        Some(format!("{}@{}", name.replace('/', "+"), version))
+    }
+
+    /// Whether this entry was written by an older version of volt and can't be used anymore.
+    pub fn is_legacy(&self) -> bool {
+        self.key.starts_with(LEGACY_PACKAGE_PREFIX)
+    }
+}
+
+/// Every live entry of the store's index.
+pub fn store_entries(store: &Path) -> miette::Result<Vec<StoreEntry>> {
+    // nothing has been written to a fresh store yet, so it doesn't have an index to list
+    let has_index = fs::read_dir(store)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .any(|entry| entry.file_name().to_string_lossy().starts_with("index-v"))
+        })
+        .unwrap_or(false);
+
+    if !has_index {
+        return Ok(vec![]);
+    }
+
+    // `cacache::list_sync` also returns entries that have since been removed, so only use it to
+    // find the keys and look each of them up again.
+    let mut keys = HashSet::new();
+
+    for metadata in cacache::list_sync(store) {
+        keys.insert(metadata.into_diagnostic()?.key);
+    }
+
+    let mut entries = vec![];
+
+    for key in keys {
+        if let Some(metadata) = cacache::metadata_sync(store, &key).into_diagnostic()? {
+            entries.push(StoreEntry {
+                key,
+                integrity: metadata.integrity,
+                time: UNIX_EPOCH + Duration::from_millis(metadata.time as u64),
+            });
+        }
+    }
+
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+
+    Ok(entries)
+}
+
+/// Size of everything under `path` in bytes, files hardlinked several times are only counted
+/// once.
+pub fn disk_usage(path: &Path) -> u64 {
+    #[cfg(unix)]
+    let mut seen = HashSet::new();
+
+    let mut size = 0;
+
+    for entry in jwalk::WalkDir::new(path).skip_hidden(false) {
+        let metadata = match entry.and_then(|entry| entry.metadata()) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => continue,
+        };
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            if !seen.insert((metadata.dev(), metadata.ino())) {
+                continue;
+            }
+        }
+
+        size += metadata.len();
+    }
+
+    size
+}
+
+/// What `prune` removed (or would remove, on a dry run) from the store.
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    pub packages: Vec<String>,
+    pub projects: Vec<PathBuf>,
+    pub content_files: usize,
+    pub freed: u64,
+}
+
+/// Remove every package that isn't installed in a project registered since `cutoff`, along
+/// with the content only they were referencing.
+///
+/// Packages written to the store since `cutoff` are always kept, and so is everything in the
+/// store that isn't a package (like cached registry metadata).
+pub fn prune(store: &Path, cutoff: SystemTime, dry_run: bool) -> miette::Result<PruneReport> {
+    let mut report = PruneReport::default();
+    let mut in_use = HashSet::new();
+
+    for project in registered_projects(store)? {
+        if !project.path.join("node_modules").exists() {
+            if !dry_run {
+                project.unregister()?;
+            }
+
+            report.projects.push(project.path);
+        } else if project.last_used >= cutoff {
+            in_use.extend(project.installed_packages());
+        }
+    }
+
+    let mut live_content = HashSet::new();
+    let mut kept_packages = HashSet::new();
+
+    for entry in store_entries(store)? {
+        let keep = match entry.directory_name() {
+            Some(name) if entry.time >= cutoff || in_use.contains(&name) => {
+                kept_packages.insert(name);
+                true
+            }
+            Some(_) => false,
+            None => !entry.is_legacy(),
+        };
+
+        if !keep {
+            if !dry_run {
+                cacache::remove_sync(store, &entry.key).into_diagnostic()?;
+            }
+
+            if let Some(name) = entry.directory_name() {
+                report.packages.push(name);
+            }
+
+            continue;
+        }
+
+        if entry.directory_name().is_some() {
+            // an unreadable index is treated as removed, its files are downloaded again
+            if let Ok(index) = PackageIndex::read(store, &entry.key) {
+                for file in &index.files {
+                    live_content.insert(content_path(store, &file.integrity()?));
+                }
+            }
+        }
+
+        live_content.insert(content_path(store, &entry.integrity));
+    }
+
+    report.packages.sort();
+
+    let content = store.join("content-v2");
+
+    // a fresh store doesn't have any content yet
+    let walker = content
+        .exists()
+        .then(|| jwalk::WalkDir::new(&content).skip_hidden(false));
+
+    for entry in walker.into_iter().flatten() {
+        let entry = entry.into_diagnostic()?;
+
+        if !entry.file_type().is_file() || live_content.contains(&entry.path()) {
+            continue;
+        }
+
+        let metadata = entry.metadata().into_diagnostic()?;
+
+        // content that was just written might belong to an install that is still running
+        if metadata.modified().unwrap_or(UNIX_EPOCH) >= cutoff {
+            continue;
+        }
+
+        if !dry_run {
+            fs::remove_file(entry.path()).map_err(|e| VoltError::WriteFileError {
+                source: e,
+                name: entry.path().display().to_string(),
+            })?;
+        }
+
+        report.content_files += 1;
+        report.freed += metadata.len();
+    }
+
+    if let Ok(unpacked) = fs::read_dir(store.join("unpacked")) {
+        for entry in unpacked.filter_map(Result::ok) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            // unpacked copies are named `<directory name>-<hash>`, temporary ones start with a `.`
+            let kept = name
+                .rsplit_once('-')
+                .map_or(false, |(package, _)| kept_packages.contains(package));
+
+            if kept && !name.starts_with('.') {
+                continue;
+            }
+
+            if !dry_run {
+                fs::remove_dir_all(entry.path()).map_err(|e| VoltError::WriteFileError {
+                    source: e,
+                    name: entry.path().display().to_string(),
+                })?;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// A problem found while verifying the store.
+#[derive(Debug)]
+pub struct Corruption {
+    pub key: String,
+    pub path: Option<String>,
+    pub integrity: Integrity,
+    pub error: String,
+}
+
+/// Re-hash the content of an entry (and every file of its package index) and report anything
+/// that doesn't match its integrity.
+pub fn verify_entry(store: &Path, entry: &StoreEntry) -> Vec<Corruption> {
+    let corruption =
+        |path: Option<&str>, integrity: &Integrity, error: cacache::Error| Corruption {
+            key: entry.key.clone(),
+            path: path.map(str::to_string),
+            integrity: integrity.clone(),
+            error: error.to_string(),
+        };
+
+    let data = match cacache::read_hash_sync(store, &entry.integrity) {
+        Ok(data) => data,
+        Err(e) => return vec![corruption(None, &entry.integrity, e)],
+    };
+
+    if entry.directory_name().is_none() {
+        return vec![];
+    }
+
+    let index = match PackageIndex::read_from_buffer(&data) {
+        Ok(index) => index,
+        Err(e) => {
+            return vec![Corruption {
+                key: entry.key.clone(),
+                path: None,
+                integrity: entry.integrity.clone(),
+                error: e.to_string(),
+            }]
+        }
+    };
+
+    index
+        .files
+        .iter()
+        .filter_map(|file| {
+            let integrity = file.integrity().ok()?;
+
+            cacache::read_hash_sync(store, &integrity)
+                .err()
+                .map(|e| corruption(Some(&file.path), &integrity, e))
+        })
+        .collect()
+}
+
+/// Remove a corrupted entry from the store, so that the package is downloaded again on the next
+/// install.
+pub fn remove_corrupted(store: &Path, corruption: &Corruption) -> miette::Result<()> {
+    cacache::remove_sync(store, &corruption.key).into_diagnostic()?;
+
+    // `cacache` skips writing content that is already present, even if it's corrupted
+    match fs::remove_file(content_path(store, &corruption.integrity)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(VoltError::WriteFileError {
+            source: e,
+            name: content_path(store, &corruption.integrity)
+                .display()
+                .to_string(),
+        }
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        content_path, link_from_store, link_unpacked, prune, register_project, remove_corrupted,
+        save_unpacked, store_entries, unpacked_names, unpacked_path, verify_entry, IndexedFile,
+        IndexedSymlink, LinkStrategy, Linker, PackageIndex,
+    };
+    use crate::core::{io::unpack_tarball, utils::voltapi::VoltPackage};
+
+    use ssri::Integrity;
+    use tempfile::tempdir;
+
+    use std::{
+        collections::HashMap,
+        fs,
+        path::Path,
+        thread,
+        time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn content_path_matches_cacache() {
+        let dir = tempdir().unwrap();
+
+        let integrity = cacache::write_hash_sync(dir.path(), b"hello world").unwrap();
+
+        assert_eq!(
+            fs::read(content_path(dir.path(), &integrity)).unwrap(),
+            b"hello world"
+        );
+
+        let integrity: Integrity = "sha256-uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek="
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            content_path(Path::new("store"), &integrity),
+            Path::new("store/content-v2/sha256/b9/4d")
+                .join("27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")
+        );
+    }
+
+    #[test]
+    fn every_strategy_materializes_the_file() {
+        for strategy in [
+            LinkStrategy::Hardlink,
+            LinkStrategy::Reflink,
+            LinkStrategy::Copy,
+        ] {
+            let dir = tempdir().unwrap();
+
+            let source = dir.path().join("source");
+            let destination = dir.path().join("destination");
+
+            fs::write(&source, b"contents").unwrap();
+            fs::write(&destination, b"stale").unwrap();
+
+            Linker::new(strategy).link(&source, &destination).unwrap();
+
+            assert_eq!(fs::read(&destination).unwrap(), b"contents");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hardlinks_share_the_inode() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempdir().unwrap();
+
+        let source = dir.path().join("source");
+        let destination = dir.path().join("destination");
+
+        fs::write(&source, b"contents").unwrap();
+
+        let linker = Linker::new(LinkStrategy::Hardlink);
+
+        linker.link(&source, &destination).unwrap();
+
+        assert_eq!(linker.strategy(), LinkStrategy::Hardlink);
+        assert_eq!(
+            fs::metadata(&source).unwrap().ino(),
+            fs::metadata(&destination).unwrap().ino()
+        );
+    }
+
+    #[test]
+    fn index_round_trips_through_the_store() {
+        let dir = tempdir().unwrap();
+
+        let integrity = cacache::write_hash_sync(dir.path(), b"module.exports = 1;").unwrap();
+
+        let index = PackageIndex {
+            files: vec![IndexedFile {
+                path: "lib/index.js".to_string(),
+                size: 19,
+                mode: 0o644,
+                integrity: integrity.to_string(),
+            }],
+            symlinks: vec![IndexedSymlink {
+                path: "index.js".to_string(),
+                target: "lib/index.js".to_string(),
+            }],
+        };
+
+        index.write(dir.path(), "pkg-index::a::1.0.0::sha").unwrap();
+
+        let read = PackageIndex::read(dir.path(), "pkg-index::a::1.0.0::sha").unwrap();
+
+        assert_eq!(read, index);
+
+        let package = dir.path().join("node_modules/a");
+
+        read.materialize(dir.path(), &package, &Linker::new(LinkStrategy::Copy))
+            .unwrap();
+
+        assert_eq!(
+            fs::read(package.join("lib/index.js")).unwrap(),
+            b"module.exports = 1;"
+        );
+
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read(package.join("index.js")).unwrap(),
+            b"module.exports = 1;"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hardlinks_never_change_the_mode_of_shared_contents() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let store = dir.path().join("store");
+        let linker = Linker::new(LinkStrategy::Hardlink);
+
+        let integrity = cacache::write_hash_sync(&store, b"#!/usr/bin/env node").unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let plain = dir.path().join("a/index.js");
+        let executable = dir.path().join("b/cli.js");
+
+        fs::create_dir_all(plain.parent().unwrap()).unwrap();
+        fs::create_dir_all(executable.parent().unwrap()).unwrap();
+
+        link_from_store(&store, &integrity, &plain, 0o644, &linker).unwrap();
+        link_from_store(&store, &integrity, &executable, 0o755, &linker).unwrap();
+
+        assert_eq!(mode(&plain), 0o644);
+        assert_eq!(mode(&executable), 0o755);
+        assert_eq!(mode(&content_path(&store, &integrity)), 0o644);
+    }
+
+    #[test]
+    fn prune_keeps_packages_of_registered_projects() {
+        let store = tempdir().unwrap();
+        let linker = Linker::new(LinkStrategy::Copy);
+
+        for id in 0..2 {
+            let index = unpack_tarball(
+                fixture_tarball(id),
+                &store.path().join(format!("fixture-{id}")),
+                store.path(),
+                &linker,
+            )
+            .unwrap();
+
+            index
+                .write(
+                    store.path(),
+                    &format!("pkg-index::fixture-{id}::1.0.0::sha"),
+                )
+                .unwrap();
+        }
+
+        cacache::write_sync(store.path(), "pkg::legacy::1.0.0", b"{}").unwrap();
+
+        thread::sleep(Duration::from_millis(20));
+        let cutoff = SystemTime::now();
+        thread::sleep(Duration::from_millis(20));
+
+        let project = tempdir().unwrap();
+        fs::create_dir_all(project.path().join("node_modules/.volt/fixture-0@1.0.0")).unwrap();
+        register_project(store.path(), project.path()).unwrap();
+
+        let report = prune(store.path(), cutoff, false).unwrap();
+
+        assert_eq!(report.packages, vec!["fixture-1@1.0.0".to_string()]);
+        assert!(report.content_files > 0);
+
+        let entries = store_entries(store.path()).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, "pkg-index::fixture-0::1.0.0::sha");
+        assert!(verify_entry(store.path(), &entries[0]).is_empty());
+    }
+
+    #[test]
+    fn unpacked_copies_link_their_dependencies() {
+        let store = tempdir().unwrap();
+        let project = tempdir().unwrap();
+        let linker = Linker::new(LinkStrategy::Copy);
+
+        let package = |name: &str, version: &str, dependencies: serde_json::Value| {
+            let package: VoltPackage = serde_json::from_value(serde_json::json!({
+                "name": name,
+                "version": version,
+                "optional": false,
+                "integrity": format!("sha512-{name}{version}"),
+                "tarball": "",
+                "dependencies": dependencies,
+            }))
+            .unwrap();
+
+            (format!("{}@{}", name, version), package)
+        };
+
+        let tree = HashMap::from([
+            package("foo", "1.0.0", serde_json::json!({ "@scope/bar": "1.0.0" })),
+            package("@scope/bar", "1.0.0", serde_json::json!({})),
+        ]);
+
+        let names = unpacked_names(&tree);
+
+        // the same package gets another copy when it resolves other dependencies
+        let other = unpacked_names(&HashMap::from([
+            package("foo", "1.0.0", serde_json::json!({ "@scope/bar": "1.1.0" })),
+            package("@scope/bar", "1.1.0", serde_json::json!({})),
+        ]));
+
+        assert_ne!(names["foo@1.0.0"], other["foo@1.0.0"]);
+        assert_eq!(names, unpacked_names(&tree));
+        assert!(names["@scope/bar@1.0.0"].starts_with("@scope+bar@1.0.0-"));
+
+        let bar = unpacked_path(store.path(), &names["@scope/bar@1.0.0"]);
+        let foo = unpacked_path(store.path(), &names["foo@1.0.0"]);
+
+        for (name, unpacked, dependencies) in [
+            ("@scope/bar", &bar, vec![]),
+            ("foo", &foo, vec![("@scope/bar".to_string(), bar.clone())]),
+        ] {
+            let root = project.path().join("extracted").join(name);
+            fs::create_dir_all(&root).unwrap();
+            fs::write(root.join("index.js"), name).unwrap();
+
+            fs::create_dir_all(unpacked.parent().unwrap()).unwrap();
+            save_unpacked(&root, name, &dependencies, unpacked, &linker).unwrap();
+        }
+
+        // whatever a previous install left is replaced by a link to the whole copy
+        let directory = project.path().join("node_modules/.volt/foo@1.0.0");
+        fs::create_dir_all(directory.join("node_modules/foo")).unwrap();
+        fs::write(directory.join("node_modules/foo/stale.js"), "").unwrap();
+
+        link_unpacked(&foo, &directory).unwrap();
+        link_unpacked(&foo, &directory).unwrap();
+
+        assert!(!directory.join("node_modules/foo/stale.js").exists());
+        assert_eq!(
+            fs::read_to_string(directory.join("node_modules/foo/index.js")).unwrap(),
+            "foo"
+        );
+        assert_eq!(
+            fs::read_to_string(directory.join("node_modules/@scope/bar/index.js")).unwrap(),
+            "@scope/bar"
+        );
+    }
+
+    #[test]
+    fn verify_reports_corrupted_files() {
+        let store = tempdir().unwrap();
+
+        let index = unpack_tarball(
+            fixture_tarball(0),
+            &store.path().join("fixture"),
+            store.path(),
+            &Linker::new(LinkStrategy::Copy),
+        )
+        .unwrap();
+
+        index
+            .write(store.path(), "pkg-index::fixture-0::1.0.0::sha")
+            .unwrap();
+
+        let corrupted = &index.files[0];
+        let path = content_path(store.path(), &corrupted.integrity().unwrap());
+
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&path, permissions).unwrap();
+        fs::write(&path, "corrupted").unwrap();
+
+        let entries = store_entries(store.path()).unwrap();
+        let corruptions = verify_entry(store.path(), &entries[0]);
+
+        assert_eq!(corruptions.len(), 1);
+        assert_eq!(
+            corruptions[0].path.as_deref(),
+            Some(corrupted.path.as_str())
+        );
+
+        remove_corrupted(store.path(), &corruptions[0]).unwrap();
+
+        assert!(store_entries(store.path()).unwrap().is_empty());
+        assert!(!path.exists());
+    }
+
+    /// Build the tarball of a fixture package, every package has a few unique files and a
+    /// license shared with every other package.
+    fn fixture_tarball(id: usize) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        let mut files = vec![
+            (
+                "package/package.json".to_string(),
+                format!(r#"{{"name":"fixture-{id}","version":"1.0.0"}}"#),
+            ),
+            ("package/LICENSE".to_string(), "MIT".repeat(300)),
+        ];
+
+        for file in 0..8 {
+            files.push((
+                format!("package/lib/file-{file}.js"),
+                format!("module.exports = {};", id * 8 + file).repeat(50),
+            ));
+        }
+
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+
+        builder.into_inner().unwrap()
+    }
+}
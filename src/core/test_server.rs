@@ -0,0 +1,163 @@
+/*
+    Copyright 2021 Volt Contributors
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+*/
+
+//! A registry standing in for the real one in tests, answering requests with canned responses.
+
+use std::collections::HashMap;
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+/// A request received by the server.
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>, // lowercase names
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}
+
+/// A response with `status` (e.g. `200 OK`) and a JSON `body`.
+pub fn json(status: &str, body: &serde_json::Value) -> String {
+    let body = body.to_string();
+
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+async fn read_request(socket: &mut TcpStream) -> Request {
+    let mut data = vec![];
+    let mut buffer = [0; 4096];
+
+    let head_end = loop {
+        let read = socket.read(&mut buffer).await.unwrap();
+        data.extend_from_slice(&buffer[..read]);
+
+        if let Some(end) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end;
+        }
+
+        assert!(read > 0, "connection closed before the end of the headers");
+    };
+
+    let head = String::from_utf8_lossy(&data[..head_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect::<HashMap<_, _>>();
+
+    let length = headers
+        .get("content-length")
+        .and_then(|length| length.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = data[head_end + 4..].to_vec();
+
+    while body.len() < length {
+        let read = socket.read(&mut buffer).await.unwrap();
+
+        assert!(read > 0, "connection closed before the end of the body");
+
+        body.extend_from_slice(&buffer[..read]);
+    }
+
+    Request {
+        method,
+        path,
+        headers,
+        body,
+    }
+}
+
+/// Answer a connection with each of `responses` in turn, then return the requests received.
+/// The url of the server has no trailing `/`.
+pub async fn serve(responses: Vec<String>) -> (String, JoinHandle<Vec<Request>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let requests = tokio::spawn(async move {
+        let mut requests = vec![];
+
+        for response in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+
+            requests.push(read_request(&mut socket).await);
+
+            socket.write_all(response.as_bytes()).await.unwrap();
+            socket.shutdown().await.unwrap();
+        }
+
+        requests
+    });
+
+    (url, requests)
+}
+
+/// Answer `count` connections, which can arrive in any order, with the response `routes` has for
+/// the path requested, or `404 Not Found`. Returns the requests received like [`serve`].
+pub async fn route(
+    count: usize,
+    routes: HashMap<String, String>,
+) -> (String, JoinHandle<Vec<Request>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let requests = tokio::spawn(async move {
+        let mut requests = vec![];
+
+        for _ in 0..count {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let request = read_request(&mut socket).await;
+
+            let response = routes
+                .get(&request.path)
+                .cloned()
+                .unwrap_or_else(|| json("404 Not Found", &serde_json::json!({})));
+
+            requests.push(request);
+
+            socket.write_all(response.as_bytes()).await.unwrap();
+            socket.shutdown().await.unwrap();
+        }
+
+        requests
+    });
+
+    (url, requests)
+}
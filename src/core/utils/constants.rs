@@ -15,4 +15,4 @@
 */
 
 // pub static PROGRESS_CHARS: &str = "██ ";
-pub static MAX_RETRIES: u8 = 4;
+pub static VOLT_REGISTRY: &str = "http://registry.voltpkg.com";
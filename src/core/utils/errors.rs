@@ -14,28 +14,36 @@
     limitations under the License.
 */
 
-use miette::Diagnostic;
+use oro_diagnostics::{Diagnostic, DiagnosticCategory, Explain, Meta};
+use reqwest::Url;
 use thiserror::Error;
 
+use std::{fmt::Display, path::PathBuf};
+
 #[derive(Debug, Error, Diagnostic)]
 pub enum VoltError {
     // #[error("failed to enable ansi support")]
     // #[diagnostic(code(volt::environment::enable_ansi_support))]
     // EnableAnsiSupport(),
     #[error("failed to detect `{env}`")]
-    #[diagnostic(code(volt::environment::get))]
+    #[label("volt::env::var")]
+    #[category(Misc)]
     EnvironmentError { source: std::io::Error, env: String },
 
     #[error("failed to parse package specification: `{spec}`")]
-    #[diagnostic(code(volt::package_spec::parse))]
-    _PackageSpecificationError { spec: String },
+    #[label("volt::parse::package_spec")]
+    #[category(Parse)]
+    PackageSpecificationError { spec: String },
 
     #[error("failed to detect your home directory")]
-    #[diagnostic(code(volt::environment::home_dir))]
+    #[label("volt::env::home_dir")]
+    #[category(Misc)]
+    #[advice("make sure the `HOME` environment variable is set")]
     GetHomeDirError,
 
     #[error("failed to get the name of the current directory")]
-    #[diagnostic(code(volt::environment::current_dir_name))]
+    #[label("volt::env::current_dir_name")]
+    #[category(Misc)]
     GetCurrentDirNameError,
 
     // #[error("failed to initialize lz4 decoder")]
@@ -45,31 +53,40 @@ pub enum VoltError {
     // #[error("failed to decode lz4 encoded data")]
     // #[diagnostic(code(volt::decode::lz4::decode))]
     // DecodeError(#[source] std::io::Error),
-    #[error("failed to recieve response from the registry")]
-    #[diagnostic(code(volt::network))]
-    NetworkError(isahc::Error),
+    #[error("failed to send a request to {url}")]
+    #[label("volt::net::request")]
+    #[category(Net)]
+    #[advice("check your network connection and the `--registry` and `--proxy` settings")]
+    RequestError { source: reqwest::Error, url: String },
 
     // #[error("failed to recieve byte response")]
     // #[diagnostic(code(volt::network::rec))]
     // NetworkRecError(#[source] std::io::Error),
     #[error("failed to create directory")]
-    #[diagnostic(code(volt::io::create_dir))]
+    #[label("volt::fs::create_dir")]
+    #[category(Fs)]
     _CreateDirError(#[source] std::io::Error),
 
     #[error("GET {url} - 404 - {package_name} was not found in the volt registry, or you don't have the permission to request it.")]
-    #[diagnostic(code(volt::registry::volt::package_not_found))]
+    #[label("volt::net::not_found")]
+    #[category(Net)]
+    #[advice("check the spelling of the package name, or log in if the package is private")]
     PackageNotFound { url: String, package_name: String },
 
     #[error("GET {url} - 429 - Too many requests has been sent to {url} on the volt registry. Please try again later.")]
-    #[diagnostic(code(volt::registry::volt::too_many_requests))]
+    #[label("volt::net::too_many_requests")]
+    #[category(Net)]
+    #[advice("wait a bit, or lower `--network-concurrency`")]
     TooManyRequests { url: String },
 
     #[error("GET {url} - 400 - Bad request. Please try again later.")]
-    #[diagnostic(code(volt::registry::volt::bad_request))]
+    #[label("volt::net::bad_request")]
+    #[category(Net)]
     BadRequest { url: String },
 
-    #[error("GET {url} - {} - An unknown error occured. Please try again later.")]
-    #[diagnostic(code(volt::registry::volt::unknown_error))]
+    #[error("GET {url} - {code} - An unknown error occured. Please try again later.")]
+    #[label("volt::net::unknown_status")]
+    #[category(Net)]
     NetworkUnknownError {
         url: String,
         package_name: String,
@@ -77,62 +94,406 @@ pub enum VoltError {
     },
 
     #[error("failed to parse {hash} integrity hash.")]
-    #[diagnostic(code(volt::integrity::parse))]
+    #[label("volt::integrity::parse")]
+    #[category(Integrity)]
     _HashParseError { hash: String },
 
     #[error("failed to copy bytes to hasher.")]
-    #[diagnostic(code(volt::hasher::copy))]
+    #[label("volt::integrity::hash")]
+    #[category(Integrity)]
     _HasherCopyError(#[source] std::io::Error),
 
     #[error("failed to verify tarball checksum")]
-    #[diagnostic(code(volt::integrity::verify))]
+    #[label("volt::integrity::checksum")]
+    #[category(Integrity)]
+    #[advice("run `volt clean` and install again; if it keeps failing the registry may be serving a corrupted tarball")]
     _ChecksumVerificationError,
 
     #[error("failed to convert integrity into hex")]
-    #[diagnostic(code(volt::integrity::convert))]
+    #[label("volt::integrity::convert")]
+    #[category(Integrity)]
     _IntegrityConversionError,
 
     #[error("failed to deserialize slice to `SpeedyVoltResponse`")]
-    #[diagnostic(code(volt::integrity::convert))]
+    #[label("volt::parse::response")]
+    #[category(Parse)]
     _DeserializeError,
 
     #[error("failed to build request client")]
-    #[diagnostic(code(volt::network::builder))]
-    _RequestBuilderError(#[source] isahc::http::Error),
+    #[label("volt::net::client")]
+    #[category(Net)]
+    ClientBuildError(#[source] reqwest::Error),
 
     #[error("failed to build recieve response text")]
-    #[diagnostic(code(volt::io::rec::text))]
+    #[label("volt::net::response_text")]
+    #[category(Net)]
     _IoTextRecError(#[source] std::io::Error),
 
     #[error("failed to find a hash that matches the specified version requirement: {version}")]
-    #[diagnostic(code(volt::io::rec::text))]
+    #[label("volt::resolve::hash")]
+    #[category(Resolve)]
     _HashLookupError { version: String },
 
-    #[error("failed to find a version that matches the specified version requirement for {name}")]
-    #[diagnostic(code(volt::io::rec::text))]
-    _VersionLookupError { name: String },
+    #[error("no version of {name} matches `{requested}`")]
+    #[label("volt::resolve::version")]
+    #[category(Resolve)]
+    #[advice("check the versions published with `volt info <package> versions`")]
+    VersionNotFound { name: String, requested: String },
+
+    #[error("refusing to extract `{path}`: it escapes the package directory")]
+    #[label("volt::integrity::path_traversal")]
+    #[category(Integrity)]
+    TarballPathTraversal { path: String },
+
+    #[error("refusing to extract `{path}`: it links to `{target}` outside of the package")]
+    #[label("volt::integrity::link_escape")]
+    #[category(Integrity)]
+    TarballLinkEscape { path: String, target: String },
+
+    #[error("failed to extract `{path}`: its hardlink target `{target}` was not extracted")]
+    #[label("volt::integrity::missing_link_target")]
+    #[category(Integrity)]
+    TarballMissingLinkTarget { path: String, target: String },
+
+    #[error("cannot resolve `{spec}` offline: it isn't in the lockfile or the metadata cache")]
+    #[label("volt::resolve::offline_metadata")]
+    #[category(Resolve)]
+    #[advice("run the command again without `--offline` to fetch it")]
+    OfflineMetadataMissing { spec: String },
+
+    #[error("cannot install `{package}` offline: it hasn't been downloaded to the store yet")]
+    #[label("volt::resolve::offline_package")]
+    #[category(Resolve)]
+    #[advice("run the command again without `--offline` to download it")]
+    OfflinePackageMissing { package: String },
+
+    #[error("failed to parse the lockfile at `{path}`")]
+    #[label("volt::parse::lockfile")]
+    #[category(Parse)]
+    #[advice("fix the conflict markers or delete the lockfile to resolve the dependencies again")]
+    LockFileParseError {
+        source: serde_json::Error,
+        path: String,
+    },
+
+    #[error("invalid proxy url `{url}`")]
+    #[label("volt::net::proxy")]
+    #[category(Net)]
+    #[advice("proxies are given as `http://[user:password@]host:port`")]
+    InvalidProxy { source: reqwest::Error, url: String },
+
+    #[error("failed to load the certificate in `{path}`")]
+    #[label("volt::net::certificate")]
+    #[category(Net)]
+    #[advice("certificates and keys must be PEM encoded, keys in PKCS#8 or RSA format")]
+    InvalidCertificate {
+        source: reqwest::Error,
+        path: String,
+    },
 
     #[error("failed to read `{name}`")]
-    #[diagnostic(code(volt::io::file::read))]
+    #[label("volt::fs::read")]
+    #[category(Fs)]
     ReadFileError {
         source: std::io::Error,
         name: String,
     },
 
     #[error("failed to write to `{name}`")]
-    #[diagnostic(code(volt::io::file::write))]
+    #[label("volt::fs::write")]
+    #[category(Fs)]
+    #[advice("check the permissions of the file and of its directory")]
     WriteFileError {
         source: std::io::Error,
         name: String,
     },
 
+    #[error("failed to link `{name}` from the store")]
+    #[label("volt::fs::link")]
+    #[category(Fs)]
+    LinkFileError {
+        source: std::io::Error,
+        name: String,
+    },
+
     // Convert error to `String` instead of having a `source` because `git_config::parser::Error`
     // has a lifetime parameter
     #[error("failed to parse git configuration file: `{error_text}`")]
-    #[diagnostic(code(volt::git::parse))]
+    #[label("volt::parse::git_config")]
+    #[category(Parse)]
     GitConfigParseError { error_text: String },
 
+    #[error("`{spec}` isn't installed in this project")]
+    #[label("volt::resolve::not_installed")]
+    #[category(Resolve)]
+    #[advice("only the packages locked in `volt.lock` are looked at, install the project first")]
+    PackageNotInstalled { spec: String },
+
+    #[error("{} packages are locked at several versions that could be deduplicated: {}", names.len(), names.join(", "))]
+    #[label("volt::resolve::duplicates")]
+    #[category(Resolve)]
+    #[advice("run `volt dedupe` to lock each of them at a single version")]
+    DuplicatePackages { names: Vec<String> },
+
+    #[error("{count} packages are outdated")]
+    #[label("volt::outdated")]
+    #[category(Misc)]
+    #[advice("run `volt update` to update them within their ranges, or `volt update --latest`")]
+    OutdatedPackages { count: usize },
+
+    #[error("{count} vulnerable packages are installed")]
+    #[label("volt::audit::vulnerable")]
+    #[category(Misc)]
+    #[advice("run `volt audit fix` to upgrade them to versions that aren't affected")]
+    VulnerablePackages { count: usize },
+
+    #[error("POST {url} - {code} - the registry didn't answer the security audit")]
+    #[label("volt::net::audit")]
+    #[category(Net)]
+    #[advice("the registry may not support `/-/npm/v1/security/advisories/bulk`, try auditing against https://registry.npmjs.org")]
+    AuditRequestError { url: String, code: String },
+
+    #[error("failed to read your answer")]
+    #[label("volt::env::prompt")]
+    #[category(Misc)]
+    #[advice("prompts need an interactive terminal")]
+    PromptError(#[source] std::io::Error),
+
+    #[error("no NodeJS release matches `{version}`")]
+    #[label("volt::resolve::node_version")]
+    #[category(Resolve)]
+    #[advice("the available releases are listed at https://nodejs.org/dist/")]
+    NodeVersionNotFound { version: String },
+
+    #[error("no NodeJS versions are installed")]
+    #[label("volt::env::no_node_versions")]
+    #[category(Misc)]
+    #[advice("install one with `volt node install <version>`")]
+    NoNodeVersions,
+
+    #[error("NodeJS {version} isn't installed")]
+    #[label("volt::env::node_not_installed")]
+    #[category(Misc)]
+    #[advice("install it with `volt node install <version>`")]
+    NodeVersionNotInstalled { version: String },
+
+    #[error("failed to extract the NodeJS release downloaded from {url}: {reason}")]
+    #[label("volt::integrity::node_archive")]
+    #[category(Integrity)]
+    NodeArchiveError { url: String, reason: String },
+
+    #[error("{method} {url} - {code} - {reason}")]
+    #[label("volt::net::rejected")]
+    #[category(Net)]
+    #[advice("check that your token, set with `--token` or `NPM_TOKEN`, is allowed to change this package")]
+    RegistryRejected {
+        method: String,
+        url: String,
+        code: String,
+        reason: String,
+    },
+
+    #[error("{url} asks for a one-time password")]
+    #[label("volt::net::otp")]
+    #[category(Net)]
+    #[advice("pass the code from your authenticator app with `--otp`")]
+    OtpRequired { url: String },
+
+    #[error("`{otp}` can't be sent as a one-time password")]
+    #[label("volt::net::otp")]
+    #[category(Misc)]
+    #[advice("pass the code from your authenticator app as it's shown, e.g. `--otp 123456`")]
+    InvalidOtp { otp: String },
+
+    #[error("refusing to publish `{name}`: it's marked as private")]
+    #[label("volt::publish::private")]
+    #[category(Misc)]
+    #[advice("remove `\"private\": true` from package.json to publish it")]
+    PrivatePackage { name: String },
+
+    #[error("`{tag}` can't be used as a dist-tag: it's a valid semver range")]
+    #[label("volt::publish::tag")]
+    #[category(Misc)]
+    #[advice("use names that can't be confused with versions, like `next` or `beta`")]
+    InvalidDistTag { tag: String },
+
+    #[error("no version of `{name}` to tag: it isn't the package in the current directory")]
+    #[label("volt::tag::version")]
+    #[category(Misc)]
+    #[advice("name the version to tag, e.g. `volt tag add react@18.0.0 latest`")]
+    DistTagVersionMissing { name: String },
+
+    #[error("the `{script}` script failed: {reason}")]
+    #[label("volt::script::failed")]
+    #[category(Misc)]
+    #[advice("run the script on its own to see what went wrong")]
+    ScriptFailed { script: String, reason: String },
+
     #[error("an unknown error occured.")]
-    #[diagnostic(code(volt::unknown))]
+    #[label("volt::unknown")]
+    #[category(Misc)]
     _UnknownError,
 }
+
+impl Explain for VoltError {
+    fn meta(&self) -> Option<Meta> {
+        use VoltError::*;
+
+        match self {
+            // proxy urls are left out, they can contain credentials
+            RequestError { url, .. }
+            | PackageNotFound { url, .. }
+            | TooManyRequests { url }
+            | BadRequest { url }
+            | NetworkUnknownError { url, .. }
+            | AuditRequestError { url, .. }
+            | RegistryRejected { url, .. }
+            | OtpRequired { url }
+            | NodeArchiveError { url, .. } => Some(Meta::Net {
+                url: Url::parse(url).ok(),
+            }),
+            ReadFileError { name: path, .. }
+            | WriteFileError { name: path, .. }
+            | LinkFileError { name: path, .. }
+            | LockFileParseError { path, .. }
+            | InvalidCertificate { path, .. } => Some(Meta::Fs {
+                path: PathBuf::from(path),
+            }),
+            _ => None,
+        }
+    }
+}
+
+// miette renders the errors, their code and help come from the `oro_diagnostics` attributes
+impl miette::Diagnostic for VoltError {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.label()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.advice()
+            .map(|advice| Box::new(advice) as Box<dyn Display + 'a>)
+    }
+}
+
+impl VoltError {
+    /// Whether the error only sums up what the command already printed, so that its JSON output
+    /// isn't followed by the error.
+    pub fn summarizes_output(&self) -> bool {
+        matches!(
+            self,
+            VoltError::DuplicatePackages { .. }
+                | VoltError::OutdatedPackages { .. }
+                | VoltError::VulnerablePackages { .. }
+        )
+    }
+}
+
+/// The `VoltError` behind `report`, if any.
+pub fn volt_error(report: &miette::Report) -> Option<&VoltError> {
+    report
+        .chain()
+        .find_map(|error| error.downcast_ref::<VoltError>())
+}
+
+/// Category of the error behind `report`, errors that don't come from volt are `Misc`.
+pub fn category(report: &miette::Report) -> DiagnosticCategory {
+    volt_error(report).map_or(DiagnosticCategory::Misc, Diagnostic::category)
+}
+
+/// Name of `category` in the JSON output.
+fn category_name(category: &DiagnosticCategory) -> &'static str {
+    match category {
+        DiagnosticCategory::Misc => "misc",
+        DiagnosticCategory::Net => "net",
+        DiagnosticCategory::Fs => "fs",
+        DiagnosticCategory::Parse => "parse",
+        DiagnosticCategory::Resolve => "resolve",
+        DiagnosticCategory::Integrity => "integrity",
+    }
+}
+
+/// Exit code of volt when it fails with an error of `category`, so that scripts can tell network
+/// failures from resolution failures. 2 is left out, it's what clap exits with on usage errors.
+pub fn exit_code(category: &DiagnosticCategory) -> u8 {
+    match category {
+        DiagnosticCategory::Misc => 1,
+        DiagnosticCategory::Net => 3,
+        DiagnosticCategory::Fs => 4,
+        DiagnosticCategory::Parse => 5,
+        DiagnosticCategory::Resolve => 6,
+        DiagnosticCategory::Integrity => 7,
+    }
+}
+
+/// The JSON printed for an error with `--json`:
+///
+/// ```json
+/// {
+///   "error": {
+///     "code": "volt::net::not_found",
+///     "category": "net",
+///     "exitCode": 3,
+///     "message": "...",
+///     "causes": ["..."],
+///     "advice": "...",
+///     "url": "https://registry.npmjs.org/...",
+///     "path": null
+///   }
+/// }
+/// ```
+pub fn error_json(report: &miette::Report) -> serde_json::Value {
+    let category = category(report);
+
+    let (url, path) = match volt_error(report).and_then(Explain::meta) {
+        Some(Meta::Net { url }) => (url.map(|url| url.to_string()), None),
+        Some(Meta::Fs { path }) => (None, Some(path)),
+        Some(Meta::Parse { path, .. }) => (None, path),
+        None => (None, None),
+    };
+
+    serde_json::json!({
+        "error": {
+            "code": report.code().map(|code| code.to_string()),
+            "category": category_name(&category),
+            "exitCode": exit_code(&category),
+            "message": report.to_string(),
+            "causes": report.chain().skip(1).map(ToString::to_string).collect::<Vec<_>>(),
+            "advice": report.help().map(|help| help.to_string()),
+            "url": url,
+            "path": path,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_category_code_and_url() {
+        let report = miette::Report::from(VoltError::PackageNotFound {
+            url: "https://registry.npmjs.org/nope".to_string(),
+            package_name: "nope".to_string(),
+        });
+
+        assert_eq!(category(&report), DiagnosticCategory::Net);
+
+        let json = error_json(&report);
+
+        assert_eq!(json["error"]["code"], "volt::net::not_found");
+        assert_eq!(json["error"]["category"], "net");
+        assert_eq!(json["error"]["exitCode"], 3);
+        assert_eq!(json["error"]["url"], "https://registry.npmjs.org/nope");
+        assert!(json["error"]["advice"].is_string());
+    }
+
+    #[test]
+    fn other_errors_are_misc() {
+        let report = miette::miette!("something went wrong");
+
+        assert_eq!(category(&report), DiagnosticCategory::Misc);
+        assert_eq!(exit_code(&category(&report)), 1);
+        assert!(error_json(&report)["error"]["code"].is_null());
+    }
+}
@@ -25,34 +25,102 @@ pub mod voltapi;
 
 use crate::{
     cli::VoltConfig,
-    core::{io::extract_tarball, net::fetch_tarball, utils::voltapi::VoltPackage},
+    core::{
+        http::HttpClient,
+        io::{extract_tarball, package_root},
+        net::fetch_tarball,
+        progress::InstallProgress,
+        store::{
+            detach_unpacked, link_unpacked, save_unpacked, unpacked_path, Linker, PackageIndex,
+        },
+        utils::voltapi::VoltPackage,
+    },
 };
 
 use errors::VoltError;
-use futures::TryFutureExt;
 use git_config::file::GitConfig;
 use git_config::parser::parse_from_str;
 use miette::{IntoDiagnostic, Result};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use reqwest::Client;
-use ssri::{Algorithm, Integrity};
+use ssri::Algorithm;
 
-use std::{collections::HashMap, fs::read_to_string, io::Write, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::{self, read_to_string},
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio::sync::Semaphore;
 
 pub struct State {
-    pub http_client: Client,
+    pub http_client: HttpClient,
+    pub linker: Linker,
+    /// Limits how many packages are extracted or linked at the same time
+    pub fs_permits: Arc<Semaphore>,
+    pub progress: InstallProgress,
+    /// Name of the unpacked copy of every package, empty without `--store-unpacked`
+    pub unpacked_names: Arc<HashMap<String, String>>,
 }
 
-pub fn decompress_gzip(gz_data: &[u8]) -> Result<Vec<u8>> {
+/// The unpacked copy of a package in the store, along with the copies of its dependencies.
+#[derive(Clone)]
+struct UnpackedCopy {
+    path: PathBuf,
+    dependencies: Vec<(String, PathBuf)>,
+}
+
+impl UnpackedCopy {
+    fn new(package: &VoltPackage, config: &VoltConfig, state: &State) -> Result<Option<Self>> {
+        let name = match state
+            .unpacked_names
+            .get(&format!("{}@{}", package.name, package.version))
+        {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+
+        let volt_home = config.volt_home()?;
+
+        let dependencies = package
+            .dependencies
+            .iter()
+            .flatten()
+            .filter_map(|(name, version)| {
+                let copy = state.unpacked_names.get(&format!("{}@{}", name, version))?;
+
+                Some((name.clone(), unpacked_path(&volt_home, copy)))
+            })
+            .collect();
+
+        Ok(Some(Self {
+            path: unpacked_path(&volt_home, name),
+            dependencies,
+        }))
+    }
+
+    /// Whether the copy can be linked as is, the copies of its dependencies might have been
+    /// pruned from the store.
+    fn is_complete(&self) -> bool {
+        self.path.exists() && self.dependencies.iter().all(|(_, path)| path.exists())
+    }
+}
+
+/// Size of gzipped data once decompressed.
+pub fn gzip_unpacked_size(gz_data: &[u8]) -> Result<usize> {
     // gzip RFC1952: a valid gzip file has an ISIZE field in the
     // footer, which is a little-endian u32 number representing the
-    // decompressed size. This is ideal for libdeflate, which needs
-    // preallocating the decompressed buffer.
-    let isize = {
-        let isize_start = gz_data.len() - 4;
-        let isize_bytes: [u8; 4] = gz_data[isize_start..].try_into().into_diagnostic()?;
-        u32::from_le_bytes(isize_bytes) as usize
-    };
+    // decompressed size.
+    let isize_start = gz_data
+        .len()
+        .checked_sub(4)
+        .ok_or_else(|| miette::miette!("gzip data is truncated"))?;
+    let isize_bytes: [u8; 4] = gz_data[isize_start..].try_into().into_diagnostic()?;
+
+    Ok(u32::from_le_bytes(isize_bytes) as usize)
+}
+
+pub fn decompress_gzip(gz_data: &[u8]) -> Result<Vec<u8>> {
+    // This is ideal for libdeflate, which needs preallocating the decompressed buffer.
+    let isize = gzip_unpacked_size(gz_data)?;
 
     let mut decompressor = libdeflater::Decompressor::new();
 
@@ -64,6 +132,21 @@ pub fn decompress_gzip(gz_data: &[u8]) -> Result<Vec<u8>> {
     Ok(outbuf)
 }
 
+/// Compress `data` with gzip. The gzip header has no timestamp, so the same data always
+/// compresses to the same bytes.
+pub fn compress_gzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut compressor = libdeflater::Compressor::new(libdeflater::CompressionLvl::best());
+
+    let mut outbuf = vec![0; compressor.gzip_compress_bound(data.len())];
+    let size = compressor
+        .gzip_compress(data, &mut outbuf)
+        .map_err(|e| miette::miette!("failed to compress data: {:?}", e))?;
+
+    outbuf.truncate(size);
+
+    Ok(outbuf)
+}
+
 fn get_git_config_value_if_exists(
     config: &VoltConfig,
     section: &str,
@@ -332,12 +415,8 @@ pub fn _check_peer_dependency(_package_name: &str) -> bool {
 pub fn verify_existing_installation(
     package: &VoltPackage,
     config: &VoltConfig,
-) -> miette::Result<Vec<u8>> {
-    let volt_home = config.volt_home()?;
-
-    let result = cacache::read_sync(volt_home, package.cacache_key()).into_diagnostic()?;
-
-    Ok(result)
+) -> miette::Result<PackageIndex> {
+    PackageIndex::read(&config.volt_home()?, &package.cacache_key())
 }
 
 pub fn verify_checksum(
@@ -402,24 +481,44 @@ pub fn link_dependencies(package: &VoltPackage, config: &VoltConfig) -> miette::
             // node_modules/.volt/accepts@1.2.3/node_modules/ms
             target_link_path.push(&name);
 
+            let link_error = |e| VoltError::LinkFileError {
+                source: e,
+                name: target_link_path.display().to_string(),
+            };
+
+            // installing over a previous install: keep the links that are still right, and
+            // replace the ones to versions that aren't depended on anymore
+            if fs::read_link(&target_link_path)
+                .map_or(false, |target| target == dependency_link_path)
+            {
+                continue;
+            }
+
+            match fs::symlink_metadata(&target_link_path) {
+                // junctions are reported as symlinks too
+                #[cfg(windows)]
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    fs::remove_dir(&target_link_path).map_err(link_error)?
+                }
+                #[cfg(unix)]
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    fs::remove_file(&target_link_path).map_err(link_error)?
+                }
+                Ok(_) => fs::remove_dir_all(&target_link_path).map_err(link_error)?,
+                Err(_) => {}
+            }
+
+            // node_modules/.volt/accepts@1.2.3/node_modules/@scope for scoped dependencies
+            if let Some(parent) = target_link_path.parent() {
+                fs::create_dir_all(parent).map_err(link_error)?;
+            }
+
             #[cfg(windows)]
-            junction::create(&dependency_link_path, &target_link_path).unwrap_or_else(|e| {
-                eprintln!(
-                    "target: {} destination: {}, {}",
-                    target_link_path.display(),
-                    dependency_link_path.display(),
-                    e
-                );
-                std::process::exit(1);
-            });
+            junction::create(&dependency_link_path, &target_link_path).map_err(link_error)?;
 
             #[cfg(unix)]
-            std::os::unix::fs::symlink(dependency_link_path, target_link_path).unwrap_or_else(
-                |e| {
-                    eprintln!("{}", e);
-                    std::process::exit(1);
-                },
-            );
+            std::os::unix::fs::symlink(&dependency_link_path, &target_link_path)
+                .map_err(link_error)?;
         }
     }
 
@@ -428,116 +527,239 @@ pub fn link_dependencies(package: &VoltPackage, config: &VoltConfig) -> miette::
 
 /// Install a JavaScript package.
 pub async fn install_package(config: VoltConfig, package: VoltPackage, state: State) -> Result<()> {
+    let unpacked = UnpackedCopy::new(&package, &config, &state)?;
+
     // Check if the package is already installed
-    match verify_existing_installation(&package, &config) {
-        Ok(value) => {
-            let cas_file_map: Vec<(PathBuf, Integrity)> =
-                serde_json::from_slice::<HashMap<PathBuf, Integrity>>(&value)
-                    .unwrap()
-                    .into_par_iter()
-                    .map(|(k, v)| (k, v))
-                    .collect();
-
-            // Add package's directory to list of created directories
-            let created_directories: Vec<PathBuf> = vec![];
-
-            let mut package_path = config.node_modules()?;
-
-            package_path.push(".volt/");
-            package_path.push(format!("{}@{}", package.name, package.version));
-            package_path.push("node_modules/");
-            package_path.push(package.name.to_string().replace('/', r"\"));
-
-            let mut handles = vec![];
-
-            for chunk in cas_file_map.chunks(6) {
-                let config_instance = config.clone();
-                let package_path_instance = package_path.clone();
-                let mut created_directories_instance = created_directories.clone();
-
-                let chunk_instance = chunk.to_vec();
-
-                handles.push(tokio::task::spawn_blocking(move || {
-                    for (name, hash) in chunk_instance.clone() {
-                        let contents =
-                            cacache::read_hash_sync(config_instance.clone().volt_home()?, &hash)
-                                .into_diagnostic()?;
-
-                        let file_path = package_path_instance.clone().join(&name);
-
-                        // If we haven't created this directory yet, create it
-                        if !created_directories_instance
-                            .clone()
-                            .iter()
-                            .any(|p| p == &file_path)
-                        {
-                            if let Some(value) = name.parent() {
-                                created_directories_instance.push(file_path.to_path_buf());
-                                std::fs::create_dir_all(&package_path_instance.join(value))
-                                    .into_diagnostic()?;
-                            }
-                        }
-
-                        // Write the contents to node_modules
-                        let mut file = std::fs::File::create(&file_path).unwrap();
-
-                        file.write_all(&contents).into_diagnostic()?;
-                    }
-
-                    Ok(()) as Result<()>
-                }));
-            }
+    if let Ok(index) = verify_existing_installation(&package, &config) {
+        let _permit = state.fs_permits.acquire().await.into_diagnostic()?;
+
+        let linked = tokio::task::spawn_blocking({
+            let config = config.clone();
+            let package = package.clone();
+            let linker = state.linker.clone();
+            let unpacked = unpacked.clone();
+
+            move || link_package_from_store(&index, &package, &config, &linker, unpacked.as_ref())
+        })
+        .await
+        .into_diagnostic()?;
+
+        match linked {
+            Ok(dependencies_linked) => {
+                state.progress.cache_hit();
+
+                if !dependencies_linked {
+                    link_dependencies(&package, &config)?;
+                }
+                state.progress.linked();
+
+                generate_script(&config, &package);
+                state.progress.scripts_generated();
 
-            for handle in handles {
-                handle
-                    .unwrap_or_else(|e| {
-                        eprintln!("{}", e);
-                        std::process::exit(1);
-                    })
-                    .await
-                    .unwrap_or_else(|e| {
-                        eprintln!("{}", e);
-                        std::process::exit(1);
-                    });
+                return Ok(());
             }
+            Err(e) => {
+                // the store is missing some of the package's contents, download it again
+                tracing::debug!(
+                    "failed to link {} from the store: {:?}",
+                    package.directory_name(),
+                    e
+                );
+            }
+        }
+    }
 
-            link_dependencies(&package, &config)?;
+    if config.offline() {
+        return Err(VoltError::OfflinePackageMissing {
+            package: format!("{}@{}", package.name, package.version),
         }
-        Err(_) => {
-            // fetch the tarball from the registry
-            let response = fetch_tarball(&package, state).await?;
-
-            tokio::task::spawn_blocking({
-                let config = config.clone();
-                let package = package.clone();
-                move || -> Result<()> {
-                    // verify the checksum
-                    // (checksum is valid, calculated checksum)
-                    let (verified, _checksum) = verify_checksum(&response, &package.integrity)?;
-
-                    if verified {
-                        // decompress gzipped response
-                        let decompressed_response = decompress_gzip(&response)?;
-
-                        // extract the tarball
-                        extract_tarball(decompressed_response, &package, &config)?;
-
-                        // generate .bin files
-                        generate_script(&config, &package);
-
-                        // generate symlinks
-                        link_dependencies(&package, &config)?;
-                    } else {
-                        // TODO: handle checksum failure
-                    }
-
-                    Ok(())
+        .into());
+    }
+
+    // fetch the tarball from the registry
+    let response = fetch_tarball(&package, &state).await?;
+
+    // the registry's trees don't carry `dist.unpackedSize`, the gzip footer of the tarball has
+    // its unpacked size
+    let unpacked_size = gzip_unpacked_size(&response)? as u64;
+
+    state
+        .progress
+        .downloaded(response.len() as u64, unpacked_size);
+
+    let _permit = state.fs_permits.acquire().await.into_diagnostic()?;
+
+    tokio::task::spawn_blocking({
+        let config = config.clone();
+        let package = package.clone();
+        let linker = state.linker.clone();
+        let progress = state.progress.clone();
+
+        move || -> Result<()> {
+            // verify the checksum
+            // (checksum is valid, calculated checksum)
+            let (verified, _checksum) = verify_checksum(&response, &package.integrity)?;
+
+            if verified {
+                // decompress gzipped response
+                let decompressed_response = decompress_gzip(&response)?;
+
+                // never extract into an unpacked copy linked by a previous install
+                detach_unpacked(&package_directory(&package, &config)?)?;
+
+                // extract the tarball
+                extract_tarball(decompressed_response, &package, &config, &linker)?;
+
+                if let Some(unpacked) = &unpacked {
+                    save_unpacked(
+                        &package_root(&package, &config)?,
+                        &package.name,
+                        &unpacked.dependencies,
+                        &unpacked.path,
+                        &linker,
+                    )?;
                 }
-            })
-            .await
-            .into_diagnostic()??;
+                progress.extracted(unpacked_size);
+
+                // generate symlinks
+                link_dependencies(&package, &config)?;
+                progress.linked();
+
+                // generate .bin files
+                generate_script(&config, &package);
+                progress.scripts_generated();
+            } else {
+                // TODO: handle checksum failure
+            }
+
+            Ok(())
         }
-    }
+    })
+    .await
+    .into_diagnostic()??;
 
     Ok(())
 }
+
+/// Directory of a package in `node_modules/.volt`.
+fn package_directory(package: &VoltPackage, config: &VoltConfig) -> Result<PathBuf> {
+    Ok(config
+        .node_modules()?
+        .join(VoltConfig::VOLT_HOME)
+        .join(package.directory_name()))
+}
+
+/// Materialize a package that is already in the store into `node_modules/.volt`.
+///
+/// ## Returns
+/// * Whether the package was linked from its unpacked copy, which links its dependencies already
+fn link_package_from_store(
+    index: &PackageIndex,
+    package: &VoltPackage,
+    config: &VoltConfig,
+    linker: &Linker,
+    unpacked: Option<&UnpackedCopy>,
+) -> Result<bool> {
+    let volt_home = config.volt_home()?;
+    let directory = package_directory(package, config)?;
+
+    if let Some(unpacked) = unpacked.filter(|unpacked| unpacked.is_complete()) {
+        link_unpacked(&unpacked.path, &directory)?;
+
+        return Ok(true);
+    }
+
+    detach_unpacked(&directory)?;
+
+    let package_path = package_root(package, config)?;
+
+    index.materialize(&volt_home, &package_path, linker)?;
+
+    if let Some(unpacked) = unpacked {
+        save_unpacked(
+            &package_path,
+            &package.name,
+            &unpacked.dependencies,
+            &unpacked.path,
+            linker,
+        )?;
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::link_dependencies;
+    use crate::cli::VoltConfig;
+    use crate::core::utils::voltapi::VoltPackage;
+    use clap::Parser;
+    use std::fs::{create_dir_all, read_link};
+
+    fn package(name: &str, version: &str, dependencies: &[(&str, &str)]) -> VoltPackage {
+        VoltPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            optional: false,
+            integrity: String::new(),
+            tarball: String::new(),
+            bin: None,
+            scripts: None,
+            dependencies: Some(
+                dependencies
+                    .iter()
+                    .map(|(name, version)| (name.to_string(), version.to_string()))
+                    .collect(),
+            ),
+            peer_dependencies: None,
+            peer_dependencies_meta: None,
+            optional_dependencies: None,
+            overrides: None,
+            engines: None,
+            os: None,
+            cpu: None,
+        }
+    }
+
+    #[test]
+    fn relinks_dependencies_over_a_previous_install() {
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = dir.path().display().to_string();
+        let config = VoltConfig::parse_from(["volt", "--cwd", &cwd]);
+
+        let volt = dir.path().join("node_modules").join(".volt");
+        let installed =
+            |package: &str, name: &str| volt.join(package).join("node_modules").join(name);
+
+        for (package, name) in [
+            ("app@1.0.0", "app"),
+            ("@scope+b@1.0.0", "@scope/b"),
+            ("@scope+b@2.0.0", "@scope/b"),
+            ("c@1.0.0", "c"),
+        ] {
+            create_dir_all(installed(package, name)).unwrap();
+        }
+
+        // install, then install again over it
+        let app = package("app", "1.0.0", &[("@scope/b", "1.0.0"), ("c", "1.0.0")]);
+
+        link_dependencies(&app, &config).unwrap();
+        link_dependencies(&app, &config).unwrap();
+
+        let link = |name: &str| read_link(installed("app@1.0.0", name)).unwrap();
+
+        assert_eq!(link("@scope/b"), installed("@scope+b@1.0.0", "@scope/b"));
+        assert_eq!(link("c"), installed("c@1.0.0", "c"));
+
+        // update @scope/b, with c left behind as a directory by some other tool
+        std::fs::remove_file(installed("app@1.0.0", "c")).unwrap();
+        create_dir_all(installed("app@1.0.0", "c").join("lib")).unwrap();
+
+        let app = package("app", "1.0.0", &[("@scope/b", "2.0.0"), ("c", "1.0.0")]);
+
+        link_dependencies(&app, &config).unwrap();
+
+        assert_eq!(link("@scope/b"), installed("@scope+b@2.0.0", "@scope/b"));
+        assert_eq!(link("c"), installed("c@1.0.0", "c"));
+    }
+}
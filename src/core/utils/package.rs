@@ -290,7 +290,9 @@ pub struct PackageJson {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub bundled_dependencies: Option<NewBundledDeps>,
+    #[serde(rename = "optionalDependencies")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub opt_dependencies: Option<BTreeMap<String, String>>,
     // TODO: overrides
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -306,31 +308,113 @@ pub struct PackageJson {
     pub workspaces: Option<Vec<String>>,
 }
 
-impl PackageJson {
-    pub fn get() -> Result<(Self, PathBuf)> {
-        for parent in std::env::current_dir()
-            .map_err(|e| VoltError::EnvironmentError {
-                env: String::from("CURRENT_DIR"),
-                source: e,
-            })?
-            .ancestors()
-        {
-            let pkg_path = parent.join("package.json");
+/// Edit the package.json at `path` as plain JSON, so that the fields volt doesn't know about and
+/// the order of the fields are kept.
+pub fn edit_package_json(path: &Path, edit: impl FnOnce(&mut serde_json::Value)) -> Result<()> {
+    let data = read_to_string(path).map_err(|e| VoltError::ReadFileError {
+        source: e,
+        name: path.display().to_string(),
+    })?;
 
-            if pkg_path.exists() {
-                let data = read_to_string(&pkg_path).map_err(|e| VoltError::ReadFileError {
-                    source: e,
-                    name: pkg_path.to_str().unwrap().to_string(),
-                })?;
+    let mut package_json: serde_json::Value = serde_json::from_str(&data).into_diagnostic()?;
 
-                return Ok((
-                    serde_json::from_str(data.as_str()).into_diagnostic()?,
-                    pkg_path,
-                ));
+    edit(&mut package_json);
+
+    let mut data = serde_json::to_string_pretty(&package_json).into_diagnostic()?;
+
+    data.push('\n');
+
+    fs::write(path, data).map_err(|e| VoltError::WriteFileError {
+        source: e,
+        name: path.display().to_string(),
+    })?;
+
+    Ok(())
+}
+
+/// The field of `package.json` a dependency of the project is listed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DependencyKind {
+    Dependencies,
+    DevDependencies,
+    OptionalDependencies,
+    PeerDependencies,
+}
+
+impl DependencyKind {
+    /// The field of package.json these dependencies are declared in.
+    pub fn field(self) -> &'static str {
+        match self {
+            DependencyKind::Dependencies => "dependencies",
+            DependencyKind::DevDependencies => "devDependencies",
+            DependencyKind::OptionalDependencies => "optionalDependencies",
+            DependencyKind::PeerDependencies => "peerDependencies",
+        }
+    }
+}
+
+impl PackageJson {
+    /// Every dependency of the project: the field it's listed in, its name and its range.
+    pub fn dependencies_by_kind(&self) -> Vec<(DependencyKind, &str, &str)> {
+        [
+            (DependencyKind::Dependencies, &self.dependencies),
+            (DependencyKind::DevDependencies, &self.dev_dependencies),
+            (DependencyKind::OptionalDependencies, &self.opt_dependencies),
+        ]
+        .into_iter()
+        .flat_map(|(kind, dependencies)| {
+            dependencies
+                .iter()
+                .flatten()
+                .map(move |(name, range)| (kind, name.as_str(), range.as_str()))
+        })
+        .collect()
+    }
+
+    /// The directories of the workspaces of the project located in `root`, the patterns in
+    /// `workspaces` can use `*` to match any directory name.
+    pub fn workspace_dirs(&self, root: &Path) -> Vec<PathBuf> {
+        let mut dirs = vec![];
+
+        for pattern in self.workspaces.iter().flatten() {
+            let mut matches = vec![root.to_path_buf()];
+
+            for component in pattern.trim_start_matches("./").split('/') {
+                if !component.contains('*') {
+                    matches = matches.into_iter().map(|dir| dir.join(component)).collect();
+                    continue;
+                }
+
+                let component = match regex::Regex::new(&format!(
+                    "^{}$",
+                    regex::escape(component).replace("\\*", ".*")
+                )) {
+                    Ok(component) => component,
+                    Err(_) => continue,
+                };
+
+                matches = matches
+                    .iter()
+                    .filter_map(|dir| fs::read_dir(dir).ok())
+                    .flatten()
+                    .flatten()
+                    .filter(|entry| entry.path().is_dir())
+                    .filter(|entry| component.is_match(&entry.file_name().to_string_lossy()))
+                    .map(|entry| entry.path())
+                    .collect();
             }
+
+            matches.sort();
+
+            dirs.extend(
+                matches
+                    .into_iter()
+                    .filter(|dir| dir.join("package.json").is_file()),
+            );
         }
 
-        miette::bail!("No package.json found!");
+        dirs
     }
 
     pub fn get_from_dir(from: &Path) -> Result<(Self, PathBuf)> {
@@ -414,3 +498,41 @@ impl PackageJson {
     //     }
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PackageJson;
+    use std::fs::{create_dir_all, write};
+
+    #[test]
+    fn finds_workspace_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        for workspace in ["packages/a", "packages/b", "packages/empty", "tools/cli"] {
+            create_dir_all(root.join(workspace)).unwrap();
+        }
+
+        for workspace in ["packages/b", "packages/a", "tools/cli"] {
+            write(root.join(workspace).join("package.json"), "{}").unwrap();
+        }
+
+        let package = PackageJson {
+            workspaces: Some(vec![
+                "packages/*".to_string(),
+                "./tools/cli".to_string(),
+                "missing/*".to_string(),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            package.workspace_dirs(root),
+            vec![
+                root.join("packages/a"),
+                root.join("packages/b"),
+                root.join("tools/cli"),
+            ]
+        );
+    }
+}
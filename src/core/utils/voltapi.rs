@@ -11,6 +11,8 @@
     limitations under the License.
 */
 
+use crate::core::store::PACKAGE_INDEX_PREFIX;
+
 use serde::{Deserialize, Serialize};
 use speedy::{Readable, Writable};
 use std::collections::HashMap;
@@ -49,7 +51,10 @@ impl VoltPackage {
     }
 
     pub fn cacache_key(&self) -> String {
-        format!("pkg::{}::{}::{}", self.name, self.version, self.integrity)
+        format!(
+            "{}{}::{}::{}",
+            PACKAGE_INDEX_PREFIX, self.name, self.version, self.integrity
+        )
     }
 }
 
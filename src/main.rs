@@ -23,23 +23,53 @@ mod cli;
 mod commands;
 mod core;
 
-use std::{str::FromStr, time::Instant};
+use std::{process::ExitCode, str::FromStr, time::Instant};
 
-use tracing::Level;
+use miette::MietteHandlerOpts;
 use tracing_subscriber::EnvFilter;
 
-use crate::cli::{VoltCli, VoltCommand};
+use crate::{
+    cli::{VoltCli, VoltCommand},
+    core::{
+        io::{write, write_debug},
+        utils::errors::{category, error_json, exit_code, volt_error, VoltError},
+    },
+};
 
 //#[tokio::main(worker_threads = 6)]
 //#[tokio::main(flavor = "current_thread")]
-fn main() -> miette::Result<()> {
+fn main() -> ExitCode {
+    let app = VoltCli::new();
+    let meta = app.config.meta();
+
+    if meta.no_color {
+        colored::control::set_override(false);
+
+        miette::set_hook(Box::new(|_| {
+            Box::new(MietteHandlerOpts::new().color(false).build())
+        }))
+        .ok();
+    }
+
     let body = async {
+        // `RUST_LOG` takes precedence over the output flags
+        let level = if meta.silent {
+            "volt=error"
+        } else if meta.debug {
+            "volt=trace"
+        } else if meta.verbose {
+            "volt=debug"
+        } else {
+            "volt=info"
+        };
+
         tracing_subscriber::fmt()
-            .with_max_level(Level::TRACE)
             .with_env_filter(
                 EnvFilter::try_from_default_env()
-                    .unwrap_or_else(|_| EnvFilter::from_str("volt=info").unwrap()),
+                    .unwrap_or_else(|_| EnvFilter::from_str(level).unwrap()),
             )
+            .with_ansi(!meta.no_color)
+            .with_writer(std::io::stderr)
             .without_time()
             .init();
 
@@ -47,25 +77,45 @@ fn main() -> miette::Result<()> {
             core::utils::enable_ansi_support().unwrap();
         }
 
-        let start = Instant::now();
+        write_debug(&format!("{:#?}", app.config), &meta);
 
-        let app = VoltCli::new();
+        let start = Instant::now();
 
         app.cmd.exec(app.config).await?;
 
-        println!("Finished in {:.2}s", start.elapsed().as_secs_f32());
+        write(
+            &format!("Finished in {:.2}s", start.elapsed().as_secs_f32()),
+            &meta,
+        );
 
         Ok(())
     };
 
-    tokio::runtime::Builder::new_multi_thread()
+    let result: miette::Result<()> = tokio::runtime::Builder::new_multi_thread()
         .worker_threads(6)
         .max_blocking_threads(6)
         .thread_name("volt")
         .enable_all()
         .build()
         .expect("Failed to build the runtime")
-        .block_on(body)
+        .block_on(body);
+
+    if let Err(report) = result {
+        let category = category(&report);
+
+        // tools reading `--json` output get the error as JSON too, unless the output of the
+        // command already describes the failure
+        if !meta.json {
+            eprintln!("{:?}", report);
+        } else if !volt_error(&report).map_or(false, VoltError::summarizes_output) {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&error_json(&report)).unwrap()
+            );
+        }
+
+        return ExitCode::from(exit_code(&category));
+    }
 
     /*
      *tokio::runtime::Builder::new_multi_thread()
@@ -75,4 +125,6 @@ fn main() -> miette::Result<()> {
      *    .expect("Failed building the Runtime")
      *    .block_on(body)
      */
+
+    ExitCode::SUCCESS
 }